@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Health of anything that can be attacked
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(100.0)
+    }
+}
+
+/// Attack stats for units that can fight
+#[derive(Component, Debug, Clone)]
+pub struct AttackStats {
+    pub damage: f32,   // Damage per hit before the combat skill is applied
+    pub range: i32,    // Reach in grid cells
+    pub cooldown: f32, // Seconds between hits
+}
+
+impl Default for AttackStats {
+    fn default() -> Self {
+        Self {
+            damage: 10.0,
+            range: 1,
+            cooldown: 1.0,
+        }
+    }
+}
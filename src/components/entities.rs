@@ -2,11 +2,14 @@ use bevy::app::{App, Plugin};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::components::inventory::{Inventory, InventorySettings};
+use crate::components::combat::{AttackStats, Health};
+use crate::components::inventory::{Inventory, Stockpile, StorageFilter};
+use crate::components::items::ItemId;
 use crate::components::movement::{Collider, Movable, MoveTarget};
 use crate::components::resources::ResourceNode;
 use crate::components::skills::{SkillProgression, Skills};
-use crate::components::unit::Selectable;
+use crate::components::unit::{Owner, Selectable, Unit, UnitType};
+use crate::components::wildlife::{Creature, CreatureKind, Temperament};
 
 /// Plugin for entities in the game.
 pub struct EntitiesPlugin;
@@ -14,21 +17,184 @@ pub struct EntitiesPlugin;
 #[derive(Default, Component)]
 pub struct Character;
 
+/// Workers gather resources and construct buildings.
+#[derive(Default, Component)]
+pub struct Worker;
+
+/// Warriors fight, but can't gather or build.
+#[derive(Default, Component)]
+pub struct Warrior;
+
+#[derive(Default, Bundle, LdtkEntity)]
+struct WorkerBundle {
+    unit: Unit,
+    character: Character,
+    worker: Worker,
+    #[with(owner_from_field)]
+    owner: Owner,
+    selectable: Selectable,
+    collider: Collider,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+    #[grid_coords]
+    grid_coords: GridCoords,
+    #[with(worker_movable)]
+    movable: Movable,
+    move_target: MoveTarget,
+    #[with(worker_inventory)]
+    inventory: Inventory,
+    #[with(worker_skills)]
+    skills: Skills,
+    skill_progression: SkillProgression,
+    #[with(worker_health)]
+    health: Health,
+}
+
 #[derive(Default, Bundle, LdtkEntity)]
-struct CharacterBundle {
+struct WarriorBundle {
+    unit: Unit,
     character: Character,
+    warrior: Warrior,
+    #[with(owner_from_field)]
+    owner: Owner,
+    selectable: Selectable,
+    collider: Collider,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+    #[grid_coords]
+    grid_coords: GridCoords,
+    #[with(warrior_movable)]
+    movable: Movable,
+    move_target: MoveTarget,
+    #[with(warrior_inventory)]
+    inventory: Inventory,
+    #[with(warrior_skills)]
+    skills: Skills,
+    skill_progression: SkillProgression,
+    #[with(warrior_health)]
+    health: Health,
+    #[with(warrior_attack_stats)]
+    attack_stats: AttackStats,
+}
+
+/// Reads the optional "Owner" field, entities without one belong to the player
+fn owner_from_field(entity_instance: &EntityInstance) -> Owner {
+    Owner(
+        entity_instance
+            .get_int_field("Owner")
+            .map(|owner| *owner as u8)
+            .unwrap_or_default(),
+    )
+}
+
+fn worker_movable(_: &EntityInstance) -> Movable {
+    UnitType::Worker.movable()
+}
+
+fn worker_inventory(_: &EntityInstance) -> Inventory {
+    UnitType::Worker.inventory()
+}
+
+fn worker_skills(_: &EntityInstance) -> Skills {
+    UnitType::Worker.base_skills()
+}
+
+fn worker_health(_: &EntityInstance) -> Health {
+    UnitType::Worker.health()
+}
+
+fn warrior_movable(_: &EntityInstance) -> Movable {
+    UnitType::Warrior.movable()
+}
+
+fn warrior_inventory(_: &EntityInstance) -> Inventory {
+    UnitType::Warrior.inventory()
+}
+
+fn warrior_skills(_: &EntityInstance) -> Skills {
+    UnitType::Warrior.base_skills()
+}
+
+fn warrior_health(_: &EntityInstance) -> Health {
+    UnitType::Warrior.health()
+}
+
+fn warrior_attack_stats(_: &EntityInstance) -> AttackStats {
+    UnitType::Warrior.attack_stats().unwrap_or_default()
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+struct CreatureBundle {
+    #[with(creature_from_fields)]
+    creature: Creature,
+    #[with(neutral_owner)]
+    owner: Owner,
     selectable: Selectable,
     collider: Collider,
     #[sprite_sheet]
     sprite_sheet: Sprite,
     #[grid_coords]
     grid_coords: GridCoords,
+    #[with(creature_movable)]
     movable: Movable,
     move_target: MoveTarget,
+    #[with(creature_loot)]
     inventory: Inventory,
-    inventory_settings: InventorySettings,
+    #[with(creature_skills)]
     skills: Skills,
     skill_progression: SkillProgression,
+    #[with(creature_health)]
+    health: Health,
+    #[with(creature_attack_stats)]
+    attack_stats: AttackStats,
+}
+
+/// The creature's kind comes from the entity identifier, and the optional
+/// "Temperament" and "LeashRadius" fields override what the kind does by default
+fn creature_from_fields(entity_instance: &EntityInstance) -> Creature {
+    let mut creature = Creature::new(creature_kind(entity_instance));
+
+    if let Some(temperament) = entity_instance
+        .get_enum_field("Temperament")
+        .ok()
+        .and_then(|name| Temperament::from_name(name))
+    {
+        creature.temperament = temperament;
+    }
+    if let Ok(radius) = entity_instance.get_int_field("LeashRadius") {
+        if *radius > 0 {
+            creature.leash_radius = *radius;
+        }
+    }
+    creature
+}
+
+fn creature_kind(entity_instance: &EntityInstance) -> CreatureKind {
+    CreatureKind::from_identifier(&entity_instance.identifier).unwrap_or_default()
+}
+
+fn neutral_owner(_: &EntityInstance) -> Owner {
+    Owner::NEUTRAL
+}
+
+fn creature_movable(entity_instance: &EntityInstance) -> Movable {
+    creature_kind(entity_instance).movable()
+}
+
+fn creature_loot(entity_instance: &EntityInstance) -> Inventory {
+    creature_kind(entity_instance).loot()
+}
+
+fn creature_skills(entity_instance: &EntityInstance) -> Skills {
+    creature_kind(entity_instance).base_skills()
+}
+
+fn creature_health(entity_instance: &EntityInstance) -> Health {
+    creature_kind(entity_instance).health()
+}
+
+fn creature_attack_stats(entity_instance: &EntityInstance) -> AttackStats {
+    creature_kind(entity_instance).attack_stats()
 }
 
 #[derive(Default, Component)]
@@ -67,13 +233,53 @@ pub struct Chest;
 #[derive(Default, Bundle, LdtkEntity)]
 struct ChestBundle {
     chest: Chest,
+    #[with(owner_from_field)]
+    owner: Owner,
     selectable: Selectable,
     #[sprite_sheet]
     sprite_sheet: Sprite,
     #[grid_coords]
     grid_coords: GridCoords,
+    #[with(chest_inventory)]
     inventory: Inventory,
-    inventory_settings: InventorySettings,
+    stockpile: Stockpile,
+    #[with(chest_storage_filter)]
+    storage_filter: StorageFilter,
+}
+
+/// Slots in a chest without a "Capacity" field.
+const DEFAULT_CHEST_CAPACITY: usize = 4;
+
+/// Reads the optional "Capacity" field for the number of slots
+fn chest_inventory(entity_instance: &EntityInstance) -> Inventory {
+    let capacity = entity_instance
+        .get_int_field("Capacity")
+        .ok()
+        .filter(|capacity| **capacity > 0)
+        .map(|capacity| *capacity as usize)
+        .unwrap_or(DEFAULT_CHEST_CAPACITY);
+
+    Inventory::new(capacity)
+}
+
+/// Reads the optional "Accepts" or "Rejects" item id lists, chests take everything by default
+fn chest_storage_filter(entity_instance: &EntityInstance) -> StorageFilter {
+    let item_ids = |identifier: &str| -> Option<Vec<ItemId>> {
+        let ids: Vec<ItemId> = entity_instance
+            .get_maybe_strings_field(identifier)
+            .ok()?
+            .iter()
+            .flatten()
+            .map(|id| ItemId(id.clone().into()))
+            .collect();
+        (!ids.is_empty()).then_some(ids)
+    };
+
+    if let Some(accepted) = item_ids("Accepts") {
+        StorageFilter::Accept(accepted)
+    } else {
+        StorageFilter::Reject(item_ids("Rejects").unwrap_or_default())
+    }
 }
 
 #[derive(Default, Component)]
@@ -145,7 +351,13 @@ struct WaterBundle {
 
 impl Plugin for EntitiesPlugin {
     fn build(&self, app: &mut App) {
-        app.register_ldtk_entity::<CharacterBundle>("Character")
+        // Characters placed before the unit types existed are workers
+        app.register_ldtk_entity::<WorkerBundle>("Character")
+            .register_ldtk_entity::<WorkerBundle>("Worker")
+            .register_ldtk_entity::<WarriorBundle>("Warrior")
+            .register_ldtk_entity::<CreatureBundle>("Deer")
+            .register_ldtk_entity::<CreatureBundle>("Boar")
+            .register_ldtk_entity::<CreatureBundle>("Wolf")
             .register_ldtk_entity::<MineBundle>("Mine")
             .register_ldtk_entity::<QuarryBundle>("Quarry")
             .register_ldtk_entity::<ChestBundle>("Chest")
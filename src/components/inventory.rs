@@ -1,24 +1,21 @@
+use crate::components::items::{ItemId, ItemRegistry};
 use bevy::prelude::*;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ResourceType {
-    Gold,
-    Wood,
-    Stone,
-}
+use serde::{Deserialize, Serialize};
 
 // Represents a stack of items in an inventory slot
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct InventorySlot {
-    pub resource_type: ResourceType,
+    pub item: ItemId,
     pub quantity: u32,
 }
 
 // Entity's inventory containing multiple slots
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Inventory {
     pub slots: Vec<Option<InventorySlot>>,
     pub max_slots: usize,
+    // Heaviest load a unit can carry, chests only care about slots
+    pub max_weight: Option<f32>,
 }
 
 // You'll need to implement Default for Inventory to make this work:
@@ -28,15 +25,44 @@ impl Default for Inventory {
     }
 }
 
-// Maximum capacity for different entity types
-#[derive(Component, Debug)]
-pub struct InventorySettings {
-    pub max_stack_size: u32,
+// Marks an inventory whose contents count towards the shared stockpile
+#[derive(Component, Debug, Default)]
+pub struct Stockpile;
+
+// Which items a storage inventory will take, the default takes everything
+#[derive(Component, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageFilter {
+    // Only these items
+    Accept(Vec<ItemId>),
+    // Everything except these items
+    Reject(Vec<ItemId>),
 }
 
-impl Default for InventorySettings {
+impl Default for StorageFilter {
     fn default() -> Self {
-        Self { max_stack_size: 10 }
+        StorageFilter::Reject(Vec::new())
+    }
+}
+
+impl StorageFilter {
+    pub fn allows(&self, item: &ItemId) -> bool {
+        match self {
+            StorageFilter::Accept(accepted) => accepted.contains(item),
+            StorageFilter::Reject(rejected) => !rejected.contains(item),
+        }
+    }
+
+    // Flip whether an item is allowed, keeping the current kind of list
+    pub fn toggle(&mut self, item: &ItemId) {
+        let list = match self {
+            StorageFilter::Accept(list) | StorageFilter::Reject(list) => list,
+        };
+
+        if let Some(index) = list.iter().position(|listed| listed == item) {
+            list.remove(index);
+        } else {
+            list.push(item.clone());
+        }
     }
 }
 
@@ -46,26 +72,35 @@ impl Inventory {
         for _ in 0..max_slots {
             slots.push(None);
         }
-        Self { slots, max_slots }
+        Self {
+            slots,
+            max_slots,
+            max_weight: None,
+        }
     }
 
-    // Add resources to inventory, returns amount that couldn't fit
-    pub fn add_resource(
-        &mut self,
-        resource_type: ResourceType,
-        quantity: u32,
-        max_stack: u32,
-    ) -> u32 {
+    pub fn with_max_weight(mut self, max_weight: f32) -> Self {
+        self.max_weight = Some(max_weight);
+        self
+    }
+
+    // Add items to inventory, returns amount that couldn't fit
+    pub fn add_item(&mut self, item: &ItemId, quantity: u32, items: &ItemRegistry) -> u32 {
+        let max_stack = items.max_stack(item);
         let mut remaining = quantity;
 
-        // First fill existing stacks of same resource type
+        // Only take as many as the weight limit allows
+        let too_heavy = remaining - self.fits_by_weight(item, remaining, items);
+        remaining -= too_heavy;
+
+        // First fill existing stacks of the same item
         for slot in &mut self.slots {
             if remaining == 0 {
                 break;
             }
 
             if let Some(inv_slot) = slot {
-                if inv_slot.resource_type == resource_type && inv_slot.quantity < max_stack {
+                if inv_slot.item == *item && inv_slot.quantity < max_stack {
                     let can_add = max_stack - inv_slot.quantity;
                     let to_add = remaining.min(can_add);
 
@@ -75,7 +110,7 @@ impl Inventory {
             }
         }
 
-        // If we still have resources, try to find empty slots
+        // If we still have items, try to find empty slots
         if remaining > 0 {
             for slot in &mut self.slots {
                 if remaining == 0 {
@@ -85,7 +120,7 @@ impl Inventory {
                 if slot.is_none() {
                     let to_add = remaining.min(max_stack);
                     *slot = Some(InventorySlot {
-                        resource_type,
+                        item: item.clone(),
                         quantity: to_add,
                     });
                     remaining -= to_add;
@@ -93,11 +128,150 @@ impl Inventory {
             }
         }
 
-        remaining
+        remaining + too_heavy
     }
 
-    // Remove resources from inventory, returns amount actually removed
-    pub fn remove_resource(&mut self, resource_type: ResourceType, quantity: u32) -> u32 {
+    // How many of the given quantity the weight limit leaves room for
+    fn fits_by_weight(&self, item: &ItemId, quantity: u32, items: &ItemRegistry) -> u32 {
+        let Some(max_weight) = self.max_weight else {
+            return quantity;
+        };
+
+        let item_weight = items.weight(item);
+        if item_weight <= 0.0 {
+            return quantity;
+        }
+
+        let free_weight = (max_weight - self.total_weight(items)).max(0.0);
+        // Small epsilon so rounding doesn't reject an exact fit
+        let fits = ((free_weight + 0.001) / item_weight).floor() as u32;
+        quantity.min(fits)
+    }
+
+    // How many more of an item would fit, by both slots and weight
+    pub fn room_for(&self, item: &ItemId, items: &ItemRegistry) -> u32 {
+        let max_stack = items.max_stack(item);
+        let by_slots: u32 = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                None => max_stack,
+                Some(inv_slot) if inv_slot.item == *item => {
+                    max_stack.saturating_sub(inv_slot.quantity)
+                }
+                Some(_) => 0,
+            })
+            .sum();
+
+        self.fits_by_weight(item, by_slots, items)
+    }
+
+    // Merge partial stacks of the same item and move empty slots to the end
+    pub fn compact(&mut self, items: &ItemRegistry) {
+        let mut compacted: Vec<InventorySlot> = Vec::new();
+
+        for stack in self.slots.iter_mut().filter_map(Option::take) {
+            let max_stack = items.max_stack(&stack.item);
+            let mut remaining = stack.quantity;
+
+            for existing in compacted
+                .iter_mut()
+                .filter(|existing| existing.item == stack.item)
+            {
+                let to_add = remaining.min(max_stack.saturating_sub(existing.quantity));
+                existing.quantity += to_add;
+                remaining -= to_add;
+            }
+
+            if remaining > 0 {
+                compacted.push(InventorySlot {
+                    item: stack.item,
+                    quantity: remaining,
+                });
+            }
+        }
+
+        for (slot, stack) in self.slots.iter_mut().zip(compacted) {
+            *slot = Some(stack);
+        }
+    }
+
+    // Compact, then order stacks by category and name with the largest stacks first
+    pub fn sort(&mut self, items: &ItemRegistry) {
+        self.compact(items);
+
+        self.slots.sort_by(|a, b| match (a, b) {
+            (Some(a), Some(b)) => {
+                let category =
+                    |stack: &InventorySlot| items.get(&stack.item).map(|item| item.category);
+                category(a)
+                    .cmp(&category(b))
+                    .then_with(|| items.name(&a.item).cmp(items.name(&b.item)))
+                    .then_with(|| b.quantity.cmp(&a.quantity))
+            }
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+    }
+
+    // Take up to `quantity` items out of a single slot
+    pub fn take_from_slot(&mut self, index: usize, quantity: u32) -> Option<InventorySlot> {
+        let slot = self.slots.get_mut(index)?;
+        let inv_slot = slot.as_mut()?;
+
+        let taken = quantity.min(inv_slot.quantity);
+        if taken == 0 {
+            return None;
+        }
+
+        inv_slot.quantity -= taken;
+        let item = inv_slot.item.clone();
+
+        if inv_slot.quantity == 0 {
+            *slot = None;
+        }
+
+        Some(InventorySlot {
+            item,
+            quantity: taken,
+        })
+    }
+
+    // Put a stack into a single slot, merging with the same item, returns amount that couldn't fit
+    pub fn put_in_slot(&mut self, index: usize, stack: InventorySlot, items: &ItemRegistry) -> u32 {
+        let max_stack = items.max_stack(&stack.item);
+        let fits = self.fits_by_weight(&stack.item, stack.quantity, items);
+
+        let Some(slot) = self.slots.get_mut(index) else {
+            return stack.quantity;
+        };
+
+        let added = match slot {
+            None => {
+                let to_add = fits.min(max_stack);
+                if to_add > 0 {
+                    *slot = Some(InventorySlot {
+                        item: stack.item,
+                        quantity: to_add,
+                    });
+                }
+                to_add
+            }
+            Some(inv_slot) if inv_slot.item == stack.item => {
+                let to_add = fits.min(max_stack.saturating_sub(inv_slot.quantity));
+                inv_slot.quantity += to_add;
+                to_add
+            }
+            // A different item is already there
+            Some(_) => 0,
+        };
+
+        stack.quantity - added
+    }
+
+    // Remove items from inventory, returns amount actually removed
+    pub fn remove_item(&mut self, item: &ItemId, quantity: u32) -> u32 {
         let mut remaining = quantity;
         let mut removed = 0;
 
@@ -107,7 +281,7 @@ impl Inventory {
             }
 
             if let Some(inv_slot) = slot {
-                if inv_slot.resource_type == resource_type {
+                if inv_slot.item == *item {
                     let to_remove = remaining.min(inv_slot.quantity);
 
                     inv_slot.quantity -= to_remove;
@@ -125,13 +299,13 @@ impl Inventory {
         removed
     }
 
-    // Count total of a specific resource
-    pub fn count_resource(&self, resource_type: ResourceType) -> u32 {
+    // Count total of a specific item
+    pub fn count_item(&self, item: &ItemId) -> u32 {
         self.slots
             .iter()
             .filter_map(|slot| {
                 slot.as_ref().and_then(|s| {
-                    if s.resource_type == resource_type {
+                    if s.item == *item {
                         Some(s.quantity)
                     } else {
                         None
@@ -141,6 +315,22 @@ impl Inventory {
             .sum()
     }
 
+    // Combined weight of everything in the inventory
+    pub fn total_weight(&self, items: &ItemRegistry) -> f32 {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|slot| items.weight(&slot.item) * slot.quantity as f32)
+            .sum()
+    }
+
+    // How close the inventory is to its weight limit, from 0.0 to 1.0
+    pub fn load_ratio(&self, items: &ItemRegistry) -> Option<f32> {
+        self.max_weight
+            .filter(|max_weight| *max_weight > 0.0)
+            .map(|max_weight| (self.total_weight(items) / max_weight).min(1.0))
+    }
+
     #[allow(dead_code)]
     // Add a method to get capacity information
     pub fn capacity_info(&self) -> (usize, usize) {
@@ -152,19 +342,19 @@ impl Inventory {
     pub fn transfer_to(
         &mut self,
         other: &mut Inventory,
-        resource_type: ResourceType,
+        item: &ItemId,
         quantity: u32,
-        max_stack: u32,
+        items: &ItemRegistry,
     ) -> u32 {
         // First remove from this inventory
-        let removed = self.remove_resource(resource_type, quantity);
+        let removed = self.remove_item(item, quantity);
 
         // Then add to the other inventory
-        let overflow = other.add_resource(resource_type, removed, max_stack);
+        let overflow = other.add_item(item, removed, items);
 
         // If there was overflow, add it back to original inventory
         if overflow > 0 {
-            self.add_resource(resource_type, overflow, max_stack);
+            self.add_item(item, overflow, items);
         }
 
         // Return how much was successfully transferred
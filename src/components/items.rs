@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Identifier of an item type, matching an `id` in `assets/data/core.items.ron`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ItemId(pub Cow<'static, str>);
+
+impl ItemId {
+    // Items the game logic refers to directly
+    pub const GOLD: ItemId = ItemId(Cow::Borrowed("gold"));
+    pub const WOOD: ItemId = ItemId(Cow::Borrowed("wood"));
+    pub const STONE: ItemId = ItemId(Cow::Borrowed("stone"));
+    pub const PLANKS: ItemId = ItemId(Cow::Borrowed("planks"));
+    pub const BRICKS: ItemId = ItemId(Cow::Borrowed("bricks"));
+    pub const TOOLS: ItemId = ItemId(Cow::Borrowed("tools"));
+    pub const FOOD: ItemId = ItemId(Cow::Borrowed("food"));
+    pub const HIDES: ItemId = ItemId(Cow::Borrowed("hides"));
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Broad kind of an item, used for grouping and storage rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ItemCategory {
+    /// Raw resources gathered from the map
+    Resource,
+    /// Crafted building materials
+    Material,
+    Tool,
+}
+
+/// Definition of an item type, loaded from `assets/data/core.items.ron`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemDefinition {
+    pub id: ItemId,
+    pub name: String,
+    pub icon: String,
+    pub max_stack: u32,
+    pub weight: f32,
+    pub category: ItemCategory,
+}
+
+/// Stack size for items missing from the definitions.
+const DEFAULT_MAX_STACK: u32 = 10;
+
+/// Weight for items missing from the definitions.
+const DEFAULT_WEIGHT: f32 = 1.0;
+
+/// Every item type the game knows about, by id.
+#[derive(Resource, Debug, Default)]
+pub struct ItemRegistry {
+    items: HashMap<ItemId, ItemDefinition>,
+}
+
+impl ItemRegistry {
+    /// Replaces every definition with these
+    pub fn load(&mut self, definitions: &[ItemDefinition]) {
+        self.items = definitions
+            .iter()
+            .map(|item| (item.id.clone(), item.clone()))
+            .collect();
+    }
+
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+
+    /// Every known item id, in a stable order for cycling through in the UI
+    pub fn ids(&self) -> Vec<&ItemId> {
+        let mut ids: Vec<&ItemId> = self.items.keys().collect();
+        ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        ids
+    }
+
+    /// Display name of an item, falling back to its id for unknown items
+    pub fn name<'a>(&'a self, id: &'a ItemId) -> &'a str {
+        self.get(id)
+            .map(|item| item.name.as_str())
+            .unwrap_or(id.as_str())
+    }
+
+    pub fn max_stack(&self, id: &ItemId) -> u32 {
+        self.get(id)
+            .map(|item| item.max_stack)
+            .unwrap_or(DEFAULT_MAX_STACK)
+    }
+
+    pub fn weight(&self, id: &ItemId) -> f32 {
+        self.get(id)
+            .map(|item| item.weight)
+            .unwrap_or(DEFAULT_WEIGHT)
+    }
+}
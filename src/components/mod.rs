@@ -1,7 +1,10 @@
+pub mod combat;
 pub mod entities;
 pub mod inventory;
+pub mod items;
 pub mod movement;
 pub mod resources;
 pub mod skills;
 pub mod ui;
 pub mod unit;
+pub mod wildlife;
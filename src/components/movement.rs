@@ -26,6 +26,8 @@ pub struct Moving {
     pub from: Vec3,
     pub to: Vec3,
     pub progress: f32,
+    /// Progress before the last tick, so the sprite can be drawn between ticks
+    pub previous_progress: f32,
 }
 
 /// A component that indicates the entity is a collisidable object.
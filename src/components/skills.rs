@@ -1,16 +1,74 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One of the skills a unit can train, matching a `skill` in `assets/data/core.skills.ron`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SkillKind {
+    Mining,
+    Woodcutting,
+    Harvesting,
+    Combat,
+    Construction,
+    Crafting,
+}
+
+impl SkillKind {
+    pub const ALL: [SkillKind; 6] = [
+        SkillKind::Mining,
+        SkillKind::Woodcutting,
+        SkillKind::Harvesting,
+        SkillKind::Combat,
+        SkillKind::Construction,
+        SkillKind::Crafting,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkillKind::Mining => "Mining",
+            SkillKind::Woodcutting => "Woodcutting",
+            SkillKind::Harvesting => "Harvesting",
+            SkillKind::Combat => "Combat",
+            SkillKind::Construction => "Construction",
+            SkillKind::Crafting => "Crafting",
+        }
+    }
+}
 
 /// Skills component
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Skills {
-    pub mining: f32,      // Effectiveness at mining
-    pub woodcutting: f32, // Effectiveness at cutting trees
-    pub harvesting: f32,  // Effectiveness at harvesting resources
-    #[allow(dead_code)]
-    pub combat: f32, // Combat effectiveness
+    pub mining: f32,       // Effectiveness at mining
+    pub woodcutting: f32,  // Effectiveness at cutting trees
+    pub harvesting: f32,   // Effectiveness at harvesting resources
+    pub combat: f32,       // Combat effectiveness
     pub construction: f32, // Building construction speed
-    #[allow(dead_code)]
-    pub crafting: f32, // Item crafting quality
+    pub crafting: f32,     // Item crafting speed
+}
+
+impl Skills {
+    pub fn get(&self, skill: SkillKind) -> f32 {
+        match skill {
+            SkillKind::Mining => self.mining,
+            SkillKind::Woodcutting => self.woodcutting,
+            SkillKind::Harvesting => self.harvesting,
+            SkillKind::Combat => self.combat,
+            SkillKind::Construction => self.construction,
+            SkillKind::Crafting => self.crafting,
+        }
+    }
+
+    pub fn get_mut(&mut self, skill: SkillKind) -> &mut f32 {
+        match skill {
+            SkillKind::Mining => &mut self.mining,
+            SkillKind::Woodcutting => &mut self.woodcutting,
+            SkillKind::Harvesting => &mut self.harvesting,
+            SkillKind::Combat => &mut self.combat,
+            SkillKind::Construction => &mut self.construction,
+            SkillKind::Crafting => &mut self.crafting,
+        }
+    }
 }
 
 impl Default for Skills {
@@ -26,28 +84,87 @@ impl Default for Skills {
     }
 }
 
+/// Level and XP towards the next level in one skill.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SkillLevel {
+    pub level: u32,
+    pub xp: f32,
+}
+
+impl Default for SkillLevel {
+    fn default() -> Self {
+        Self { level: 1, xp: 0.0 }
+    }
+}
+
 /// Experience gain component
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SkillProgression {
-    pub mining_xp: f32,
-    pub woodcutting_xp: f32,
-    pub harvesting_xp: f32,
-    #[allow(dead_code)]
-    pub combat_xp: f32,
-    pub construction_xp: f32,
-    #[allow(dead_code)]
-    pub crafting_xp: f32,
+    // Skills that haven't gained any XP yet are at level 1
+    pub levels: HashMap<SkillKind, SkillLevel>,
 }
 
-impl Default for SkillProgression {
-    fn default() -> Self {
-        Self {
-            mining_xp: 0.0,
-            woodcutting_xp: 0.0,
-            harvesting_xp: 0.0,
-            combat_xp: 0.0,
-            construction_xp: 0.0,
-            crafting_xp: 0.0,
+impl SkillProgression {
+    pub fn get(&self, skill: SkillKind) -> SkillLevel {
+        self.levels.get(&skill).copied().unwrap_or_default()
+    }
+
+    pub fn level(&self, skill: SkillKind) -> u32 {
+        self.get(skill).level
+    }
+}
+
+/// How much XP each level of a skill costs.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum XpCurve {
+    /// Each level costs `per_level` more than the one before
+    Linear { base: f32, per_level: f32 },
+    /// Each level costs `factor` times the one before
+    Exponential { base: f32, factor: f32 },
+}
+
+impl XpCurve {
+    /// XP needed to go from `level` to the next one
+    pub fn xp_to_next(&self, level: u32) -> f32 {
+        let steps = level.saturating_sub(1);
+        match *self {
+            XpCurve::Linear { base, per_level } => base + per_level * steps as f32,
+            XpCurve::Exponential { base, factor } => base * factor.powi(steps as i32),
         }
     }
 }
+
+/// What a perk does once unlocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PerkEffect {
+    /// Share of gathered yields that come out doubled
+    DoubleYield,
+    /// Extra construction speed
+    BuildSpeed,
+    /// Extra crafting speed
+    CraftSpeed,
+    /// Extra damage dealt
+    Damage,
+}
+
+/// Perk unlocked when a skill reaches a level.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PerkDefinition {
+    pub level: u32,
+    pub name: String,
+    pub effect: PerkEffect,
+    // Size of the effect, 0.25 is 25%
+    pub amount: f32,
+}
+
+/// Definition of a skill's progression, loaded from `assets/data/core.skills.ron`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SkillDefinition {
+    pub skill: SkillKind,
+    pub curve: XpCurve,
+    pub max_level: u32,
+    // Added to the skill's effectiveness on every level up
+    pub gain_per_level: f32,
+    #[serde(default)]
+    pub perks: Vec<PerkDefinition>,
+}
@@ -8,6 +8,15 @@ pub struct EntityInfoPanel;
 #[derive(Component)]
 pub struct EntityNameText;
 
+/// UI, like a menu, that clicks on shouldn't also reach the map underneath.
+/// Needs an `Interaction`, and its buttons `FocusPolicy::Pass`.
+#[derive(Component)]
+pub struct BlocksMapClicks;
+
+/// UI container in the top left for player-wide counters.
+#[derive(Component)]
+pub struct HudPanel;
+
 /// UI component for displaying the entity's name.
 #[derive(Component, Debug, Clone, PartialEq)]
 pub struct UiState {
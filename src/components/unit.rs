@@ -1,4 +1,11 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::combat::{AttackStats, Health};
+use crate::components::inventory::Inventory;
+use crate::components::items::ItemId;
+use crate::components::movement::Movable;
+use crate::components::skills::Skills;
 
 /// A unit component, a character or npc.
 #[derive(Component, Default)]
@@ -17,3 +24,94 @@ pub struct Selected;
 pub struct SelectionRing {
     pub owner: Entity, // Add this to track which entity this ring belongs to
 }
+
+/// The player that owns a unit or building.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Owner(pub u8);
+
+impl Owner {
+    /// Wildlife, which belongs to nobody and is fair game for everyone
+    pub const NEUTRAL: Owner = Owner(u8::MAX);
+}
+
+/// The kinds of unit that can be trained in a house.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitType {
+    Worker,
+    Warrior,
+}
+
+impl UnitType {
+    pub fn get_cost(&self) -> Vec<(ItemId, u32)> {
+        match self {
+            UnitType::Worker => vec![(ItemId::GOLD, 2)],
+            UnitType::Warrior => vec![(ItemId::GOLD, 5)],
+        }
+    }
+
+    /// Seconds needed to train this unit
+    pub fn training_time(&self) -> f32 {
+        match self {
+            UnitType::Worker => 8.0,
+            UnitType::Warrior => 12.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitType::Worker => "Worker",
+            UnitType::Warrior => "Warrior",
+        }
+    }
+
+    pub fn sprite_path(&self) -> &'static str {
+        match self {
+            UnitType::Worker => "worker.gif",
+            UnitType::Warrior => "warrior.gif",
+        }
+    }
+
+    /// Starting skills, workers are good with tools and warriors with weapons
+    pub fn base_skills(&self) -> Skills {
+        match self {
+            UnitType::Worker => Skills::default(),
+            UnitType::Warrior => Skills {
+                mining: 0.5,
+                woodcutting: 0.5,
+                harvesting: 0.5,
+                combat: 2.0,
+                construction: 0.5,
+                crafting: 0.5,
+            },
+        }
+    }
+
+    pub fn movable(&self) -> Movable {
+        match self {
+            UnitType::Worker => Movable { speed: 3.0 },
+            UnitType::Warrior => Movable { speed: 2.5 },
+        }
+    }
+
+    pub fn inventory(&self) -> Inventory {
+        match self {
+            UnitType::Worker => Inventory::new(4).with_max_weight(20.0),
+            UnitType::Warrior => Inventory::new(2).with_max_weight(10.0),
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        match self {
+            UnitType::Worker => Health::new(50.0),
+            UnitType::Warrior => Health::new(120.0),
+        }
+    }
+
+    /// Only warriors can fight
+    pub fn attack_stats(&self) -> Option<AttackStats> {
+        match self {
+            UnitType::Worker => None,
+            UnitType::Warrior => Some(AttackStats::default()),
+        }
+    }
+}
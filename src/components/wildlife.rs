@@ -0,0 +1,172 @@
+use crate::components::combat::{AttackStats, Health};
+use crate::components::inventory::{Inventory, InventorySlot};
+use crate::components::items::ItemId;
+use crate::components::movement::Movable;
+use crate::components::skills::Skills;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// How a creature reacts to people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Temperament {
+    /// Runs from whoever attacks it
+    #[default]
+    Timid,
+    /// Fights back when attacked
+    Defensive,
+    /// Attacks anyone who comes near, like a camp guarding its ground
+    Hostile,
+}
+
+impl Temperament {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Timid" => Some(Temperament::Timid),
+            "Defensive" => Some(Temperament::Defensive),
+            "Hostile" => Some(Temperament::Hostile),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Temperament::Timid => "Timid",
+            Temperament::Defensive => "Defensive",
+            Temperament::Hostile => "Hostile",
+        }
+    }
+}
+
+/// The kinds of animal that can be placed in LDtk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CreatureKind {
+    #[default]
+    Deer,
+    Boar,
+    Wolf,
+}
+
+impl CreatureKind {
+    /// The kind for an LDtk entity identifier
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        match identifier {
+            "Deer" => Some(CreatureKind::Deer),
+            "Boar" => Some(CreatureKind::Boar),
+            "Wolf" => Some(CreatureKind::Wolf),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CreatureKind::Deer => "Deer",
+            CreatureKind::Boar => "Boar",
+            CreatureKind::Wolf => "Wolf",
+        }
+    }
+
+    pub fn temperament(&self) -> Temperament {
+        match self {
+            CreatureKind::Deer => Temperament::Timid,
+            CreatureKind::Boar => Temperament::Defensive,
+            CreatureKind::Wolf => Temperament::Hostile,
+        }
+    }
+
+    /// Cells it strays from home before turning back
+    pub fn leash_radius(&self) -> i32 {
+        match self {
+            CreatureKind::Deer => 8,
+            CreatureKind::Boar => 6,
+            CreatureKind::Wolf => 5,
+        }
+    }
+
+    pub fn movable(&self) -> Movable {
+        match self {
+            CreatureKind::Deer => Movable { speed: 3.5 },
+            CreatureKind::Boar => Movable { speed: 2.5 },
+            CreatureKind::Wolf => Movable { speed: 3.0 },
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        match self {
+            CreatureKind::Deer => Health::new(40.0),
+            CreatureKind::Boar => Health::new(80.0),
+            CreatureKind::Wolf => Health::new(60.0),
+        }
+    }
+
+    pub fn attack_stats(&self) -> AttackStats {
+        match self {
+            CreatureKind::Deer => AttackStats {
+                damage: 3.0,
+                ..default()
+            },
+            CreatureKind::Boar => AttackStats {
+                damage: 8.0,
+                cooldown: 1.5,
+                ..default()
+            },
+            CreatureKind::Wolf => AttackStats {
+                damage: 6.0,
+                cooldown: 0.8,
+                ..default()
+            },
+        }
+    }
+
+    /// Only the combat skill matters for animals
+    pub fn base_skills(&self) -> Skills {
+        Skills {
+            mining: 0.0,
+            woodcutting: 0.0,
+            harvesting: 0.0,
+            combat: 1.0,
+            construction: 0.0,
+            crafting: 0.0,
+        }
+    }
+
+    /// What it leaves behind when killed, carried so the usual drop on death handles it
+    pub fn loot(&self) -> Inventory {
+        let loot = match self {
+            CreatureKind::Deer => vec![(ItemId::FOOD, 4), (ItemId::HIDES, 1)],
+            CreatureKind::Boar => vec![(ItemId::FOOD, 6), (ItemId::HIDES, 2)],
+            CreatureKind::Wolf => vec![(ItemId::HIDES, 2)],
+        };
+
+        let mut inventory = Inventory::new(loot.len());
+        inventory.slots = loot
+            .into_iter()
+            .map(|(item, quantity)| Some(InventorySlot { item, quantity }))
+            .collect();
+        inventory
+    }
+}
+
+/// An animal, wandering around the spot it was placed.
+#[derive(Component, Debug, Default)]
+pub struct Creature {
+    pub kind: CreatureKind,
+    pub temperament: Temperament,
+    pub leash_radius: i32,
+    /// Where it was placed, set on its first tick
+    pub home: Option<GridCoords>,
+    /// Heading home after straying past its leash, ignoring everything on the way
+    pub returning: bool,
+    /// Tick it next picks somewhere to wander to
+    pub next_wander: u64,
+}
+
+impl Creature {
+    pub fn new(kind: CreatureKind) -> Self {
+        Self {
+            kind,
+            temperament: kind.temperament(),
+            leash_radius: kind.leash_radius(),
+            ..default()
+        }
+    }
+}
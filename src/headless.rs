@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::components::entities::{Forest, Mine, Quarry};
+use crate::components::inventory::{Inventory, Stockpile};
+use crate::components::items::ItemId;
+use crate::components::unit::{Owner, Unit};
+use crate::systems::ai::{AiDifficulty, AiPlayers};
+use crate::systems::game_command::GameCommand;
+use crate::systems::idle::AutoBehaviour;
+use crate::systems::movement::grid_distance;
+use crate::systems::replay::{Replay, ReplayPlayback, ReplayRecorder};
+use crate::systems::resource_gathering::resource_item;
+use crate::systems::scene::MAP_PATH;
+use crate::systems::simulation::{state_hash, LevelReady, SimState, SimTick, TICK_RATE};
+use crate::systems::victory::{MatchResult, MatchState};
+use crate::{Headless, SimulationPlugins};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::Instant;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Game time one headless update advances, exactly one simulation tick.
+pub const TICK: Duration = Duration::from_millis(1000 / TICK_RATE as u64);
+
+/// Runs the game rules with no window, renderer or input.
+///
+/// Time moves forward by exactly one `TICK` per update however long the update
+/// took, so every update runs one simulation tick, runs are repeatable and go
+/// as fast as the machine allows.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            ImagePlugin::default(),
+        ))
+        .init_asset::<TextureAtlasLayout>()
+        .add_plugins(LdtkPlugin)
+        .add_plugins(SimulationPlugins)
+        .insert_resource(Headless)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+        .insert_resource(LevelSelection::index(0))
+        .add_systems(Startup, load_map);
+    }
+}
+
+/// Spawns the map straight away, there is no splash screen to wait for
+fn load_map(mut commands: Commands, asset_server: Res<AssetServer>) {
+    info!("<load_map> Loading LDtk map: {}", MAP_PATH);
+    commands.spawn(LdtkWorldBundle {
+        ldtk_handle: asset_server.load(MAP_PATH).into(),
+        ..Default::default()
+    });
+}
+
+/// A headless game driven one tick at a time, for integration tests and balance runs.
+///
+/// Orders go in as the same `GameCommand`s the player's input turns into, so
+/// units behave exactly as they would in a game.
+pub struct Simulation {
+    app: App,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(HeadlessPlugin);
+        Self { app }
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    /// Seconds of game time run so far
+    pub fn elapsed(&self) -> f32 {
+        self.app.world().resource::<Time>().elapsed_secs()
+    }
+
+    /// Runs a single tick
+    pub fn step(&mut self) {
+        self.app.update();
+    }
+
+    /// Runs for the given seconds of game time
+    pub fn run_for(&mut self, seconds: f32) {
+        let ticks = (seconds / TICK.as_secs_f32()).ceil() as u32;
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Runs until `done` returns true, giving up after `max_seconds` of game time
+    pub fn run_until(
+        &mut self,
+        max_seconds: f32,
+        mut done: impl FnMut(&mut World) -> bool,
+    ) -> bool {
+        let end = self.elapsed() + max_seconds;
+        while self.elapsed() < end {
+            self.step();
+            if done(self.world_mut()) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Runs until the map has spawned and the simulation is ticking, giving up
+    /// after `timeout` of real time.
+    ///
+    /// The map is read from disk in the background, so this is measured on the
+    /// wall clock rather than in ticks
+    pub fn wait_for_level(&mut self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            self.step();
+            if self.app.world().contains_resource::<LevelReady>() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Hash of every numbered entity's cell, health, inventory and walking
+    /// progress, equal in two runs only if they played out the same
+    pub fn state_hash(&mut self) -> u64 {
+        self.app
+            .world_mut()
+            .run_system_once(|entities: SimState| state_hash(&entities))
+            .expect("state_hash has no conflicting parameters")
+    }
+
+    /// Every unit, oldest first
+    pub fn units(&mut self) -> Vec<Entity> {
+        let world = self.app.world_mut();
+        let mut units: Vec<Entity> = world
+            .query_filtered::<Entity, With<Unit>>()
+            .iter(world)
+            .collect();
+        units.sort();
+        units
+    }
+
+    /// Resource nodes yielding `item`, nearest to `unit` first
+    pub fn resource_nodes(&mut self, item: &ItemId, unit: Entity) -> Vec<Entity> {
+        let world = self.app.world_mut();
+        let Some(unit_coords) = world.get::<GridCoords>(unit).copied() else {
+            return Vec::new();
+        };
+
+        let mut nodes: Vec<(Entity, GridCoords)> = world
+            .query::<(Entity, &GridCoords, Has<Forest>, Has<Mine>, Has<Quarry>)>()
+            .iter(world)
+            .filter(|(_, _, is_tree, is_mine, is_quarry)| {
+                resource_item(*is_tree, *is_mine, *is_quarry).as_ref() == Some(item)
+            })
+            .map(|(entity, coords, ..)| (entity, *coords))
+            .collect();
+        nodes.sort_by_key(|(entity, coords)| (grid_distance(coords, &unit_coords), *entity));
+        nodes.into_iter().map(|(entity, _)| entity).collect()
+    }
+
+    /// Gives an order, it is carried out on the next tick
+    pub fn command(&mut self, command: GameCommand) {
+        self.app.world_mut().send_event(command);
+    }
+
+    /// Walks a unit to a cell
+    pub fn move_to(&mut self, unit: Entity, destination: GridCoords) {
+        self.command(GameCommand::Move { unit, destination });
+    }
+
+    /// Sends a unit to gather from a resource node
+    pub fn gather(&mut self, unit: Entity, resource: Entity) {
+        self.command(GameCommand::Gather { unit, resource });
+    }
+
+    /// Sets what a worker does once it runs out of orders
+    pub fn set_auto_behaviour(&mut self, unit: Entity, behaviour: AutoBehaviour) {
+        self.command(GameCommand::SetAutoBehaviour { unit, behaviour });
+    }
+
+    /// Hands a player over to a computer opponent
+    pub fn add_ai(&mut self, owner: Owner, difficulty: AiDifficulty) {
+        self.app
+            .world_mut()
+            .resource_mut::<AiPlayers>()
+            .add(owner, difficulty);
+    }
+
+    /// Plays a replay's commands instead of taking orders, call before the level loads
+    pub fn play_replay(&mut self, replay: Replay) {
+        self.app
+            .world_mut()
+            .insert_resource(ReplayPlayback::new(replay));
+    }
+
+    /// The commands given so far, as a replay starting from the map
+    pub fn recording(&self) -> Replay {
+        self.app.world().resource::<ReplayRecorder>().replay()
+    }
+
+    /// Whether a replay being played has run out
+    pub fn replay_finished(&self) -> bool {
+        let world = self.app.world();
+        world
+            .get_resource::<ReplayPlayback>()
+            .is_some_and(|playback| playback.is_finished(*world.resource::<SimTick>()))
+    }
+
+    /// How the game ended, if it has
+    pub fn match_result(&self) -> Option<MatchResult> {
+        self.app.world().resource::<MatchState>().result.clone()
+    }
+
+    /// Items stored in stockpile chests, by item
+    pub fn stockpile(&mut self) -> HashMap<ItemId, u32> {
+        let world = self.app.world_mut();
+        let mut totals = HashMap::new();
+        for inventory in world
+            .query_filtered::<&Inventory, With<Stockpile>>()
+            .iter(world)
+        {
+            for slot in inventory.slots.iter().flatten() {
+                *totals.entry(slot.item.clone()).or_insert(0) += slot.quantity;
+            }
+        }
+        totals
+    }
+}
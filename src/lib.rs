@@ -0,0 +1,130 @@
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+
+pub mod components;
+pub mod headless;
+pub mod systems;
+
+use crate::components::entities::EntitiesPlugin;
+use crate::systems::ai::AiPlugin;
+use crate::systems::audio::AudioSystemPlugin;
+use crate::systems::camera::CameraPlugin;
+use crate::systems::combat::CombatPlugin;
+use crate::systems::construction::ConstructionPlugin;
+use crate::systems::crafting::CraftingPlugin;
+use crate::systems::game_command::GameCommandPlugin;
+use crate::systems::garrison::GarrisonPlugin;
+use crate::systems::housing::HousingPlugin;
+use crate::systems::idle::IdlePlugin;
+use crate::systems::input::PlayerInputPlugin;
+use crate::systems::inventory::InventoryPlugin;
+use crate::systems::item_piles::ItemPilesPlugin;
+use crate::systems::items::ItemsPlugin;
+use crate::systems::jobs::JobsPlugin;
+use crate::systems::movement::MovementPlugin;
+use crate::systems::multiplayer::MultiplayerPlugin;
+use crate::systems::production::ProductionPlugin;
+use crate::systems::replay::ReplayPlugin;
+use crate::systems::resource_gathering::ResourceGatheringPlugin;
+use crate::systems::save::SavePlugin;
+use crate::systems::save_menu::SaveMenuPlugin;
+use crate::systems::scene::ScenePlugin;
+use crate::systems::selection::SelectionPlugin;
+use crate::systems::setup_window::SetupWindowPlugin;
+use crate::systems::simulation::SimulationPlugin;
+use crate::systems::skills::SkillsPlugin;
+use crate::systems::storage::StoragePlugin;
+use crate::systems::transfer::TransferPlugin;
+use crate::systems::ui::{InfoPanelSet, UiPlugin};
+use crate::systems::victory::VictoryPlugin;
+use crate::systems::wildlife::WildlifePlugin;
+
+/// Systems that read the mouse, keyboard or window, or draw the HUD and info panel.
+///
+/// Gameplay plugins put their input and UI systems in this set, so the rest of
+/// the plugin can run without a window. Nothing in it may change game rules.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientSet;
+
+/// Marks an app running without a window or input, where `ClientSet` never runs.
+#[derive(Resource, Default)]
+pub struct Headless;
+
+/// The game rules: units, resources, buildings, combat, skills, saves and replays.
+///
+/// Runs the same under `DefaultPlugins` and in a headless app, with the rules
+/// themselves on the fixed timestep set up by `SimulationPlugin`.
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(ClientSetPlugin)
+            .add(SimulationPlugin)
+            .add(GameCommandPlugin)
+            .add(EntitiesPlugin)
+            .add(ItemsPlugin)
+            .add(SkillsPlugin)
+            .add(MovementPlugin)
+            .add(ResourceGatheringPlugin)
+            .add(ConstructionPlugin)
+            .add(ProductionPlugin)
+            .add(CombatPlugin)
+            .add(WildlifePlugin)
+            .add(HousingPlugin)
+            .add(GarrisonPlugin)
+            .add(CraftingPlugin)
+            .add(InventoryPlugin)
+            .add(TransferPlugin)
+            .add(ItemPilesPlugin)
+            .add(StoragePlugin)
+            .add(JobsPlugin)
+            .add(IdlePlugin)
+            .add(SavePlugin)
+            .add(ReplayPlugin)
+            .add(MultiplayerPlugin)
+            .add(AiPlugin)
+            .add(VictoryPlugin)
+    }
+}
+
+/// Everything a player sees and touches: input, camera, window, selection, menus, UI and audio.
+pub struct ClientPlugins;
+
+impl PluginGroup for ClientPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(PlayerInputPlugin)
+            .add(SaveMenuPlugin)
+            .add(CameraPlugin)
+            .add(SetupWindowPlugin)
+            .add(SelectionPlugin)
+            .add(ScenePlugin)
+            .add(UiPlugin)
+            .add(AudioSystemPlugin)
+    }
+}
+
+/// Skips `ClientSet` in headless apps.
+struct ClientSetPlugin;
+
+impl Plugin for ClientSetPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(Startup, ClientSet.run_if(not(resource_exists::<Headless>)))
+            .configure_sets(
+                PostStartup,
+                ClientSet.run_if(not(resource_exists::<Headless>)),
+            )
+            .configure_sets(Update, ClientSet.run_if(not(resource_exists::<Headless>)))
+            .configure_sets(Last, ClientSet.run_if(not(resource_exists::<Headless>)))
+            .configure_sets(
+                Update,
+                (
+                    InfoPanelSet::Input,
+                    InfoPanelSet::Rebuild,
+                    InfoPanelSet::Sections,
+                )
+                    .in_set(ClientSet),
+            );
+    }
+}
@@ -6,46 +6,178 @@ use bevy::DefaultPlugins;
 use bevy_aseprite_ultra::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-mod components;
-mod systems;
-
-use crate::components::entities::EntitiesPlugin;
-use crate::systems::audio::AudioSystemPlugin;
-use crate::systems::camera::CameraPlugin;
-use crate::systems::construction::ConstructionPlugin;
-use crate::systems::inventory::InventoryPlugin;
-use crate::systems::movement::MovementPlugin;
-use crate::systems::resource_gathering::ResourceGatheringPlugin;
-use crate::systems::scene::ScenePlugin;
-use crate::systems::selection::SelectionPlugin;
-use crate::systems::setup_window::SetupWindowPlugin;
-use crate::systems::ui::UiPlugin;
+use my_rts_game::components::unit::Owner;
+use my_rts_game::headless::Simulation;
+use my_rts_game::systems::ai::{AiDifficulty, AiPlayers};
+use my_rts_game::systems::idle::AutoBehaviour;
+use my_rts_game::systems::multiplayer::{Lockstep, DEFAULT_PORT};
+use my_rts_game::systems::replay::{read_replay, Replay, ReplayPlayback};
+use my_rts_game::{ClientPlugins, SimulationPlugins};
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "RTS Game".to_string(),
-                fit_canvas_to_parent: true,
-                canvas: Some("#bevy".to_string()),
-                ..Default::default()
-            }),
+    let args: Vec<String> = std::env::args().collect();
+    let replay = load_replay(&args);
+    let ai = ai_difficulty(&args);
+    if args.iter().any(|arg| arg == "--headless") {
+        run_headless(&args, replay, ai);
+        return;
+    }
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: "RTS Game".to_string(),
+            fit_canvas_to_parent: true,
+            canvas: Some("#bevy".to_string()),
             ..Default::default()
-        }))
-        .add_plugins(LdtkPlugin)
-        .add_plugins(WorldInspectorPlugin::new().run_if(input_toggle_active(false, KeyCode::F10)))
-        .add_plugins(AsepriteUltraPlugin)
-        .add_plugins(EntitiesPlugin)
-        .add_plugins(MovementPlugin)
-        .add_plugins(ResourceGatheringPlugin)
-        .add_plugins(ConstructionPlugin)
-        .add_plugins(InventoryPlugin)
-        .add_plugins(CameraPlugin)
-        .add_plugins(SetupWindowPlugin)
-        .add_plugins(SelectionPlugin)
-        .add_plugins(ScenePlugin)
-        .add_plugins(UiPlugin)
-        .add_plugins(AudioSystemPlugin)
-        .run();
+        }),
+        ..Default::default()
+    }))
+    .add_plugins(LdtkPlugin)
+    .add_plugins(WorldInspectorPlugin::new().run_if(input_toggle_active(false, KeyCode::F10)))
+    .add_plugins(AsepriteUltraPlugin)
+    .add_plugins(SimulationPlugins)
+    .add_plugins(ClientPlugins);
+
+    if let Some(replay) = replay {
+        app.insert_resource(ReplayPlayback::new(replay));
+    } else if let Some(lockstep) = connect_lockstep(&args) {
+        app.insert_resource(lockstep);
+    } else if let Some(difficulty) = ai {
+        app.world_mut()
+            .resource_mut::<AiPlayers>()
+            .add(AI_PLAYER, difficulty);
+    }
+    app.run();
+}
+
+/// The player a computer opponent takes over.
+const AI_PLAYER: Owner = Owner(1);
+
+/// Reads `--ai [easy|normal|hard]`, normal if no difficulty is given
+fn ai_difficulty(args: &[String]) -> Option<AiDifficulty> {
+    let index = args.iter().position(|arg| arg == "--ai")?;
+    match args.get(index + 1).filter(|value| !value.starts_with("--")) {
+        None => Some(AiDifficulty::default()),
+        Some(name) => match AiDifficulty::from_name(name) {
+            Some(difficulty) => Some(difficulty),
+            None => {
+                eprintln!("Unknown AI difficulty {}, use easy, normal or hard", name);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Sets up a multiplayer game for `--host [port]` or `--join <address>`,
+/// exiting if the socket can't be opened
+fn connect_lockstep(args: &[String]) -> Option<Lockstep> {
+    let value_after = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|index| args.get(index + 1).filter(|value| !value.starts_with("--")))
+    };
+
+    let lockstep = if let Some(port) = value_after("--host") {
+        let port = port
+            .and_then(|port| port.parse::<u16>().ok())
+            .unwrap_or(DEFAULT_PORT);
+        Lockstep::host(port)
+    } else if let Some(address) = value_after("--join") {
+        let Some(host) = address.and_then(|address| address.parse().ok()) else {
+            eprintln!("--join needs an address like 127.0.0.1:{}", DEFAULT_PORT);
+            std::process::exit(1);
+        };
+        Lockstep::join(host)
+    } else {
+        return None;
+    };
+
+    match lockstep {
+        Ok(lockstep) => Some(lockstep),
+        Err(error) => {
+            eprintln!("Could not start a multiplayer game: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads the replay given with `--replay <path>`, exiting if it can't be played
+fn load_replay(args: &[String]) -> Option<Replay> {
+    let path = args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .and_then(|index| args.get(index + 1))?;
+
+    match read_replay(Path::new(path)) {
+        Ok(replay) => Some(replay),
+        Err(error) => {
+            eprintln!("Could not play {}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Fast-forwards the game without a window and prints what ended up in the stockpile.
+///
+/// `--seconds N` sets how much game time to run, five minutes by default. With
+/// `--replay <path>` the replay's commands are played instead, to its end, and
+/// `--ai [difficulty]` puts a computer opponent in the game.
+fn run_headless(args: &[String], replay: Option<Replay>, ai: Option<AiDifficulty>) {
+    let seconds = args
+        .iter()
+        .position(|arg| arg == "--seconds")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|value| value.parse::<f32>().ok())
+        .unwrap_or(300.0);
+
+    let mut simulation = Simulation::new();
+    let replaying = replay.is_some();
+    if let Some(replay) = replay {
+        simulation.play_replay(replay);
+    } else if let Some(difficulty) = ai {
+        simulation.add_ai(AI_PLAYER, difficulty);
+    }
+
+    if !simulation.wait_for_level(Duration::from_secs(30)) {
+        eprintln!("The level did not load");
+        std::process::exit(1);
+    }
+
+    let started = Instant::now();
+    let start = simulation.elapsed();
+    if replaying {
+        while !simulation.replay_finished() {
+            simulation.step();
+        }
+    } else {
+        // Nobody designates jobs in a headless run, so workers find their own work
+        for unit in simulation.units() {
+            simulation.set_auto_behaviour(unit, AutoBehaviour::GatherNearest);
+        }
+        simulation.run_for(seconds);
+    }
+    println!(
+        "Ran {:.0}s of game time in {:.1}s",
+        simulation.elapsed() - start,
+        started.elapsed().as_secs_f32()
+    );
+
+    if let Some(result) = simulation.match_result() {
+        println!(
+            "Game over at tick {}: {:?} won, {}",
+            result.tick,
+            result.winners,
+            result.reason.describe()
+        );
+    }
+
+    let mut stockpile: Vec<_> = simulation.stockpile().into_iter().collect();
+    stockpile.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+    for (item, quantity) in stockpile {
+        println!("{}: {}", item.0, quantity);
+    }
 }
@@ -0,0 +1,991 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::components::combat::Health;
+use crate::components::entities::{Character, Forest, Mine, Quarry, Warrior, Worker};
+use crate::components::inventory::{Inventory, Stockpile};
+use crate::components::items::ItemId;
+use crate::components::movement::Collider;
+use crate::components::resources::ResourceNode;
+use crate::components::ui::HudPanel;
+use crate::components::unit::{Owner, UnitType};
+use crate::systems::combat::Attacking;
+use crate::systems::construction::{BuildingType, ConstructionSite};
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::Garrisoned;
+use crate::systems::housing::Population;
+use crate::systems::idle::{AutoBehaviour, Idle};
+use crate::systems::movement::{grid_distance, grid_to_translation};
+use crate::systems::multiplayer::Lockstep;
+use crate::systems::production::ProductionQueue;
+use crate::systems::replay::ReplayPlayback;
+use crate::systems::resource_gathering::{resource_item, Gathering, GatheringIntent};
+use crate::systems::simulation::{SimTick, SimulationSet, TICK_RATE};
+use crate::systems::transfer::{TransferAmount, TransferDirection};
+use crate::ClientSet;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for computer opponents.
+///
+/// An AI player sees the world through queries and plays it through the same
+/// `GameCommand`s a human gives, so it can't do anything a player couldn't.
+/// It thinks in `SimulationSet::Input`, which puts its orders in replays. It
+/// stays out of replays being played back and multiplayer games.
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AiPlayers>()
+            .init_resource::<ShowAiGoals>()
+            .add_systems(
+                FixedUpdate,
+                run_ai
+                    .run_if(not(resource_exists::<ReplayPlayback>))
+                    .run_if(not(resource_exists::<Lockstep>))
+                    .in_set(SimulationSet::Input),
+            )
+            .add_systems(PostStartup, setup_ai_overlay.in_set(ClientSet))
+            .add_systems(
+                Update,
+                (toggle_ai_overlay, update_ai_overlay, draw_ai_goals)
+                    .chain()
+                    .in_set(ClientSet),
+            );
+    }
+}
+
+/// How far an AI player's units and houses can see, in cells.
+const SIGHT_RADIUS: i32 = 8;
+
+/// Enemies this close to the AI's base get fought off.
+const DEFEND_RADIUS: i32 = 6;
+
+/// Furthest from its base the AI looks for somewhere to build, in cells.
+const BUILD_RADIUS: i32 = 8;
+
+/// How well a computer opponent plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AiDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl AiDifficulty {
+    /// Reads a difficulty from its name, as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "easy" => Some(AiDifficulty::Easy),
+            "normal" => Some(AiDifficulty::Normal),
+            "hard" => Some(AiDifficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AiDifficulty::Easy => "Easy",
+            AiDifficulty::Normal => "Normal",
+            AiDifficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn profile(&self) -> AiProfile {
+        match self {
+            AiDifficulty::Easy => AiProfile {
+                think_interval: 4.0,
+                max_workers: 4,
+                max_houses: 2,
+                attack_size: 6,
+                first_attack_after: 480.0,
+                scouts: false,
+                defends: false,
+            },
+            AiDifficulty::Normal => AiProfile {
+                think_interval: 2.0,
+                max_workers: 6,
+                max_houses: 3,
+                attack_size: 4,
+                first_attack_after: 300.0,
+                scouts: true,
+                defends: true,
+            },
+            AiDifficulty::Hard => AiProfile {
+                think_interval: 0.5,
+                max_workers: 10,
+                max_houses: 5,
+                attack_size: 3,
+                first_attack_after: 150.0,
+                scouts: true,
+                defends: true,
+            },
+        }
+    }
+}
+
+/// The numbers behind a difficulty.
+#[derive(Debug, Clone, Copy)]
+pub struct AiProfile {
+    /// Seconds of game time between decisions
+    pub think_interval: f32,
+    /// Workers trained before switching to warriors
+    pub max_workers: usize,
+    /// Houses, finished or not, it stops building at
+    pub max_houses: usize,
+    /// Idle warriors needed to send an attack
+    pub attack_size: usize,
+    /// Seconds of game time before the first attack
+    pub first_attack_after: f32,
+    /// Whether it sends a warrior looking for the enemy
+    pub scouts: bool,
+    /// Whether idle warriors fight off enemies near its base
+    pub defends: bool,
+}
+
+/// Something an AI player is working towards, shown in the debug overlay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AiGoal {
+    Gather { item: ItemId, workers: usize },
+    FetchMaterials { item: ItemId, amount: u32 },
+    BuildHouse { at: GridCoords },
+    Train(UnitType),
+    Scout { to: GridCoords },
+    Defend { warriors: usize },
+    Attack { warriors: usize, at: GridCoords },
+    Wait(&'static str),
+}
+
+impl AiGoal {
+    /// The cell the goal is about, if it's about one
+    fn cell(&self) -> Option<GridCoords> {
+        match self {
+            AiGoal::BuildHouse { at } | AiGoal::Attack { at, .. } => Some(*at),
+            AiGoal::Scout { to } => Some(*to),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AiGoal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiGoal::Gather { item, workers } => {
+                write!(f, "Gathering {} with {} workers", item, workers)
+            }
+            AiGoal::FetchMaterials { item, amount } => {
+                write!(f, "Fetching {} {} for a house", amount, item)
+            }
+            AiGoal::BuildHouse { at } => write!(f, "Building a house at ({}, {})", at.x, at.y),
+            AiGoal::Train(unit_type) => write!(f, "Training a {}", unit_type.name()),
+            AiGoal::Scout { to } => write!(f, "Scouting towards ({}, {})", to.x, to.y),
+            AiGoal::Defend { warriors } => {
+                write!(f, "Defending the base with {} warriors", warriors)
+            }
+            AiGoal::Attack { warriors, at } => write!(
+                f,
+                "Attacking ({}, {}) with {} warriors",
+                at.x, at.y, warriors
+            ),
+            AiGoal::Wait(reason) => write!(f, "Waiting: {}", reason),
+        }
+    }
+}
+
+/// One computer opponent and what it remembers between decisions.
+#[derive(Debug)]
+pub struct AiPlayer {
+    pub owner: Owner,
+    pub difficulty: AiDifficulty,
+    /// What it decided on last time it thought
+    pub goals: Vec<AiGoal>,
+    next_think: u64,
+    base: Option<GridCoords>,
+    builder: Option<Entity>,
+    scout: Option<Entity>,
+    next_waypoint: usize,
+    // Where it last saw the enemy
+    enemy_base: Option<GridCoords>,
+}
+
+impl AiPlayer {
+    pub fn new(owner: Owner, difficulty: AiDifficulty) -> Self {
+        Self {
+            owner,
+            difficulty,
+            goals: Vec::new(),
+            next_think: 0,
+            base: None,
+            builder: None,
+            scout: None,
+            next_waypoint: 0,
+            enemy_base: None,
+        }
+    }
+}
+
+/// Every computer opponent in the game, none unless asked for.
+#[derive(Resource, Debug, Default)]
+pub struct AiPlayers {
+    pub players: Vec<AiPlayer>,
+}
+
+impl AiPlayers {
+    /// Hands a player over to the computer
+    pub fn add(&mut self, owner: Owner, difficulty: AiDifficulty) {
+        info!(
+            "<AiPlayers> Player {} is played by a {} AI",
+            owner.0 + 1,
+            difficulty.name()
+        );
+        self.players.retain(|player| player.owner != owner);
+        self.players.push(AiPlayer::new(owner, difficulty));
+    }
+}
+
+/// Whether the AI debug overlay is showing, toggled with F7.
+#[derive(Resource, Debug, Default)]
+struct ShowAiGoals(bool);
+
+/// One of the AI's own units.
+struct OwnUnit {
+    entity: Entity,
+    coords: GridCoords,
+    is_worker: bool,
+    is_warrior: bool,
+    idle: bool,
+    gathering: Option<ItemId>,
+    attacking: bool,
+    waits: bool,
+}
+
+/// What an AI player can see of the world.
+#[derive(SystemParam)]
+struct AiSight<'w, 's> {
+    units: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Owner,
+            &'static GridCoords,
+            Has<Worker>,
+            Has<Warrior>,
+            Has<Idle>,
+            Option<&'static Gathering>,
+            Option<&'static GatheringIntent>,
+            Has<Attacking>,
+            Option<&'static AutoBehaviour>,
+            &'static Inventory,
+        ),
+        With<Character>,
+    >,
+    resources: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GridCoords,
+            Has<Forest>,
+            Has<Mine>,
+            Has<Quarry>,
+        ),
+        With<ResourceNode>,
+    >,
+    houses: Query<
+        'w,
+        's,
+        (
+            Entity,
+            Option<&'static Owner>,
+            &'static GridCoords,
+            &'static ProductionQueue,
+        ),
+    >,
+    sites: Query<'w, 's, (Option<&'static Owner>, &'static GridCoords), With<ConstructionSite>>,
+    targets: Query<
+        'w,
+        's,
+        (Entity, Option<&'static Owner>, &'static GridCoords),
+        (With<Health>, Without<Garrisoned>),
+    >,
+    stockpiles: Query<
+        'w,
+        's,
+        (
+            Entity,
+            Option<&'static Owner>,
+            &'static GridCoords,
+            &'static Inventory,
+        ),
+        With<Stockpile>,
+    >,
+    obstacles: Query<'w, 's, &'static GridCoords, With<Collider>>,
+    population: Res<'w, Population>,
+}
+
+impl AiSight<'_, '_> {
+    fn own_units(&self, owner: Owner) -> Vec<OwnUnit> {
+        let mut units: Vec<OwnUnit> = self
+            .units
+            .iter()
+            .filter(|(_, unit_owner, ..)| **unit_owner == owner)
+            .map(
+                |(
+                    entity,
+                    _,
+                    coords,
+                    is_worker,
+                    is_warrior,
+                    idle,
+                    gathering,
+                    intent,
+                    attacking,
+                    behaviour,
+                    _,
+                )| OwnUnit {
+                    entity,
+                    coords: *coords,
+                    is_worker,
+                    is_warrior,
+                    idle,
+                    gathering: gathering
+                        .map(|gathering| gathering.item.clone())
+                        .or_else(|| intent.map(|intent| intent.item.clone())),
+                    attacking,
+                    waits: behaviour == Some(&AutoBehaviour::Wait),
+                },
+            )
+            .collect();
+        units.sort_by_key(|unit| unit.entity);
+        units
+    }
+
+    fn own_houses(&self, owner: Owner) -> Vec<(Entity, GridCoords, &ProductionQueue)> {
+        let mut houses: Vec<_> = self
+            .houses
+            .iter()
+            .filter(|(_, house_owner, ..)| house_owner.copied().unwrap_or_default() == owner)
+            .map(|(entity, _, coords, production)| (entity, *coords, production))
+            .collect();
+        houses.sort_by_key(|(entity, ..)| *entity);
+        houses
+    }
+
+    /// Enemies close enough to one of `lookouts` to be seen, wildlife is left alone
+    fn visible_enemies(&self, owner: Owner, lookouts: &[GridCoords]) -> Vec<(Entity, GridCoords)> {
+        let mut enemies: Vec<_> = self
+            .targets
+            .iter()
+            .filter(|(_, target_owner, _)| {
+                let target_owner = target_owner.copied().unwrap_or_default();
+                target_owner != owner && target_owner != Owner::NEUTRAL
+            })
+            .filter(|(_, _, coords)| {
+                lookouts
+                    .iter()
+                    .any(|lookout| grid_distance(lookout, coords) <= SIGHT_RADIUS)
+            })
+            .map(|(entity, _, coords)| (entity, *coords))
+            .collect();
+        enemies.sort_by_key(|(entity, _)| *entity);
+        enemies
+    }
+
+    /// Nearest resource of the given kind
+    fn nearest_resource(&self, item: &ItemId, from: &GridCoords) -> Option<Entity> {
+        self.resources
+            .iter()
+            .filter(|(_, _, is_tree, is_mine, is_quarry)| {
+                resource_item(*is_tree, *is_mine, *is_quarry).as_ref() == Some(item)
+            })
+            .min_by_key(|(entity, coords, ..)| (grid_distance(from, coords), *entity))
+            .map(|(entity, ..)| entity)
+    }
+
+    /// Chests belonging to `owner`
+    fn own_stockpiles(
+        &self,
+        owner: Owner,
+    ) -> impl Iterator<Item = (Entity, &GridCoords, &Inventory)> {
+        self.stockpiles
+            .iter()
+            .filter(move |(_, chest_owner, ..)| chest_owner.copied().unwrap_or_default() == owner)
+            .map(|(entity, _, coords, inventory)| (entity, coords, inventory))
+    }
+
+    fn stockpile_count(&self, owner: Owner, item: &ItemId) -> u32 {
+        self.own_stockpiles(owner)
+            .map(|(_, _, inventory)| inventory.count_item(item))
+            .sum()
+    }
+
+    /// Nearest free cell at least `min_distance` from `center`, searching outwards
+    fn free_cell_near(&self, center: GridCoords, min_distance: i32) -> Option<GridCoords> {
+        let blocked: HashSet<GridCoords> = self
+            .obstacles
+            .iter()
+            .chain(self.sites.iter().map(|(_, coords)| coords))
+            .copied()
+            .collect();
+
+        (min_distance..=BUILD_RADIUS).find_map(|radius| {
+            (-radius..=radius)
+                .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+                .filter(|(x, y)| x.abs().max(y.abs()) == radius)
+                .map(|(x, y)| GridCoords::new(center.x + x, center.y + y))
+                .find(|cell| !blocked.contains(cell))
+        })
+    }
+
+    /// Corners of the area everything on the map fits in
+    fn map_corners(&self) -> Vec<GridCoords> {
+        let cells: Vec<&GridCoords> = self
+            .obstacles
+            .iter()
+            .chain(self.resources.iter().map(|(_, coords, ..)| coords))
+            .collect();
+        let (Some(min_x), Some(max_x)) = (
+            cells.iter().map(|cell| cell.x).min(),
+            cells.iter().map(|cell| cell.x).max(),
+        ) else {
+            return Vec::new();
+        };
+        let min_y = cells.iter().map(|cell| cell.y).min().unwrap_or_default();
+        let max_y = cells.iter().map(|cell| cell.y).max().unwrap_or_default();
+
+        vec![
+            GridCoords::new(min_x + 1, min_y + 1),
+            GridCoords::new(max_x - 1, min_y + 1),
+            GridCoords::new(max_x - 1, max_y - 1),
+            GridCoords::new(min_x + 1, max_y - 1),
+        ]
+    }
+}
+
+/// Lets every AI player whose turn it is look around and give its orders
+fn run_ai(
+    mut ai_players: ResMut<AiPlayers>,
+    sight: AiSight,
+    tick: Res<SimTick>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    for ai in &mut ai_players.players {
+        if tick.0 < ai.next_think {
+            continue;
+        }
+        let profile = ai.difficulty.profile();
+        ai.next_think = tick.0 + (profile.think_interval as f64 * TICK_RATE).max(1.0) as u64;
+
+        let mut orders = Vec::new();
+        think(ai, &profile, &sight, tick.0, &mut orders);
+        game_commands.send_batch(orders);
+    }
+}
+
+/// Works out what one AI player should do next
+fn think(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    sight: &AiSight,
+    tick: u64,
+    orders: &mut Vec<GameCommand>,
+) {
+    ai.goals.clear();
+
+    let units = sight.own_units(ai.owner);
+    let houses = sight.own_houses(ai.owner);
+    if units.is_empty() && houses.is_empty() {
+        ai.goals.push(AiGoal::Wait("nothing left to play with"));
+        return;
+    }
+
+    // Home is the first house, or where the units started out
+    let base = houses
+        .first()
+        .map(|(_, coords, _)| *coords)
+        .or(ai.base)
+        .or_else(|| units.first().map(|unit| unit.coords));
+    ai.base = base;
+    let Some(base) = base else {
+        return;
+    };
+
+    // Forget units that died
+    let alive = |entity: Option<Entity>| {
+        entity.filter(|entity| units.iter().any(|unit| unit.entity == *entity))
+    };
+    ai.builder = alive(ai.builder);
+    ai.scout = alive(ai.scout);
+
+    // The AI gives its workers every order itself
+    for unit in units.iter().filter(|unit| unit.is_worker && !unit.waits) {
+        orders.push(GameCommand::SetAutoBehaviour {
+            unit: unit.entity,
+            behaviour: AutoBehaviour::Wait,
+        });
+    }
+
+    let lookouts: Vec<GridCoords> = units
+        .iter()
+        .map(|unit| unit.coords)
+        .chain(houses.iter().map(|(_, coords, _)| *coords))
+        .collect();
+    let enemies = sight.visible_enemies(ai.owner, &lookouts);
+    if let Some((_, coords)) = enemies
+        .iter()
+        .min_by_key(|(entity, coords)| (grid_distance(&base, coords), *entity))
+    {
+        ai.enemy_base = Some(*coords);
+    }
+
+    defend(ai, profile, &units, &enemies, base, orders);
+    build_house(ai, profile, sight, &units, &houses, base, orders);
+    gather(ai, sight, &units, orders);
+    train(ai, profile, sight, &units, &houses, orders);
+    scout(ai, profile, sight, &units, orders);
+    attack(ai, profile, sight, &units, tick, orders);
+}
+
+/// Sends idle warriors at enemies near the base
+fn defend(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    units: &[OwnUnit],
+    enemies: &[(Entity, GridCoords)],
+    base: GridCoords,
+    orders: &mut Vec<GameCommand>,
+) {
+    let threats: Vec<_> = enemies
+        .iter()
+        .filter(|(_, coords)| grid_distance(&base, coords) <= DEFEND_RADIUS)
+        .collect();
+    if !profile.defends || threats.is_empty() {
+        return;
+    }
+
+    let defenders: Vec<_> = units
+        .iter()
+        .filter(|unit| unit.is_warrior && !unit.attacking)
+        .collect();
+    for defender in &defenders {
+        if let Some((target, _)) = threats
+            .iter()
+            .min_by_key(|(entity, coords)| (grid_distance(&defender.coords, coords), *entity))
+        {
+            orders.push(GameCommand::Attack {
+                unit: defender.entity,
+                target: *target,
+            });
+        }
+    }
+
+    if !defenders.is_empty() {
+        ai.goals.push(AiGoal::Defend {
+            warriors: defenders.len(),
+        });
+    }
+}
+
+/// Builds another house once the population is close to the cap, with a
+/// worker that fetches the materials from storage first
+fn build_house(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    sight: &AiSight,
+    units: &[OwnUnit],
+    houses: &[(Entity, GridCoords, &ProductionQueue)],
+    base: GridCoords,
+    orders: &mut Vec<GameCommand>,
+) {
+    let sites = sight
+        .sites
+        .iter()
+        .filter(|(owner, _)| owner.copied().unwrap_or_default() == ai.owner)
+        .count();
+    let population = sight.population.get(ai.owner);
+    let needs_house = sites == 0
+        && houses.len() < profile.max_houses
+        && (houses.is_empty() || population.units + population.queued + 1 >= population.cap);
+    if !needs_house {
+        ai.builder = None;
+        return;
+    }
+
+    let cost = BuildingType::House.get_cost();
+    if let Some((item, _)) = cost
+        .iter()
+        .find(|(item, amount)| sight.stockpile_count(ai.owner, item) < *amount)
+    {
+        // A builder may already carry some of it, but waiting on the rest is simplest
+        if ai.builder.is_none() {
+            ai.goals.push(AiGoal::Wait(if *item == ItemId::WOOD {
+                "not enough wood for a house"
+            } else {
+                "not enough stone for a house"
+            }));
+            return;
+        }
+    }
+
+    let builder = ai.builder.or_else(|| {
+        units
+            .iter()
+            .filter(|unit| unit.is_worker)
+            .min_by_key(|unit| (!unit.idle, grid_distance(&unit.coords, &base), unit.entity))
+            .map(|unit| unit.entity)
+    });
+    let Some(builder) = builder.and_then(|builder| units.iter().find(|u| u.entity == builder))
+    else {
+        ai.goals.push(AiGoal::Wait("no worker to build a house"));
+        return;
+    };
+    ai.builder = Some(builder.entity);
+
+    // Leave the builder be while it's walking or fetching
+    let free = builder.idle || builder.gathering.is_some();
+
+    let Ok((.., carried)) = sight.units.get(builder.entity) else {
+        return;
+    };
+    let missing = cost.iter().find_map(|(item, amount)| {
+        let have = carried.count_item(item);
+        (have < *amount).then(|| (item.clone(), amount - have))
+    });
+
+    if let Some((item, amount)) = missing {
+        ai.goals.push(AiGoal::FetchMaterials {
+            item: item.clone(),
+            amount,
+        });
+        let chest = sight
+            .own_stockpiles(ai.owner)
+            .filter(|(_, _, inventory)| inventory.count_item(&item) > 0)
+            .min_by_key(|(entity, coords, _)| (grid_distance(&builder.coords, coords), *entity));
+        if let (true, Some((chest, ..))) = (free, chest) {
+            orders.push(GameCommand::Transfer {
+                unit: builder.entity,
+                target: chest,
+                item,
+                amount: TransferAmount::Exactly(amount),
+                direction: TransferDirection::Take,
+            });
+        }
+        return;
+    }
+
+    let Some(at) = sight.free_cell_near(base, 2) else {
+        ai.goals.push(AiGoal::Wait("no room for a house"));
+        return;
+    };
+    ai.goals.push(AiGoal::BuildHouse { at });
+    if free {
+        orders.push(GameCommand::Build {
+            unit: builder.entity,
+            building_type: BuildingType::House,
+            at,
+        });
+    }
+}
+
+/// Puts idle workers on whichever resource has the fewest gatherers
+fn gather(ai: &mut AiPlayer, sight: &AiSight, units: &[OwnUnit], orders: &mut Vec<GameCommand>) {
+    let kinds = [ItemId::GOLD, ItemId::WOOD, ItemId::STONE];
+    let mut gatherers: Vec<usize> = kinds
+        .iter()
+        .map(|item| {
+            units
+                .iter()
+                .filter(|unit| unit.gathering.as_ref() == Some(item))
+                .count()
+        })
+        .collect();
+
+    let idle_workers = units
+        .iter()
+        .filter(|unit| unit.is_worker && unit.idle && Some(unit.entity) != ai.builder);
+    for worker in idle_workers {
+        let mut by_need: Vec<usize> = (0..kinds.len()).collect();
+        by_need.sort_by_key(|index| gatherers[*index]);
+
+        let Some((index, resource)) = by_need.into_iter().find_map(|index| {
+            sight
+                .nearest_resource(&kinds[index], &worker.coords)
+                .map(|resource| (index, resource))
+        }) else {
+            continue;
+        };
+
+        gatherers[index] += 1;
+        orders.push(GameCommand::Gather {
+            unit: worker.entity,
+            resource,
+        });
+    }
+
+    for (item, workers) in kinds.into_iter().zip(gatherers) {
+        if workers > 0 {
+            ai.goals.push(AiGoal::Gather { item, workers });
+        }
+    }
+}
+
+/// Trains workers up to the profile's count, then warriors
+fn train(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    sight: &AiSight,
+    units: &[OwnUnit],
+    houses: &[(Entity, GridCoords, &ProductionQueue)],
+    orders: &mut Vec<GameCommand>,
+) {
+    let mut population = sight.population.get(ai.owner);
+    let mut gold = sight.stockpile_count(ai.owner, &ItemId::GOLD);
+    let workers = units.iter().filter(|unit| unit.is_worker).count();
+
+    for (house, _, production) in houses {
+        if let Some(unit_type) = production.queue.first() {
+            ai.goals.push(AiGoal::Train(*unit_type));
+            continue;
+        }
+        if population.is_capped() {
+            continue;
+        }
+
+        let unit_type = if workers + (population.queued as usize) < profile.max_workers {
+            UnitType::Worker
+        } else {
+            UnitType::Warrior
+        };
+        let cost: u32 = unit_type
+            .get_cost()
+            .iter()
+            .filter(|(item, _)| *item == ItemId::GOLD)
+            .map(|(_, amount)| amount)
+            .sum();
+        if gold < cost {
+            ai.goals.push(AiGoal::Wait("not enough gold to train"));
+            break;
+        }
+
+        gold -= cost;
+        population.queued += 1;
+        ai.goals.push(AiGoal::Train(unit_type));
+        orders.push(GameCommand::Train {
+            building: *house,
+            unit_type,
+        });
+    }
+}
+
+/// Walks a warrior around the edges of the map until the enemy turns up
+fn scout(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    sight: &AiSight,
+    units: &[OwnUnit],
+    orders: &mut Vec<GameCommand>,
+) {
+    if !profile.scouts || ai.enemy_base.is_some() {
+        ai.scout = None;
+        return;
+    }
+
+    let scout = ai.scout.or_else(|| {
+        units
+            .iter()
+            .find(|unit| unit.is_warrior && unit.idle)
+            .map(|unit| unit.entity)
+    });
+    let Some(scout) = scout.and_then(|scout| units.iter().find(|unit| unit.entity == scout)) else {
+        return;
+    };
+    ai.scout = Some(scout.entity);
+
+    let corners = sight.map_corners();
+    if corners.is_empty() {
+        return;
+    }
+    let to = corners[ai.next_waypoint % corners.len()];
+    ai.goals.push(AiGoal::Scout { to });
+
+    if scout.idle {
+        ai.next_waypoint += 1;
+        let to = corners[ai.next_waypoint % corners.len()];
+        orders.push(GameCommand::Move {
+            unit: scout.entity,
+            destination: to,
+        });
+    }
+}
+
+/// Sends the idle warriors at the enemy once there are enough of them
+fn attack(
+    ai: &mut AiPlayer,
+    profile: &AiProfile,
+    sight: &AiSight,
+    units: &[OwnUnit],
+    tick: u64,
+    orders: &mut Vec<GameCommand>,
+) {
+    let Some(enemy_base) = ai.enemy_base else {
+        return;
+    };
+
+    let fighting = units.iter().filter(|unit| unit.attacking).count();
+    if fighting > 0 {
+        ai.goals.push(AiGoal::Attack {
+            warriors: fighting,
+            at: enemy_base,
+        });
+    }
+
+    let seconds = tick as f64 / TICK_RATE;
+    if seconds < profile.first_attack_after as f64 {
+        return;
+    }
+
+    let army: Vec<_> = units
+        .iter()
+        .filter(|unit| unit.is_warrior && unit.idle && Some(unit.entity) != ai.scout)
+        .collect();
+    if army.len() < profile.attack_size {
+        return;
+    }
+
+    // Whatever it can find where it last saw the enemy
+    let targets: Vec<_> = sight
+        .targets
+        .iter()
+        .filter(|(_, owner, coords)| {
+            let owner = owner.copied().unwrap_or_default();
+            owner != ai.owner
+                && owner != Owner::NEUTRAL
+                && grid_distance(&enemy_base, coords) <= SIGHT_RADIUS
+        })
+        .map(|(entity, _, coords)| (entity, *coords))
+        .collect();
+    if targets.is_empty() {
+        // They moved or are gone, go and look again
+        ai.enemy_base = None;
+        return;
+    }
+
+    for warrior in &army {
+        if let Some((target, _)) = targets
+            .iter()
+            .min_by_key(|(entity, coords)| (grid_distance(&warrior.coords, coords), *entity))
+        {
+            orders.push(GameCommand::Attack {
+                unit: warrior.entity,
+                target: *target,
+            });
+        }
+    }
+    ai.goals.push(AiGoal::Attack {
+        warriors: army.len(),
+        at: enemy_base,
+    });
+}
+
+/// Text listing what every AI player is up to.
+#[derive(Component)]
+struct AiOverlayText;
+
+/// Adds the AI goals text to the HUD, hidden until F7 is pressed
+fn setup_ai_overlay(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::srgb(1.0, 0.6, 0.6)),
+            Node {
+                display: Display::None,
+                ..default()
+            },
+            AiOverlayText,
+        ));
+    });
+}
+
+/// Pressing F7 shows or hides the AI goals
+fn toggle_ai_overlay(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut show: ResMut<ShowAiGoals>,
+    mut overlays: Query<&mut Node, With<AiOverlayText>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F7) {
+        return;
+    }
+
+    show.0 = !show.0;
+    for mut node in &mut overlays {
+        node.display = if show.0 { Display::Flex } else { Display::None };
+    }
+}
+
+/// Keeps the AI goals text up to date
+fn update_ai_overlay(
+    show: Res<ShowAiGoals>,
+    ai_players: Res<AiPlayers>,
+    mut texts: Query<&mut Text, With<AiOverlayText>>,
+) {
+    if !show.0 {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    if ai_players.players.is_empty() {
+        lines.push("No AI players (start with --ai)".to_string());
+    }
+    for ai in &ai_players.players {
+        lines.push(format!(
+            "AI player {} ({})",
+            ai.owner.0 + 1,
+            ai.difficulty.name()
+        ));
+        lines.extend(ai.goals.iter().map(|goal| format!("  {}", goal)));
+    }
+
+    for mut text in &mut texts {
+        *text = Text::new(lines.join("\n"));
+    }
+}
+
+/// Marks the cells the AI goals are about on the map
+fn draw_ai_goals(show: Res<ShowAiGoals>, ai_players: Res<AiPlayers>, mut gizmos: Gizmos) {
+    if !show.0 {
+        return;
+    }
+
+    for ai in &ai_players.players {
+        if let Some(base) = ai.base {
+            gizmos.circle_2d(
+                grid_to_translation(base, 0.0).truncate(),
+                40.0,
+                Color::srgb(1.0, 0.6, 0.6),
+            );
+        }
+        for cell in ai.goals.iter().filter_map(AiGoal::cell) {
+            gizmos.rect_2d(
+                grid_to_translation(cell, 0.0).truncate(),
+                Vec2::new(56.0, 56.0),
+                Color::srgb(1.0, 0.3, 0.3),
+            );
+        }
+    }
+}
@@ -15,6 +15,14 @@ impl CameraPanState {
             zoom_level: 1.0, // Default zoom level
         }
     }
+
+    pub fn zoom_level(&self) -> f32 {
+        self.zoom_level
+    }
+
+    pub fn set_zoom_level(&mut self, zoom_level: f32) {
+        self.zoom_level = zoom_level;
+    }
 }
 
 /// Plugin for camera controls.
@@ -0,0 +1,267 @@
+use crate::components::combat::{AttackStats, Health};
+use crate::components::entities::Warrior;
+use crate::components::inventory::Inventory;
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::skills::{PerkEffect, SkillKind, SkillProgression, Skills};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::{Garrison, Garrisoned};
+use crate::systems::item_piles::DropItems;
+use crate::systems::movement::grid_distance;
+use crate::systems::resource_gathering::find_adjacent_positions;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::skills::{SkillAction, SkillRegistry, SkillXpGained};
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for combat systems.
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            execute_attack_commands.in_set(SimulationSet::Commands),
+        )
+        .add_systems(FixedUpdate, pursue_targets.in_set(SimulationSet::Orders))
+        .add_systems(FixedUpdate, process_attacks.in_set(SimulationSet::Actions))
+        .add_systems(FixedUpdate, remove_dead.in_set(SimulationSet::Resolve))
+        .add_systems(Update, update_combat_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+// Component to track who a unit is attacking
+#[derive(Component, Debug)]
+pub struct Attacking {
+    pub target: Entity,
+    pub cooldown: f32,
+}
+
+/// Whoever last hit this, so it can strike back or run.
+#[derive(Component, Debug)]
+pub struct LastAttacker {
+    pub attacker: Entity,
+}
+
+/// Sets warriors on the enemy they were ordered to attack, any other order stops the fight
+fn execute_attack_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    attackers: Query<Option<&Owner>, With<AttackStats>>,
+    fighting: Query<(), With<Attacking>>,
+    targets: Query<Option<&Owner>, (With<Health>, Without<Garrisoned>)>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Attack { unit, target } = command else {
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| fighting.contains(*unit))
+            {
+                commands.entity(unit).remove::<Attacking>();
+            }
+            continue;
+        };
+
+        let (Ok(attacker_owner), Ok(target_owner)) = (attackers.get(*unit), targets.get(*target))
+        else {
+            continue;
+        };
+
+        if unit == target || attacker_owner.copied() == target_owner.copied() {
+            info!(
+                "<execute_attack_commands> {:?} won't attack its own side",
+                unit
+            );
+            continue;
+        }
+
+        info!(
+            "<execute_attack_commands> {:?} is attacking {:?}",
+            unit, target
+        );
+        commands.entity(*unit).insert(Attacking {
+            target: *target,
+            cooldown: 0.0,
+        });
+    }
+}
+
+/// Moves attackers into range of their target, and drops attacks on dead targets
+fn pursue_targets(
+    mut commands: Commands,
+    mut attackers: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Attacking,
+            &AttackStats,
+            &mut MoveTarget,
+        ),
+        Without<Moving>,
+    >,
+    // Units that went inside a building can't be reached any more
+    targets: Query<&GridCoords, (With<Health>, Without<Garrisoned>)>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+) {
+    for (entity, coords, attacking, stats, mut move_target) in &mut attackers {
+        let Ok(target_coords) = targets.get(attacking.target) else {
+            info!("<pursue_targets> Target is gone, stopping attack");
+            commands.entity(entity).remove::<Attacking>();
+            continue;
+        };
+
+        if grid_distance(coords, target_coords) <= stats.range {
+            // Close enough, stand and fight
+            if move_target.destination.is_some() {
+                move_target.destination = None;
+                move_target.path.clear();
+            }
+            continue;
+        }
+
+        // Already walking to a cell in range of the target
+        if move_target
+            .destination
+            .is_some_and(|dest| grid_distance(&dest, target_coords) <= stats.range)
+        {
+            continue;
+        }
+
+        let mut approach_positions = find_adjacent_positions(*target_coords, &obstacles);
+        approach_positions.sort_by_key(|pos| (pos.x - coords.x).pow(2) + (pos.y - coords.y).pow(2));
+
+        if let Some(dest) = approach_positions.first() {
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
+        }
+    }
+}
+
+/// Deals damage to targets in range, scaled by the combat skill
+fn process_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut attackers: Query<
+        (
+            Entity,
+            &GridCoords,
+            &mut Attacking,
+            &AttackStats,
+            &Skills,
+            &SkillProgression,
+        ),
+        Without<Moving>,
+    >,
+    mut targets: Query<(&GridCoords, &mut Health, Option<&Garrison>)>,
+    warriors: Query<(), With<Warrior>>,
+    skill_registry: Res<SkillRegistry>,
+    mut xp_events: EventWriter<SkillXpGained>,
+) {
+    for (entity, coords, mut attacking, stats, skills, progression) in &mut attackers {
+        attacking.cooldown = (attacking.cooldown - time.delta_secs()).max(0.0);
+
+        let Ok((target_coords, mut health, garrison)) = targets.get_mut(attacking.target) else {
+            continue;
+        };
+
+        if attacking.cooldown > 0.0 || grid_distance(coords, target_coords) > stats.range {
+            continue;
+        }
+
+        // Warriors inside a building help defend it
+        let defence = garrison.map_or(1.0, |garrison| garrison.damage_taken(&warriors));
+        let perk_damage =
+            1.0 + skill_registry.perk_bonus(progression, SkillKind::Combat, PerkEffect::Damage);
+        let damage = stats.damage * skills.combat * perk_damage * defence;
+        health.current -= damage;
+        attacking.cooldown = stats.cooldown;
+        commands
+            .entity(attacking.target)
+            .try_insert(LastAttacker { attacker: entity });
+
+        info!(
+            "<process_attacks> {:?} hit {:?} for {:.1} ({:.1}/{:.1})",
+            entity, attacking.target, damage, health.current, health.max
+        );
+
+        // Gain combat XP
+        xp_events.send(SkillXpGained {
+            entity,
+            action: SkillAction::Attack,
+        });
+    }
+}
+
+/// Removes anything whose health has run out, leaving what it carried on the ground
+fn remove_dead(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &Health,
+        Option<&Name>,
+        Option<&Inventory>,
+        Option<&GridCoords>,
+    )>,
+    mut drop_events: EventWriter<DropItems>,
+) {
+    for (entity, health, name, inventory, coords) in &query {
+        if health.current <= 0.0 {
+            let name = name.map(|name| name.as_str()).unwrap_or("Entity");
+            info!("<remove_dead> {} {:?} was destroyed", name, entity);
+
+            if let (Some(inventory), Some(coords)) = (inventory, coords) {
+                for stack in inventory.slots.iter().flatten() {
+                    drop_events.send(DropItems {
+                        coords: *coords,
+                        item: stack.item.clone(),
+                        quantity: stack.quantity,
+                    });
+                }
+            }
+
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Shows health and attack status for the selected entity
+fn update_combat_ui(
+    selected: Query<(&Health, Option<&Attacking>), With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((health, attacking)) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new(format!("Health: {:.0}/{:.0}", health.current, health.max)),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        if attacking.is_some() {
+            parent.spawn((
+                Text::new("Attacking"),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.3, 0.3)),
+            ));
+        }
+    });
+}
@@ -1,18 +1,44 @@
-use crate::components::inventory::{Inventory, InventorySettings, ResourceType};
-use crate::components::skills::{SkillProgression, Skills};
+use crate::components::combat::Health;
+use crate::components::entities::Worker;
+use crate::components::inventory::Inventory;
+use crate::components::items::ItemId;
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::skills::{PerkEffect, SkillKind, SkillProgression, Skills};
 use crate::components::ui::EntityInfoPanel;
-use crate::components::unit::Selected;
+use crate::components::unit::{Owner, Selectable, Selected};
+use crate::systems::crafting::CraftingStation;
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::Garrison;
+use crate::systems::housing::{Homeless, Housing, HOMELESS_EFFICIENCY};
+use crate::systems::movement::{grid_distance, grid_to_translation};
+use crate::systems::production::ProductionQueue;
+use crate::systems::resource_gathering::find_adjacent_positions;
+use crate::systems::scene::find_entity_layer;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::skills::{SkillAction, SkillRegistry, SkillXpGained};
+use crate::systems::ui::InfoPanelSet;
+use crate::systems::victory::MatchStats;
 use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// Plugin for construction systems.
 pub struct ConstructionPlugin;
 
 impl Plugin for ConstructionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, construction_system)
-            .add_systems(Update, start_construction)
-            .add_systems(Update, process_construction)
-            .add_systems(Update, update_construction_ui);
+        app.add_systems(
+            FixedUpdate,
+            execute_build_commands.in_set(SimulationSet::Commands),
+        )
+        .add_systems(
+            FixedUpdate,
+            (construction_system, process_construction).in_set(SimulationSet::Actions),
+        )
+        .add_systems(
+            Update,
+            update_construction_ui.in_set(InfoPanelSet::Sections),
+        );
     }
 }
 
@@ -26,9 +52,22 @@ pub struct Constructing {
     pub building_type: BuildingType,
     pub progress: f32,
     pub required_time: f32,
+    pub site: Entity,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// A building that has been placed but not finished yet
+#[derive(Component, Debug)]
+pub struct ConstructionSite {
+    pub building_type: BuildingType,
+}
+
+// A completed building
+#[derive(Component, Debug)]
+pub struct Building {
+    pub building_type: BuildingType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BuildingType {
     House,
     Workshop,
@@ -36,85 +75,197 @@ pub enum BuildingType {
 }
 
 impl BuildingType {
-    pub fn get_cost(&self) -> Vec<(ResourceType, u32)> {
+    pub fn get_cost(&self) -> Vec<(ItemId, u32)> {
+        match self {
+            BuildingType::House => vec![(ItemId::WOOD, 5), (ItemId::STONE, 3)],
+            BuildingType::Workshop => {
+                vec![(ItemId::WOOD, 10), (ItemId::STONE, 5), (ItemId::GOLD, 2)]
+            }
+            BuildingType::Wall => vec![(ItemId::STONE, 5)],
+        }
+    }
+
+    pub fn max_health(&self) -> f32 {
         match self {
-            BuildingType::House => vec![(ResourceType::Wood, 5), (ResourceType::Stone, 3)],
-            BuildingType::Workshop => vec![
-                (ResourceType::Wood, 10),
-                (ResourceType::Stone, 5),
-                (ResourceType::Gold, 2),
-            ],
-            BuildingType::Wall => vec![(ResourceType::Stone, 5)],
+            BuildingType::House => 300.0,
+            BuildingType::Workshop => 400.0,
+            BuildingType::Wall => 600.0,
+        }
+    }
+
+    pub fn sprite_path(&self) -> &'static str {
+        match self {
+            BuildingType::House => "walls1.png",
+            BuildingType::Workshop => "wall3.png",
+            BuildingType::Wall => "wall1.png",
+        }
+    }
+}
+
+/// Seconds to finish a building, faster with higher skill
+pub fn construction_time(construction_skill: f32) -> f32 {
+    let base_time = 10.0;
+    let skill_modifier = 0.7 + (0.3 * construction_skill); // 1.0 skill = normal, 5.0 = twice as fast
+    base_time / skill_modifier
+}
+
+/// Places a see-through site in the entity layer so the player can see what is being built
+pub fn spawn_construction_site(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    layer: Entity,
+    building_type: BuildingType,
+    owner: Owner,
+    coords: GridCoords,
+) -> Entity {
+    let site = commands
+        .spawn((
+            ConstructionSite { building_type },
+            Name::new(format!("{:?} (under construction)", building_type)),
+            owner,
+            Health::new(building_type.max_health()),
+            Selectable,
+            Collider,
+            Sprite {
+                image: asset_server.load(building_type.sprite_path()),
+                color: Color::srgba(1.0, 1.0, 1.0, 0.5),
+                custom_size: Some(Vec2::new(64.0, 64.0)),
+                ..default()
+            },
+            Transform::from_translation(grid_to_translation(coords, 0.0)),
+            coords,
+        ))
+        .id();
+    commands.entity(layer).add_child(site);
+    site
+}
+
+/// Turns a construction site into a working building of its type
+pub fn finish_building(commands: &mut Commands, site: Entity, building_type: BuildingType) {
+    commands.entity(site).remove::<ConstructionSite>().insert((
+        Building { building_type },
+        Name::new(format!("{:?}", building_type)),
+    ));
+
+    match building_type {
+        BuildingType::House => {
+            commands.entity(site).insert((
+                ProductionQueue::default(),
+                Housing::new(4),
+                Garrison::new(4),
+            ));
         }
+        BuildingType::Workshop => {
+            commands
+                .entity(site)
+                .insert((CraftingStation::default(), Garrison::new(2)));
+        }
+        BuildingType::Wall => {}
     }
 }
 
-// Start construction when pressing B key and clicking
-fn start_construction(
+/// Pays for ordered buildings, places their sites and sends the builder over,
+/// any other order stops the builder
+fn execute_build_commands(
     mut commands: Commands,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    mut selected_builders: Query<
-        (Entity, &Skills, &mut Inventory, &InventorySettings),
-        With<Selected>,
+    mut game_commands: EventReader<GameCommand>,
+    mut builders: Query<
+        (
+            &Skills,
+            &GridCoords,
+            Option<&Owner>,
+            &mut Inventory,
+            &mut MoveTarget,
+        ),
+        With<Worker>,
     >,
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
+    constructing: Query<(), With<Constructing>>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    layers: Query<(Entity, &LayerMetadata)>,
+    asset_server: Res<AssetServer>,
 ) {
-    if keyboard.pressed(KeyCode::KeyB) && mouse_button.just_pressed(MouseButton::Left) {
-        // Get the selected builder
-        if let Ok((builder_entity, skills, mut inventory, _settings)) =
-            selected_builders.get_single_mut()
-        {
-            // Get construction skill
-            let construction_skill = skills.construction;
-
-            // Get cursor position for placement
-            let window = windows.single();
-            if let Some(_cursor_pos) = window.cursor_position() {
-                let (camera, camera_transform) = camera_q.single();
-                if let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, _cursor_pos) {
-                    let _cursor_pos = cursor_ray.origin.truncate();
-
-                    // Determine building type (using keyboard modifier keys)
-                    let building_type = if keyboard.pressed(KeyCode::ShiftLeft) {
-                        BuildingType::Workshop
-                    } else if keyboard.pressed(KeyCode::ControlLeft) {
-                        BuildingType::Wall
-                    } else {
-                        BuildingType::House
-                    };
-
-                    // Check if builder has required resources
-                    let cost = building_type.get_cost();
-                    let has_resources = cost.iter().all(|(resource_type, amount)| {
-                        inventory.count_resource(*resource_type) >= *amount
-                    });
-
-                    if has_resources {
-                        // Consume resources
-                        for (resource_type, amount) in cost {
-                            inventory.remove_resource(resource_type, amount);
-                        }
-
-                        // Start construction - faster with higher skill
-                        let base_time = 10.0;
-                        let skill_modifier = 0.7 + (0.3 * construction_skill); // 1.0 skill = normal, 5.0 = twice as fast
-                        let required_time = base_time / skill_modifier;
-
-                        commands.entity(builder_entity).insert(Constructing {
-                            building_type,
-                            progress: 0.0,
-                            required_time,
-                        });
-
-                        info!("Started construction of {:?}", building_type);
-                    } else {
-                        info!("Not enough resources for {:?}", building_type);
-                    }
-                }
+    for command in game_commands.read() {
+        let GameCommand::Build {
+            unit,
+            building_type,
+            at: site_coords,
+        } = command
+        else {
+            // The site stays, so the job board can hand it to someone else
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| constructing.contains(*unit))
+            {
+                commands.entity(unit).remove::<Constructing>();
             }
+            continue;
+        };
+
+        let Ok((skills, builder_coords, owner, mut inventory, mut move_target)) =
+            builders.get_mut(*unit)
+        else {
+            continue;
+        };
+
+        if obstacles
+            .iter()
+            .any(|o| o.x == site_coords.x && o.y == site_coords.y)
+        {
+            info!("Cannot build at {:?}, the cell is occupied", site_coords);
+            continue;
+        }
+
+        let Some(layer) = find_entity_layer(&layers) else {
+            info!("No entity layer to place the building in");
+            continue;
+        };
+
+        // Check if builder has required resources
+        let cost = building_type.get_cost();
+        let has_resources = cost
+            .iter()
+            .all(|(item, amount)| inventory.count_item(item) >= *amount);
+
+        if !has_resources {
+            info!("Not enough resources for {:?}", building_type);
+            continue;
+        }
+
+        // Consume resources
+        for (item, amount) in cost {
+            inventory.remove_item(&item, amount);
+        }
+
+        let site = spawn_construction_site(
+            &mut commands,
+            &asset_server,
+            layer,
+            *building_type,
+            owner.copied().unwrap_or_default(),
+            *site_coords,
+        );
+
+        // Walk the builder next to the site
+        let mut approach_positions = find_adjacent_positions(*site_coords, &obstacles);
+        approach_positions.sort_by_key(|pos| {
+            (pos.x - builder_coords.x).pow(2) + (pos.y - builder_coords.y).pow(2)
+        });
+        if let Some(dest) = approach_positions.first() {
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
         }
+
+        commands.entity(*unit).insert(Constructing {
+            building_type: *building_type,
+            progress: 0.0,
+            required_time: construction_time(skills.construction),
+            site,
+        });
+
+        info!(
+            "Started construction of {:?} at {:?}",
+            building_type, site_coords
+        );
     }
 }
 
@@ -122,33 +273,59 @@ fn start_construction(
 fn process_construction(
     mut commands: Commands,
     time: Res<Time>,
-    mut builders: Query<(
-        Entity,
-        &mut Constructing,
-        &mut Skills,
-        &mut SkillProgression,
-    )>,
+    mut builders: Query<
+        (
+            Entity,
+            &GridCoords,
+            &mut Constructing,
+            &SkillProgression,
+            Option<&Homeless>,
+        ),
+        Without<Moving>,
+    >,
+    mut sites: Query<(&GridCoords, &mut Sprite, Option<&Owner>), With<ConstructionSite>>,
+    skill_registry: Res<SkillRegistry>,
+    mut xp_events: EventWriter<SkillXpGained>,
+    mut stats: ResMut<MatchStats>,
 ) {
-    for (entity, mut constructing, mut skills, mut progression) in &mut builders {
-        constructing.progress += time.delta_secs();
+    for (entity, builder_coords, mut constructing, progression, homeless) in &mut builders {
+        let Ok((site_coords, mut sprite, owner)) = sites.get_mut(constructing.site) else {
+            info!("Construction site is gone, stopping construction");
+            commands.entity(entity).remove::<Constructing>();
+            continue;
+        };
+
+        // Only build while standing next to the site
+        if grid_distance(site_coords, builder_coords) > 1 {
+            continue;
+        }
+
+        let efficiency = if homeless.is_some() {
+            HOMELESS_EFFICIENCY
+        } else {
+            1.0
+        };
+        let perk_speed = 1.0
+            + skill_registry.perk_bonus(
+                progression,
+                SkillKind::Construction,
+                PerkEffect::BuildSpeed,
+            );
+        constructing.progress += time.delta_secs() * efficiency * perk_speed;
 
         // Construction complete
         if constructing.progress >= constructing.required_time {
-            // Spawn the building at builder location
-            // (This is a simplified version - you'd want to use the cursor position from before)
-
             info!("Construction of {:?} complete!", constructing.building_type);
 
+            sprite.color = Color::WHITE;
+            finish_building(&mut commands, constructing.site, constructing.building_type);
+            stats.record_built(owner.copied().unwrap_or_default());
+
             // Gain construction XP
-            progression.construction_xp += 10.0;
-            if progression.construction_xp >= 100.0 * skills.construction {
-                progression.construction_xp = 0.0;
-                skills.construction += 0.1;
-                info!(
-                    "Character improved construction to {:.1}",
-                    skills.construction
-                );
-            }
+            xp_events.send(SkillXpGained {
+                entity,
+                action: SkillAction::Construct,
+            });
 
             // Remove construction component
             commands.entity(entity).remove::<Constructing>();
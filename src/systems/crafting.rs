@@ -0,0 +1,405 @@
+use crate::components::entities::Worker;
+use crate::components::inventory::{Inventory, Stockpile, StorageFilter};
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::skills::{PerkEffect, SkillKind, SkillProgression, Skills};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::Garrison;
+use crate::systems::housing::{Homeless, HOMELESS_EFFICIENCY};
+use crate::systems::inventory::{stockpile_add, stockpile_count, stockpile_take};
+use crate::systems::item_piles::DropItems;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::skills::{SkillAction, SkillRegistry, SkillXpGained};
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for workshop crafting systems.
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_recipe_buttons.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                execute_craft_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(FixedUpdate, process_crafting.in_set(SimulationSet::Actions))
+            .add_systems(Update, update_crafting_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// How many orders a single workshop can have waiting.
+const MAX_QUEUE_LENGTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recipe {
+    Planks,
+    Bricks,
+    Tools,
+}
+
+impl Recipe {
+    pub const ALL: [Recipe; 3] = [Recipe::Planks, Recipe::Bricks, Recipe::Tools];
+
+    pub fn inputs(&self) -> Vec<(ItemId, u32)> {
+        match self {
+            Recipe::Planks => vec![(ItemId::WOOD, 2)],
+            Recipe::Bricks => vec![(ItemId::STONE, 2)],
+            Recipe::Tools => vec![(ItemId::PLANKS, 2), (ItemId::GOLD, 1)],
+        }
+    }
+
+    pub fn output(&self) -> (ItemId, u32) {
+        match self {
+            Recipe::Planks => (ItemId::PLANKS, 2),
+            Recipe::Bricks => (ItemId::BRICKS, 1),
+            Recipe::Tools => (ItemId::TOOLS, 1),
+        }
+    }
+
+    /// Seconds to craft with a crafting skill of 1.0
+    pub fn crafting_time(&self) -> f32 {
+        match self {
+            Recipe::Planks => 4.0,
+            Recipe::Bricks => 6.0,
+            Recipe::Tools => 10.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Recipe::Planks => "Planks",
+            Recipe::Bricks => "Bricks",
+            Recipe::Tools => "Tools",
+        }
+    }
+}
+
+/// Queue of crafting orders for a workshop.
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CraftingStation {
+    pub queue: Vec<Recipe>,
+    pub progress: f32,
+}
+
+/// Button in the info panel that orders a recipe.
+#[derive(Component)]
+pub struct CraftRecipeButton(pub Recipe);
+
+/// The worker doing the crafting, which is the first worker inside the workshop
+fn assigned_crafter(garrison: &Garrison, workers: &Query<(), With<Worker>>) -> Option<Entity> {
+    garrison
+        .occupants
+        .iter()
+        .copied()
+        .find(|occupant| workers.contains(*occupant))
+}
+
+/// Orders the selected workshop to craft a recipe when its button is clicked
+fn handle_recipe_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &CraftRecipeButton)>,
+    stations: Query<Entity, (With<Selected>, With<CraftingStation>)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(station) = stations.get_single() else {
+        return;
+    };
+
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            game_commands.send(GameCommand::Craft {
+                station,
+                recipe: button.0,
+            });
+        }
+    }
+}
+
+/// Queues ordered recipes, taking the inputs from the crafter and the stockpile
+fn execute_craft_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut stations: Query<(&mut CraftingStation, &Garrison, Option<&Owner>)>,
+    mut worker_inventories: Query<&mut Inventory, (With<Worker>, Without<Stockpile>)>,
+    mut stockpile: Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Craft { station, recipe } = command else {
+            continue;
+        };
+
+        let Ok((mut station, garrison, owner)) = stations.get_mut(*station) else {
+            continue;
+        };
+        let owner = owner.copied().unwrap_or_default();
+
+        if station.queue.len() >= MAX_QUEUE_LENGTH {
+            info!("<execute_craft_commands> Crafting queue is full");
+            continue;
+        }
+
+        // Workers inside the workshop can chip in with what they carry
+        let crafters: Vec<Entity> = garrison
+            .occupants
+            .iter()
+            .copied()
+            .filter(|occupant| worker_inventories.contains(*occupant))
+            .collect();
+
+        let available = |item: &ItemId| {
+            let carried: u32 = crafters
+                .iter()
+                .filter_map(|crafter| worker_inventories.get(*crafter).ok())
+                .map(|inventory| inventory.count_item(item))
+                .sum();
+            carried + stockpile_count(&stockpile, owner, item)
+        };
+
+        let inputs = recipe.inputs();
+        let has_resources = inputs
+            .iter()
+            .all(|(item, amount)| available(item) >= *amount);
+
+        if !has_resources {
+            info!(
+                "<execute_craft_commands> Not enough resources for {}",
+                recipe.name()
+            );
+            continue;
+        }
+
+        for (item, amount) in inputs {
+            let mut remaining = amount;
+
+            for crafter in &crafters {
+                if let Ok(mut inventory) = worker_inventories.get_mut(*crafter) {
+                    remaining -= inventory.remove_item(&item, remaining);
+                }
+            }
+
+            stockpile_take(&mut stockpile, owner, &item, remaining);
+        }
+
+        station.queue.push(*recipe);
+        info!("<execute_craft_commands> Queued {}", recipe.name());
+    }
+}
+
+/// Advances crafting at workshops with a worker inside and delivers the output to the stockpile
+fn process_crafting(
+    time: Res<Time>,
+    mut stations: Query<(
+        Entity,
+        &mut CraftingStation,
+        &Garrison,
+        &GridCoords,
+        Option<&Owner>,
+    )>,
+    workers: Query<(), With<Worker>>,
+    mut crafters: Query<
+        (
+            &Skills,
+            &SkillProgression,
+            &mut Inventory,
+            Option<&Homeless>,
+        ),
+        (With<Worker>, Without<Stockpile>),
+    >,
+    mut stockpile: Query<(&mut Inventory, Option<&StorageFilter>, Option<&Owner>), With<Stockpile>>,
+    items: Res<ItemRegistry>,
+    skill_registry: Res<SkillRegistry>,
+    mut xp_events: EventWriter<SkillXpGained>,
+    mut drop_events: EventWriter<DropItems>,
+) {
+    for (entity, mut station, garrison, coords, owner) in &mut stations {
+        let Some(&recipe) = station.queue.first() else {
+            continue;
+        };
+
+        // Nothing happens without someone to do the work
+        let Some(crafter) = assigned_crafter(garrison, &workers) else {
+            continue;
+        };
+
+        let Ok((skills, progression, mut inventory, homeless)) = crafters.get_mut(crafter) else {
+            continue;
+        };
+
+        let efficiency = if homeless.is_some() {
+            HOMELESS_EFFICIENCY
+        } else {
+            1.0
+        };
+        let perk_speed = 1.0
+            + skill_registry.perk_bonus(progression, SkillKind::Crafting, PerkEffect::CraftSpeed);
+        station.progress += time.delta_secs() * skills.crafting * efficiency * perk_speed;
+
+        if station.progress < recipe.crafting_time() {
+            continue;
+        }
+
+        station.queue.remove(0);
+        station.progress = 0.0;
+
+        let (item, amount) = recipe.output();
+        let mut overflow = stockpile_add(
+            &mut stockpile,
+            owner.copied().unwrap_or_default(),
+            &item,
+            amount,
+            &items,
+        );
+
+        // Whatever the stockpile can't take, the crafter carries
+        if overflow > 0 {
+            overflow = inventory.add_item(&item, overflow, &items);
+        }
+
+        // And the rest is left on the ground by the workshop
+        if overflow > 0 {
+            info!(
+                "<process_crafting> No room for {} {}, leaving it at {:?}",
+                overflow, item, coords
+            );
+            drop_events.send(DropItems {
+                coords: *coords,
+                item: item.clone(),
+                quantity: overflow,
+            });
+        }
+
+        info!(
+            "<process_crafting> {:?} crafted {} {}",
+            entity, amount, item
+        );
+
+        // Gain crafting XP
+        xp_events.send(SkillXpGained {
+            entity: crafter,
+            action: SkillAction::Craft,
+        });
+    }
+}
+
+/// Shows crafting progress and the recipe buttons for the selected workshop
+fn update_crafting_ui(
+    selected: Query<(&CraftingStation, &Garrison), With<Selected>>,
+    workers: Query<(), With<Worker>>,
+    crafters: Query<(&Name, &Skills)>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((station, garrison)) = selected.get_single() else {
+        return;
+    };
+
+    let crafter =
+        assigned_crafter(garrison, &workers).and_then(|crafter| crafters.get(crafter).ok());
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new("Crafting"),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        let (crafter_line, crafter_color) = match crafter {
+            Some((name, skills)) => (
+                format!("Crafter: {} (skill {:.1})", name.as_str(), skills.crafting),
+                Color::WHITE,
+            ),
+            None => (
+                "No crafter, garrison a worker here".to_string(),
+                Color::srgb(1.0, 0.5, 0.0),
+            ),
+        };
+
+        parent.spawn((
+            Text::new(crafter_line),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(crafter_color),
+        ));
+
+        if let Some(recipe) = station.queue.first() {
+            let progress_percent = (station.progress / recipe.crafting_time()) * 100.0;
+
+            parent.spawn((
+                Text::new(format!(
+                    "Crafting {}: {:.1}% ({}/{} queued)",
+                    recipe.name(),
+                    progress_percent,
+                    station.queue.len(),
+                    MAX_QUEUE_LENGTH
+                )),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.0, 1.0, 0.0)),
+            ));
+        }
+
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for recipe in Recipe::ALL {
+                    let inputs = recipe
+                        .inputs()
+                        .iter()
+                        .map(|(item, amount)| format!("{} {}", amount, items.name(item)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    row.spawn((
+                        Button,
+                        // Let the panel see the click too, so it doesn't reach the map
+                        FocusPolicy::Pass,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                        CraftRecipeButton(recipe),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(format!("{} ({})", recipe.name(), inputs)),
+                            TextFont {
+                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                font_size: 12.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                }
+            });
+    });
+}
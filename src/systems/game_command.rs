@@ -0,0 +1,146 @@
+use crate::components::items::ItemId;
+use crate::components::unit::UnitType;
+use crate::systems::construction::BuildingType;
+use crate::systems::crafting::Recipe;
+use crate::systems::idle::AutoBehaviour;
+use crate::systems::jobs::JobKind;
+use crate::systems::transfer::{TransferAmount, TransferDirection};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for the commands the player, the AI and tests give to units and buildings.
+pub struct GameCommandPlugin;
+
+impl Plugin for GameCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GameCommand>();
+    }
+}
+
+/// An order for a unit or building.
+///
+/// Everything that changes the game on the player's behalf goes through here,
+/// so the AI, tests and other players can give exactly the same orders.
+///
+/// Commands are carried out at the start of the next simulation tick, in
+/// `SimulationSet::Commands`.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum GameCommand {
+    /// Walk to a cell
+    Move {
+        unit: Entity,
+        destination: GridCoords,
+    },
+    /// Gather from a tree, mine or quarry until told otherwise
+    Gather { unit: Entity, resource: Entity },
+    /// Pay for a building from the unit's inventory, place its site and build it
+    Build {
+        unit: Entity,
+        building_type: BuildingType,
+        at: GridCoords,
+    },
+    /// Walk to another inventory and give or take items
+    Transfer {
+        unit: Entity,
+        target: Entity,
+        item: ItemId,
+        amount: TransferAmount,
+        direction: TransferDirection,
+    },
+    /// Chase and fight another unit or building
+    Attack { unit: Entity, target: Entity },
+    /// Walk into one of your own buildings
+    Garrison { unit: Entity, building: Entity },
+    /// Walk onto a pile and pick it up
+    PickUp { unit: Entity, pile: Entity },
+    /// Drop whatever the unit is doing
+    Stop { unit: Entity },
+    /// Set where a building sends the units it trains
+    SetRallyPoint { building: Entity, at: GridCoords },
+    /// Queue a unit at a building, paid from the stockpile
+    Train {
+        building: Entity,
+        unit_type: UnitType,
+    },
+    /// Queue a recipe at a workshop
+    Craft { station: Entity, recipe: Recipe },
+    /// Send every unit out of a building
+    Eject { building: Entity },
+    /// Mark a resource for the job board, or unmark it
+    ToggleGatherDesignation { resource: Entity },
+    /// Set what a worker does once it runs out of orders
+    SetAutoBehaviour {
+        unit: Entity,
+        behaviour: AutoBehaviour,
+    },
+    /// Sort and compact an inventory
+    SortInventory { owner: Entity },
+    /// Move some of a stack to a slot of the same or another inventory
+    MoveSlot {
+        from: Entity,
+        slot: usize,
+        quantity: u32,
+        to: Entity,
+        to_slot: usize,
+    },
+    /// Drop some of a stack on the ground where its owner stands
+    DropSlot {
+        owner: Entity,
+        slot: usize,
+        quantity: u32,
+    },
+    /// Let an item into a storage, or keep it out
+    ToggleFilter { storage: Entity, item: ItemId },
+    /// Step a worker's priority for a kind of job
+    CyclePriority { unit: Entity, kind: JobKind },
+}
+
+impl GameCommand {
+    /// The unit being given a new order, which replaces whatever it was doing.
+    ///
+    /// `None` for building orders and for settings that don't interrupt the unit.
+    pub fn ordered_unit(&self) -> Option<Entity> {
+        match self {
+            GameCommand::Move { unit, .. }
+            | GameCommand::Gather { unit, .. }
+            | GameCommand::Build { unit, .. }
+            | GameCommand::Transfer { unit, .. }
+            | GameCommand::Attack { unit, .. }
+            | GameCommand::Garrison { unit, .. }
+            | GameCommand::PickUp { unit, .. }
+            | GameCommand::Stop { unit } => Some(*unit),
+            GameCommand::SetRallyPoint { .. }
+            | GameCommand::Train { .. }
+            | GameCommand::Craft { .. }
+            | GameCommand::Eject { .. }
+            | GameCommand::ToggleGatherDesignation { .. }
+            | GameCommand::SetAutoBehaviour { .. }
+            | GameCommand::SortInventory { .. }
+            | GameCommand::MoveSlot { .. }
+            | GameCommand::DropSlot { .. }
+            | GameCommand::ToggleFilter { .. }
+            | GameCommand::CyclePriority { .. } => None,
+        }
+    }
+
+    /// The unit or building taking the order, which must belong to whoever gives it.
+    ///
+    /// `None` for orders about the map rather than anything a player owns.
+    pub fn subject(&self) -> Option<Entity> {
+        match self {
+            GameCommand::SetRallyPoint { building, .. }
+            | GameCommand::Train { building, .. }
+            | GameCommand::Eject { building } => Some(*building),
+            GameCommand::Craft { station, .. } => Some(*station),
+            GameCommand::SetAutoBehaviour { unit, .. }
+            | GameCommand::CyclePriority { unit, .. } => Some(*unit),
+            GameCommand::SortInventory { owner } | GameCommand::DropSlot { owner, .. } => {
+                Some(*owner)
+            }
+            GameCommand::MoveSlot { from, .. } => Some(*from),
+            GameCommand::ToggleFilter { storage, .. } => Some(*storage),
+            GameCommand::ToggleGatherDesignation { .. } => None,
+            _ => self.ordered_unit(),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::components::entities::{Character, Warrior, Worker};
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selectable, Selected};
+use crate::systems::combat::Attacking;
+use crate::systems::construction::Constructing;
+use crate::systems::game_command::GameCommand;
+use crate::systems::movement::{grid_distance, grid_to_translation};
+use crate::systems::resource_gathering::{find_adjacent_positions, Gathering, GatheringIntent};
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for garrisoning units inside buildings.
+pub struct GarrisonPlugin;
+
+impl Plugin for GarrisonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_eject_button.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                (execute_garrison_commands, execute_eject_commands).in_set(SimulationSet::Commands),
+            )
+            .add_systems(FixedUpdate, enter_garrison.in_set(SimulationSet::Actions))
+            .add_systems(
+                FixedUpdate,
+                release_orphaned_units.in_set(SimulationSet::Resolve),
+            )
+            .add_systems(Update, update_garrison_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Production speed bonus for each worker inside a building.
+const WORKER_WORK_BONUS: f32 = 0.25;
+
+/// Damage reduction for each warrior defending a building.
+const WARRIOR_DEFENCE_BONUS: f32 = 0.5;
+
+/// A building that units can shelter inside.
+#[derive(Component, Debug)]
+pub struct Garrison {
+    pub capacity: usize,
+    pub occupants: Vec<Entity>,
+}
+
+impl Garrison {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            occupants: Vec::new(),
+        }
+    }
+
+    /// Work speed multiplier from the workers inside
+    pub fn work_speed(&self, workers: &Query<(), With<Worker>>) -> f32 {
+        let count = self
+            .occupants
+            .iter()
+            .filter(|occupant| workers.contains(**occupant))
+            .count();
+        1.0 + WORKER_WORK_BONUS * count as f32
+    }
+
+    /// Incoming damage multiplier from the warriors inside
+    pub fn damage_taken(&self, warriors: &Query<(), With<Warrior>>) -> f32 {
+        let count = self
+            .occupants
+            .iter()
+            .filter(|occupant| warriors.contains(**occupant))
+            .count();
+        1.0 / (1.0 + WARRIOR_DEFENCE_BONUS * count as f32)
+    }
+}
+
+// Component to track a unit walking to a building to go inside
+#[derive(Component, Debug)]
+pub struct GarrisonIntent {
+    pub building: Entity,
+}
+
+// Component for a unit that is inside a building
+#[derive(Component, Debug)]
+pub struct Garrisoned {
+    pub building: Entity,
+}
+
+/// Button in the info panel that ejects every unit from the selected building.
+#[derive(Component)]
+struct EjectGarrisonButton;
+
+/// Sends units into the building they were ordered into, any other order calls it off
+fn execute_garrison_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut units: Query<(&GridCoords, Option<&Owner>, &mut MoveTarget), With<Character>>,
+    heading_in: Query<(), With<GarrisonIntent>>,
+    buildings: Query<(&GridCoords, Option<&Owner>, &Garrison)>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Garrison { unit, building } = command else {
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| heading_in.contains(*unit))
+            {
+                commands.entity(unit).remove::<GarrisonIntent>();
+            }
+            continue;
+        };
+
+        let Ok((unit_coords, unit_owner, mut move_target)) = units.get_mut(*unit) else {
+            continue;
+        };
+        let Ok((building_coords, building_owner, garrison)) = buildings.get(*building) else {
+            continue;
+        };
+
+        if unit_owner.copied() != building_owner.copied() {
+            info!(
+                "<execute_garrison_commands> {:?} doesn't belong to {:?}'s side",
+                building, unit
+            );
+            continue;
+        }
+
+        if garrison.occupants.len() >= garrison.capacity {
+            info!("<execute_garrison_commands> {:?} is full", building);
+            continue;
+        }
+
+        if grid_distance(unit_coords, building_coords) > 1 {
+            let mut approach_positions = find_adjacent_positions(*building_coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - unit_coords.x).pow(2) + (pos.y - unit_coords.y).pow(2));
+
+            let Some(dest) = approach_positions.first() else {
+                info!("<execute_garrison_commands> No way into {:?}", building);
+                continue;
+            };
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
+        }
+
+        commands.entity(*unit).insert(GarrisonIntent {
+            building: *building,
+        });
+        info!(
+            "<execute_garrison_commands> {:?} is heading into {:?}",
+            unit, building
+        );
+    }
+}
+
+/// Moves units inside once they reach the building
+fn enter_garrison(
+    mut commands: Commands,
+    mut units: Query<
+        (
+            Entity,
+            &GarrisonIntent,
+            &mut GridCoords,
+            &mut Transform,
+            &mut MoveTarget,
+        ),
+        Without<Moving>,
+    >,
+    mut buildings: Query<(&GridCoords, &mut Garrison), Without<GarrisonIntent>>,
+) {
+    for (entity, intent, mut coords, mut transform, mut move_target) in &mut units {
+        let Ok((building_coords, mut garrison)) = buildings.get_mut(intent.building) else {
+            commands.entity(entity).remove::<GarrisonIntent>();
+            continue;
+        };
+
+        if grid_distance(&coords, building_coords) > 1 {
+            continue;
+        }
+
+        commands.entity(entity).remove::<GarrisonIntent>();
+
+        if garrison.occupants.len() >= garrison.capacity {
+            info!("<enter_garrison> {:?} is full", intent.building);
+            continue;
+        }
+
+        garrison.occupants.push(entity);
+
+        // Park the unit on the building's cell, out of sight and out of the way
+        *coords = *building_coords;
+        transform.translation = grid_to_translation(*building_coords, transform.translation.z);
+        move_target.destination = None;
+        move_target.path.clear();
+        shelter_unit(&mut commands, entity, intent.building);
+
+        info!(
+            "<enter_garrison> {:?} entered {:?}",
+            entity, intent.building
+        );
+    }
+}
+
+/// Takes a unit off the map and into a building, dropping whatever it was doing
+pub fn shelter_unit(commands: &mut Commands, unit: Entity, building: Entity) {
+    commands
+        .entity(unit)
+        .remove::<(
+            Selected,
+            Selectable,
+            Collider,
+            Gathering,
+            GatheringIntent,
+            Constructing,
+            Attacking,
+        )>()
+        .insert((Garrisoned { building }, Visibility::Hidden));
+}
+
+/// Puts a unit back on the map at the given cell
+fn release_unit(commands: &mut Commands, unit: Entity, coords: GridCoords, z: f32) {
+    commands.entity(unit).remove::<Garrisoned>().insert((
+        coords,
+        Transform::from_translation(grid_to_translation(coords, z)),
+        Visibility::Inherited,
+        Selectable,
+        Collider,
+    ));
+}
+
+/// Orders the selected building to eject its units when the button is clicked
+fn handle_eject_button(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<&Interaction, With<EjectGarrisonButton>>,
+    buildings: Query<Entity, (With<Selected>, With<Garrison>)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left)
+        || !buttons
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+
+    if let Ok(building) = buildings.get_single() {
+        game_commands.send(GameCommand::Eject { building });
+    }
+}
+
+/// Puts every unit inside an ejected building back on the map around it
+fn execute_eject_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut buildings: Query<(&GridCoords, &mut Garrison)>,
+    units: Query<&Transform, With<Garrisoned>>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Eject { building } = command else {
+            continue;
+        };
+
+        let Ok((building_coords, mut garrison)) = buildings.get_mut(*building) else {
+            continue;
+        };
+
+        let free_cells = find_adjacent_positions(*building_coords, &obstacles);
+        let mut free_cells = free_cells.into_iter();
+
+        // Units that don't fit around the building stay inside
+        let mut remaining = Vec::new();
+
+        for occupant in garrison.occupants.drain(..) {
+            let Ok(transform) = units.get(occupant) else {
+                continue;
+            };
+
+            match free_cells.next() {
+                Some(cell) => release_unit(&mut commands, occupant, cell, transform.translation.z),
+                None => remaining.push(occupant),
+            }
+        }
+
+        if !remaining.is_empty() {
+            info!(
+                "<execute_eject_commands> No room for {} units, they stay inside",
+                remaining.len()
+            );
+        }
+
+        garrison.occupants = remaining;
+    }
+}
+
+/// Lets units out where the building used to be if it was destroyed
+fn release_orphaned_units(
+    mut commands: Commands,
+    units: Query<(Entity, &Garrisoned, &GridCoords, &Transform)>,
+    buildings: Query<(), With<Garrison>>,
+) {
+    for (entity, garrisoned, coords, transform) in &units {
+        if !buildings.contains(garrisoned.building) {
+            info!(
+                "<release_orphaned_units> Building of {:?} is gone, leaving",
+                entity
+            );
+            release_unit(&mut commands, entity, *coords, transform.translation.z);
+        }
+    }
+}
+
+/// Lists the units inside the selected building with an eject button
+fn update_garrison_ui(
+    selected: Query<&Garrison, With<Selected>>,
+    names: Query<&Name>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok(garrison) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new(format!(
+                "Garrison ({}/{})",
+                garrison.occupants.len(),
+                garrison.capacity
+            )),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        for occupant in &garrison.occupants {
+            let name = names
+                .get(*occupant)
+                .map(|name| name.as_str().to_string())
+                .unwrap_or_else(|_| "Unit".to_string());
+
+            parent.spawn((
+                Text::new(name),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        }
+
+        if !garrison.occupants.is_empty() {
+            parent
+                .spawn((
+                    Button,
+                    // Let the panel see the click too, so it doesn't reach the map
+                    FocusPolicy::Pass,
+                    Node {
+                        padding: UiRect::all(Val::Px(4.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                    EjectGarrisonButton,
+                ))
+                .with_children(|button| {
+                    button.spawn((
+                        Text::new("Eject all"),
+                        TextFont {
+                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                            font_size: 12.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                });
+        }
+    });
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use crate::components::entities::Character;
+use crate::components::ui::{EntityInfoPanel, HudPanel};
+use crate::components::unit::{Owner, Selected};
+use crate::systems::multiplayer::LocalPlayer;
+use crate::systems::production::ProductionQueue;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use crate::ClientSet;
+use bevy::prelude::*;
+
+/// Plugin for housing and population systems.
+pub struct HousingPlugin;
+
+impl Plugin for HousingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Population>()
+            .add_systems(PostStartup, setup_population_text.in_set(ClientSet))
+            .add_systems(
+                FixedUpdate,
+                (check_homes, assign_homes, update_population)
+                    .chain()
+                    .in_set(SimulationSet::Resolve),
+            )
+            .add_systems(Update, update_population_text.in_set(ClientSet))
+            .add_systems(Update, update_housing_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Units every player can have without any houses.
+pub const BASE_POPULATION_CAP: u32 = 5;
+
+/// Work speed multiplier for units that lost their home.
+pub const HOMELESS_EFFICIENCY: f32 = 0.75;
+
+/// A building that units can live in.
+#[derive(Component, Debug)]
+pub struct Housing {
+    pub capacity: u32,
+    pub residents: Vec<Entity>,
+}
+
+impl Housing {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            residents: Vec::new(),
+        }
+    }
+
+    pub fn has_room(&self) -> bool {
+        (self.residents.len() as u32) < self.capacity
+    }
+}
+
+/// The house a unit belongs to.
+#[derive(Component, Debug)]
+pub struct Home(pub Entity);
+
+/// The unit's house was destroyed, which hurts its morale and work speed.
+#[derive(Component, Debug)]
+pub struct Homeless;
+
+/// Population numbers for a single player.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlayerPopulation {
+    pub units: u32,
+    pub queued: u32,
+    pub cap: u32,
+}
+
+impl PlayerPopulation {
+    /// True when no more units can be trained, counting ones already queued
+    pub fn is_capped(&self) -> bool {
+        self.units + self.queued >= self.cap
+    }
+}
+
+/// Population counters for every player.
+#[derive(Resource, Debug, Default)]
+pub struct Population {
+    players: HashMap<Owner, PlayerPopulation>,
+}
+
+impl Population {
+    pub fn get(&self, owner: Owner) -> PlayerPopulation {
+        self.players
+            .get(&owner)
+            .copied()
+            .unwrap_or(PlayerPopulation {
+                cap: BASE_POPULATION_CAP,
+                ..default()
+            })
+    }
+}
+
+/// UI text showing the player's population.
+#[derive(Component)]
+struct PopulationText;
+
+/// Adds the population counter to the HUD
+fn setup_population_text(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            PopulationText,
+        ));
+    });
+}
+
+/// Makes units homeless when their house is gone, and forgets residents that died
+fn check_homes(
+    mut commands: Commands,
+    units: Query<(Entity, &Home)>,
+    mut houses: Query<&mut Housing>,
+    characters: Query<(), With<Character>>,
+) {
+    for (entity, home) in &units {
+        if !houses.contains(home.0) {
+            info!("<check_homes> Home of {:?} was destroyed", entity);
+            commands.entity(entity).remove::<Home>().insert(Homeless);
+        }
+    }
+
+    for mut housing in &mut houses {
+        housing
+            .residents
+            .retain(|resident| characters.contains(*resident));
+    }
+}
+
+/// Moves units without a home into a house of the same owner that has room
+fn assign_homes(
+    mut commands: Commands,
+    units: Query<(Entity, Option<&Owner>), (With<Character>, Without<Home>)>,
+    mut houses: Query<(Entity, Option<&Owner>, &mut Housing)>,
+) {
+    for (entity, owner) in &units {
+        let Some((house, _, mut housing)) = houses.iter_mut().find(|(_, house_owner, housing)| {
+            house_owner.copied() == owner.copied() && housing.has_room()
+        }) else {
+            continue;
+        };
+
+        housing.residents.push(entity);
+        commands
+            .entity(entity)
+            .insert(Home(house))
+            .remove::<Homeless>();
+        info!("<assign_homes> {:?} moved into {:?}", entity, house);
+    }
+}
+
+/// Recounts units, queued units and housing for every player
+pub(crate) fn update_population(
+    mut population: ResMut<Population>,
+    units: Query<Option<&Owner>, With<Character>>,
+    houses: Query<(Option<&Owner>, &Housing)>,
+    producers: Query<(Option<&Owner>, &ProductionQueue)>,
+) {
+    let mut players: HashMap<Owner, PlayerPopulation> = HashMap::new();
+
+    for owner in &units {
+        let entry = players
+            .entry(owner.copied().unwrap_or_default())
+            .or_default();
+        entry.units += 1;
+    }
+
+    for (owner, production) in &producers {
+        let entry = players
+            .entry(owner.copied().unwrap_or_default())
+            .or_default();
+        entry.queued += production.queue.len() as u32;
+    }
+
+    for (owner, housing) in &houses {
+        let entry = players
+            .entry(owner.copied().unwrap_or_default())
+            .or_default();
+        entry.cap += housing.capacity;
+    }
+
+    for entry in players.values_mut() {
+        entry.cap += BASE_POPULATION_CAP;
+    }
+
+    population.players = players;
+}
+
+/// Keeps the HUD population counter up to date
+fn update_population_text(
+    population: Res<Population>,
+    local_player: Res<LocalPlayer>,
+    mut text_query: Query<&mut Text, With<PopulationText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let player = population.get(local_player.0);
+    *text = Text::new(format!("Population: {}/{}", player.units, player.cap));
+}
+
+/// Shows residents for houses and the home status for units
+fn update_housing_ui(
+    selected: Query<(Option<&Housing>, Option<&Home>, Option<&Homeless>), With<Selected>>,
+    names: Query<&Name>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((housing, home, homeless)) = selected.get_single() else {
+        return;
+    };
+
+    let line = if let Some(housing) = housing {
+        Some((
+            format!(
+                "Residents: {}/{}",
+                housing.residents.len(),
+                housing.capacity
+            ),
+            Color::WHITE,
+        ))
+    } else if let Some(home) = home {
+        let name = names
+            .get(home.0)
+            .map(|name| name.as_str().to_string())
+            .unwrap_or_else(|_| "House".to_string());
+        Some((format!("Home: {}", name), Color::WHITE))
+    } else if homeless.is_some() {
+        Some((
+            format!("Homeless ({:.0}% efficiency)", HOMELESS_EFFICIENCY * 100.0),
+            Color::srgb(1.0, 0.5, 0.0),
+        ))
+    } else {
+        None
+    };
+
+    let Some((line, color)) = line else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new(line),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(color),
+        ));
+    });
+}
@@ -0,0 +1,392 @@
+use crate::components::entities::{Character, Forest, Mine, Quarry, Worker};
+use crate::components::inventory::Inventory;
+use crate::components::items::ItemRegistry;
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::resources::ResourceNode;
+use crate::components::ui::{EntityInfoPanel, HudPanel};
+use crate::components::unit::{Owner, Selected, SelectionRing};
+use crate::systems::combat::Attacking;
+use crate::systems::construction::Constructing;
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::{GarrisonIntent, Garrisoned};
+use crate::systems::item_piles::PickupOrder;
+use crate::systems::jobs::AssignedJob;
+use crate::systems::movement::grid_distance;
+use crate::systems::multiplayer::LocalPlayer;
+use crate::systems::resource_gathering::{
+    find_adjacent_positions, resource_item, Gathering, GatheringIntent,
+};
+use crate::systems::simulation::SimulationSet;
+use crate::systems::storage::DropOff;
+use crate::systems::transfer::TransferOrder;
+use crate::systems::ui::InfoPanelSet;
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for spotting idle units and putting them back to work.
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostStartup, setup_idle_text.in_set(ClientSet))
+            .add_systems(Update, handle_behaviour_button.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                execute_auto_behaviour_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                (assign_auto_behaviour, auto_gather)
+                    .chain()
+                    .in_set(SimulationSet::Orders),
+            )
+            .add_systems(FixedUpdate, update_idle.in_set(SimulationSet::Resolve))
+            .add_systems(Update, update_idle_text.in_set(ClientSet))
+            .add_systems(Update, cycle_idle_workers.in_set(ClientSet))
+            .add_systems(Update, update_behaviour_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Units doing nothing at all, neither a direct order nor anything they took up themselves.
+pub type IdleFilter = (
+    Without<Moving>,
+    Without<Gathering>,
+    Without<GatheringIntent>,
+    Without<Constructing>,
+    Without<GarrisonIntent>,
+    Without<Garrisoned>,
+    Without<TransferOrder>,
+    Without<PickupOrder>,
+    Without<DropOff>,
+    Without<Attacking>,
+);
+
+// Marks a unit with nothing to do
+#[derive(Component, Debug)]
+pub struct Idle;
+
+/// What a worker does by itself when it runs out of things to do.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AutoBehaviour {
+    /// Stand still until given an order
+    Wait,
+    /// Take work from the job board
+    #[default]
+    TakeJobs,
+    /// Gather from the nearest resource
+    GatherNearest,
+}
+
+impl AutoBehaviour {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AutoBehaviour::Wait => "Wait",
+            AutoBehaviour::TakeJobs => "Take jobs",
+            AutoBehaviour::GatherNearest => "Gather nearest",
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            AutoBehaviour::Wait => AutoBehaviour::TakeJobs,
+            AutoBehaviour::TakeJobs => AutoBehaviour::GatherNearest,
+            AutoBehaviour::GatherNearest => AutoBehaviour::Wait,
+        }
+    }
+}
+
+/// Marker for the idle workers counter in the HUD.
+#[derive(Component)]
+struct IdleText;
+
+/// Button in the info panel that cycles the selected worker's auto behaviour.
+#[derive(Component)]
+struct AutoBehaviourButton;
+
+/// Adds the idle workers counter to the HUD
+fn setup_idle_text(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            IdleText,
+        ));
+    });
+}
+
+/// Gives new workers the default auto behaviour
+fn assign_auto_behaviour(
+    mut commands: Commands,
+    workers: Query<Entity, (With<Worker>, Without<AutoBehaviour>)>,
+) {
+    for entity in &workers {
+        commands.entity(entity).insert(AutoBehaviour::default());
+    }
+}
+
+/// Marks units with nothing to do as idle, and clears the mark once they get going
+#[allow(clippy::type_complexity)]
+fn update_idle(
+    mut commands: Commands,
+    candidates: Query<
+        (Entity, &MoveTarget, Has<Idle>),
+        (With<Character>, IdleFilter, Without<AssignedJob>),
+    >,
+    idle: Query<Entity, With<Idle>>,
+) {
+    let mut still_idle = Vec::new();
+
+    for (entity, move_target, was_idle) in &candidates {
+        if move_target.destination.is_some() {
+            continue;
+        }
+
+        still_idle.push(entity);
+        if !was_idle {
+            commands.entity(entity).insert(Idle);
+        }
+    }
+
+    for entity in &idle {
+        if !still_idle.contains(&entity) {
+            commands.entity(entity).remove::<Idle>();
+        }
+    }
+}
+
+/// Keeps the HUD idle workers counter up to date
+fn update_idle_text(
+    idle_workers: Query<Option<&Owner>, (With<Idle>, With<Worker>)>,
+    local_player: Res<LocalPlayer>,
+    mut texts: Query<(&mut Text, &mut TextColor), With<IdleText>>,
+) {
+    let count = idle_workers
+        .iter()
+        .filter(|owner| owner.copied().unwrap_or_default() == local_player.0)
+        .count();
+
+    for (mut text, mut color) in &mut texts {
+        *text = Text::new(format!("Idle workers: {} (.)", count));
+        color.0 = if count > 0 {
+            Color::srgb(1.0, 0.8, 0.0)
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+/// Pressing period selects the next idle worker and centres the camera on it
+fn cycle_idle_workers(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    idle_workers: Query<(Entity, &GlobalTransform, Option<&Owner>), (With<Idle>, With<Worker>)>,
+    local_player: Res<LocalPlayer>,
+    selected: Query<Entity, With<Selected>>,
+    selection_rings: Query<Entity, With<SelectionRing>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Period) {
+        return;
+    }
+
+    let mut workers: Vec<(Entity, Vec3)> = idle_workers
+        .iter()
+        .filter(|(_, _, owner)| owner.copied().unwrap_or_default() == local_player.0)
+        .map(|(entity, transform, _)| (entity, transform.translation()))
+        .collect();
+
+    if workers.is_empty() {
+        return;
+    }
+
+    // Entity order is stable, so stepping past the current selection cycles through everyone
+    workers.sort_by_key(|(entity, _)| *entity);
+    let current = selected.iter().next();
+    let (next, position) = current
+        .and_then(|current| workers.iter().find(|(entity, _)| *entity > current))
+        .copied()
+        .unwrap_or(workers[0]);
+
+    for entity in &selection_rings {
+        commands.entity(entity).despawn();
+    }
+    for entity in &selected {
+        commands.entity(entity).remove::<Selected>();
+    }
+    commands.entity(next).insert(Selected);
+
+    for mut transform in &mut cameras {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+
+    info!("<cycle_idle_workers> Selected idle worker {:?}", next);
+}
+
+/// Idle workers set to gather go for the nearest resource they have room for
+#[allow(clippy::type_complexity)]
+fn auto_gather(
+    mut commands: Commands,
+    mut workers: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Inventory,
+            &AutoBehaviour,
+            &mut MoveTarget,
+        ),
+        (With<Idle>, With<Worker>),
+    >,
+    resources: Query<
+        (Entity, &GridCoords, Has<Forest>, Has<Mine>, Has<Quarry>),
+        With<ResourceNode>,
+    >,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, coords, inventory, behaviour, mut move_target) in &mut workers {
+        if *behaviour != AutoBehaviour::GatherNearest {
+            continue;
+        }
+
+        let nearest = resources
+            .iter()
+            .filter_map(|(resource, resource_coords, is_tree, is_mine, is_quarry)| {
+                let item = resource_item(is_tree, is_mine, is_quarry)?;
+                (inventory.room_for(&item, &items) > 0).then_some((resource, resource_coords, item))
+            })
+            .min_by_key(|(_, resource_coords, _)| grid_distance(coords, resource_coords));
+
+        let Some((resource, resource_coords, item)) = nearest else {
+            continue;
+        };
+
+        if grid_distance(coords, resource_coords) > 1 {
+            let mut approach_positions = find_adjacent_positions(*resource_coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - coords.x).pow(2) + (pos.y - coords.y).pow(2));
+
+            let Some(dest) = approach_positions.first() else {
+                continue;
+            };
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
+        }
+
+        commands
+            .entity(entity)
+            .remove::<Idle>()
+            .insert(GatheringIntent {
+                target: resource,
+                item,
+            });
+        info!("<auto_gather> {:?} is off to gather {:?}", entity, resource);
+    }
+}
+
+/// Orders the selected worker onto its next auto behaviour when its button is clicked
+fn handle_behaviour_button(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<&Interaction, With<AutoBehaviourButton>>,
+    selected: Query<(Entity, &AutoBehaviour), With<Selected>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left)
+        || !buttons
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+
+    if let Ok((unit, behaviour)) = selected.get_single() {
+        game_commands.send(GameCommand::SetAutoBehaviour {
+            unit,
+            behaviour: behaviour.next(),
+        });
+    }
+}
+
+/// Changes auto behaviours as ordered
+fn execute_auto_behaviour_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut workers: Query<&mut AutoBehaviour>,
+) {
+    for command in game_commands.read() {
+        if let GameCommand::SetAutoBehaviour { unit, behaviour } = command {
+            if let Ok(mut auto_behaviour) = workers.get_mut(*unit) {
+                *auto_behaviour = *behaviour;
+            }
+        }
+    }
+}
+
+/// Shows whether the selected worker is idle and what it does when it is
+fn update_behaviour_ui(
+    selected: Query<(&AutoBehaviour, Has<Idle>), With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((behaviour, idle)) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        if idle {
+            parent.spawn((
+                Text::new("Idle"),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.8, 0.0)),
+            ));
+        }
+
+        parent
+            .spawn((
+                Button,
+                // Let the panel see the click too, so it doesn't reach the map
+                FocusPolicy::Pass,
+                Node {
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                AutoBehaviourButton,
+            ))
+            .with_children(|button| {
+                button.spawn((
+                    Text::new(format!("When idle: {}", behaviour.name())),
+                    TextFont {
+                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                        font_size: 12.0,
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+            });
+    });
+}
@@ -0,0 +1,291 @@
+use crate::components::combat::Health;
+use crate::components::entities::{Character, Forest, Mine, Quarry, Warrior, Worker};
+use crate::components::inventory::Inventory;
+use crate::components::resources::ResourceNode;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::construction::BuildingType;
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::{Garrison, Garrisoned};
+use crate::systems::item_piles::ItemPile;
+use crate::systems::multiplayer::LocalPlayer;
+use crate::systems::production::ProductionQueue;
+use crate::systems::transfer::TransferSettings;
+use crate::systems::ui::{pointer_over_ui, PanelInteractions};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin that turns mouse and keyboard input into game commands.
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorPosition>()
+            .add_systems(
+                Update,
+                (
+                    issue_right_click_commands,
+                    issue_build_command,
+                    issue_designation_command,
+                    issue_stop_commands,
+                )
+                    .after(update_cursor_position),
+            )
+            .add_systems(Update, update_cursor_position);
+    }
+}
+
+/// Where the cursor points this frame, worked out once for all the input systems.
+#[derive(Resource, Debug, Default)]
+pub struct CursorPosition {
+    /// World position, `None` while the cursor is outside the window
+    pub world: Option<Vec2>,
+    /// Map cell under the cursor
+    pub cell: Option<GridCoords>,
+}
+
+/// Whether a point in the world is inside an entity's sprite
+fn sprite_contains(transform: &GlobalTransform, sprite: &Sprite, point: Vec2) -> bool {
+    let size = sprite.custom_size.unwrap_or(Vec2::new(64.0, 64.0));
+    let pos = transform.translation().truncate();
+
+    (point.x - pos.x).abs() <= size.x / 2.0 && (point.y - pos.y).abs() <= size.y / 2.0
+}
+
+/// Converts the cursor to a world position and map cell
+fn update_cursor_position(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    ldtk_worlds: Query<&GlobalTransform, With<LdtkProjectHandle>>,
+    mut cursor: ResMut<CursorPosition>,
+) {
+    cursor.world = None;
+    cursor.cell = None;
+
+    let Some(cursor_position) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
+    let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
+        return;
+    };
+    let world = cursor_ray.origin.truncate();
+    cursor.world = Some(world);
+
+    // Cells are counted from the LDtk world's origin
+    let Ok(ldtk_world_transform) = ldtk_worlds.get_single() else {
+        return;
+    };
+    let relative = world - ldtk_world_transform.translation().truncate();
+    cursor.cell = Some(GridCoords {
+        x: (relative.x / 64.0).floor() as i32,
+        y: (relative.y / 64.0).floor() as i32,
+    });
+}
+
+/// Whether the player at this computer may give the order, the same check
+/// `exchange_turns` makes when the turns come in
+fn is_own_order(
+    command: &GameCommand,
+    owners: &Query<Option<&Owner>>,
+    local_player: &LocalPlayer,
+) -> bool {
+    let owner = command
+        .subject()
+        .map(|subject| owners.get(subject).ok().flatten().copied());
+    !owner.is_some_and(|owner| owner.unwrap_or_default() != local_player.0)
+}
+
+/// Right-clicking orders the selected units based on what is under the cursor
+fn issue_right_click_commands(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    panel_interactions: PanelInteractions,
+    selected_units: Query<
+        (Entity, Option<&Owner>, Has<Warrior>, Has<Worker>),
+        (With<Selected>, With<Character>),
+    >,
+    selected_producers: Query<Entity, (With<Selected>, With<ProductionQueue>)>,
+    targets: Query<
+        (Entity, &GlobalTransform, &Sprite, Option<&Owner>),
+        (With<Health>, Without<Garrisoned>),
+    >,
+    buildings: Query<(Entity, &GlobalTransform, &Sprite, Option<&Owner>), With<Garrison>>,
+    resources: Query<
+        (Entity, &GlobalTransform, &Sprite),
+        Or<(With<Forest>, With<Mine>, With<Quarry>)>,
+    >,
+    piles: Query<(Entity, &GridCoords), With<ItemPile>>,
+    holders: Query<(Entity, &GridCoords), (With<Inventory>, Without<Selected>)>,
+    transfer_settings: Res<TransferSettings>,
+    owners: Query<Option<&Owner>>,
+    local_player: Res<LocalPlayer>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Right) || pointer_over_ui(&panel_interactions) {
+        return;
+    }
+
+    let (Some(world), Some(cell)) = (cursor.world, cursor.cell) else {
+        return;
+    };
+
+    for building in &selected_producers {
+        let command = GameCommand::SetRallyPoint { building, at: cell };
+        if is_own_order(&command, &owners, &local_player) {
+            game_commands.send(command);
+        }
+    }
+
+    for (unit, owner, is_warrior, is_worker) in &selected_units {
+        let command = if keyboard.pressed(KeyCode::KeyT) {
+            // T + right-click is a transfer order
+            let Some((target, _)) = holders.iter().find(|(_, coords)| **coords == cell) else {
+                info!(
+                    "<issue_right_click_commands> Nothing with an inventory at {:?}",
+                    cell
+                );
+                continue;
+            };
+            GameCommand::Transfer {
+                unit,
+                target,
+                item: transfer_settings.item.clone(),
+                amount: transfer_settings.amount,
+                direction: transfer_settings.direction,
+            }
+        } else if let Some((target, ..)) =
+            targets
+                .iter()
+                .find(|(target, transform, sprite, target_owner)| {
+                    is_warrior
+                        && *target != unit
+                        && target_owner.copied() != owner.copied()
+                        && sprite_contains(transform, sprite, world)
+                })
+        {
+            GameCommand::Attack { unit, target }
+        } else if let Some((building, ..)) =
+            buildings
+                .iter()
+                .find(|(_, transform, sprite, building_owner)| {
+                    building_owner.copied() == owner.copied()
+                        && sprite_contains(transform, sprite, world)
+                })
+        {
+            GameCommand::Garrison { unit, building }
+        } else if let Some((pile, _)) = piles.iter().find(|(_, coords)| **coords == cell) {
+            GameCommand::PickUp { unit, pile }
+        } else if let Some((resource, ..)) = resources
+            .iter()
+            .find(|(_, transform, sprite)| is_worker && sprite_contains(transform, sprite, world))
+        {
+            GameCommand::Gather { unit, resource }
+        } else {
+            GameCommand::Move {
+                unit,
+                destination: cell,
+            }
+        };
+
+        if !is_own_order(&command, &owners, &local_player) {
+            info!(
+                "<issue_right_click_commands> {:?} isn't ours to command",
+                unit
+            );
+            continue;
+        }
+
+        info!("<issue_right_click_commands> {:?}", command);
+        game_commands.send(command);
+    }
+}
+
+/// Holding B and left-clicking orders the selected worker to build there,
+/// Shift for a workshop and Ctrl for a wall
+fn issue_build_command(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    builders: Query<Entity, (With<Selected>, With<Worker>)>,
+    owners: Query<Option<&Owner>>,
+    local_player: Res<LocalPlayer>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !keyboard.pressed(KeyCode::KeyB) || !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (Ok(unit), Some(at)) = (builders.get_single(), cursor.cell) else {
+        return;
+    };
+
+    let building_type = if keyboard.pressed(KeyCode::ShiftLeft) {
+        BuildingType::Workshop
+    } else if keyboard.pressed(KeyCode::ControlLeft) {
+        BuildingType::Wall
+    } else {
+        BuildingType::House
+    };
+
+    let command = GameCommand::Build {
+        unit,
+        building_type,
+        at,
+    };
+    if is_own_order(&command, &owners, &local_player) {
+        game_commands.send(command);
+    }
+}
+
+/// Holding G and left-clicking a resource marks it for gathering, or unmarks it
+fn issue_designation_command(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    panel_interactions: PanelInteractions,
+    resources: Query<(Entity, &GridCoords), With<ResourceNode>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !keyboard.pressed(KeyCode::KeyG)
+        || !mouse_button.just_pressed(MouseButton::Left)
+        || pointer_over_ui(&panel_interactions)
+    {
+        return;
+    }
+
+    let Some(cell) = cursor.cell else {
+        return;
+    };
+
+    if let Some((resource, _)) = resources.iter().find(|(_, coords)| **coords == cell) {
+        game_commands.send(GameCommand::ToggleGatherDesignation { resource });
+    }
+}
+
+/// Pressing S stops the selected units
+fn issue_stop_commands(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    selected_units: Query<Entity, (With<Selected>, With<Character>)>,
+    owners: Query<Option<&Owner>>,
+    local_player: Res<LocalPlayer>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyS) {
+        return;
+    }
+
+    for unit in &selected_units {
+        let command = GameCommand::Stop { unit };
+        if is_own_order(&command, &owners, &local_player) {
+            game_commands.send(command);
+        }
+    }
+}
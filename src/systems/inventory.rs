@@ -1,90 +1,829 @@
+use crate::components::entities::Chest;
 use crate::components::inventory::*;
+use crate::components::items::{ItemId, ItemRegistry};
 use crate::components::ui::EntityInfoPanel;
-use crate::components::unit::Selected;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::game_command::GameCommand;
+use crate::systems::item_piles::DropItems;
+use crate::systems::movement::{grid_distance, ENCUMBRANCE_THRESHOLD};
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::{pointer_over_ui, InfoPanelSet, PanelInteractions};
+use crate::ClientSet;
 use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::GridCoords;
 
 /// Plugin for inventory systems.
 pub struct InventoryPlugin;
 
 impl Plugin for InventoryPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_inventory_ui);
+        app.init_resource::<DragState>()
+            .add_systems(Startup, setup_inventory_overlay.in_set(ClientSet))
+            .add_systems(
+                Update,
+                (
+                    handle_sort_buttons,
+                    start_slot_drag,
+                    drop_slot_drag,
+                    update_drag_icon,
+                    update_slot_tooltip,
+                )
+                    .chain()
+                    .in_set(InfoPanelSet::Input),
+            )
+            .add_systems(
+                FixedUpdate,
+                execute_inventory_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(Update, update_inventory_ui.in_set(InfoPanelSet::Sections));
     }
 }
 
-/// Update the UI to show inventory when an entity with inventory is selected
-fn update_inventory_ui(
-    selected_entities: Query<(Entity, &Inventory), With<Selected>>,
-    inventory_settings: Query<&InventorySettings>,
-    mut commands: Commands,
-    panel_query: Query<Entity, With<EntityInfoPanel>>,
+/// Size of a slot in the inventory grid, in pixels.
+const SLOT_SIZE: f32 = 40.0;
+
+/// Size of an item icon inside a slot, in pixels.
+const ICON_SIZE: f32 = 28.0;
+
+/// A slot in an inventory grid, pointing at the inventory it shows.
+#[derive(Component, Debug)]
+struct InventorySlotButton {
+    owner: Entity,
+    index: usize,
+}
+
+/// A stack picked up from an inventory slot.
+#[derive(Debug, Clone, Copy)]
+struct DraggedStack {
+    owner: Entity,
+    index: usize,
+    quantity: u32,
+}
+
+/// The stack being dragged in the inventory grid, if any.
+#[derive(Resource, Debug, Default)]
+struct DragState {
+    dragging: Option<DraggedStack>,
+}
+
+/// Button that sorts and compacts an inventory.
+#[derive(Component, Debug)]
+struct SortInventoryButton(Entity);
+
+/// Icon that follows the cursor while dragging a stack.
+#[derive(Component)]
+struct DragIcon;
+
+/// Tooltip shown when hovering a slot.
+#[derive(Component)]
+struct SlotTooltip;
+
+/// Counts an item across a player's stockpile inventories
+pub fn stockpile_count(
+    stockpile: &Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>,
+    owner: Owner,
+    item: &ItemId,
+) -> u32 {
+    stockpile
+        .iter()
+        .filter(|(_, chest_owner)| chest_owner.copied().unwrap_or_default() == owner)
+        .map(|(inventory, _)| inventory.count_item(item))
+        .sum()
+}
+
+/// Removes an item from a player's stockpile inventories, returns the amount actually removed
+pub fn stockpile_take(
+    stockpile: &mut Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>,
+    owner: Owner,
+    item: &ItemId,
+    quantity: u32,
+) -> u32 {
+    let mut removed = 0;
+
+    for (mut inventory, chest_owner) in stockpile.iter_mut() {
+        if removed == quantity {
+            break;
+        }
+
+        if chest_owner.copied().unwrap_or_default() != owner {
+            continue;
+        }
+
+        removed += inventory.remove_item(item, quantity - removed);
+    }
+
+    removed
+}
+
+/// Adds an item to a player's stockpile inventories that accept it, returns the amount that didn't fit
+pub fn stockpile_add(
+    stockpile: &mut Query<
+        (&mut Inventory, Option<&StorageFilter>, Option<&Owner>),
+        With<Stockpile>,
+    >,
+    owner: Owner,
+    item: &ItemId,
+    quantity: u32,
+    items: &ItemRegistry,
+) -> u32 {
+    let mut remaining = quantity;
+
+    for (mut inventory, filter, chest_owner) in stockpile.iter_mut() {
+        if remaining == 0 {
+            break;
+        }
+
+        if chest_owner.copied().unwrap_or_default() != owner
+            || filter.is_some_and(|filter| !filter.allows(item))
+        {
+            continue;
+        }
+
+        remaining = inventory.add_item(item, remaining, items);
+    }
+
+    remaining
+}
+
+/// Spawns the drag icon and tooltip, which live outside the info panel so they survive its rebuild
+fn setup_inventory_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(ICON_SIZE),
+            height: Val::Px(ICON_SIZE),
+            display: Display::None,
+            ..default()
+        },
+        ImageNode::default(),
+        GlobalZIndex(10),
+        DragIcon,
+    ));
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+            font_size: 12.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.9)),
+        GlobalZIndex(11),
+        SlotTooltip,
+    ));
+}
+
+/// Orders the inventory whose sort button was clicked sorted
+fn handle_sort_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &SortInventoryButton)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            game_commands.send(GameCommand::SortInventory { owner: button.0 });
+        }
+    }
+}
+
+/// Picks up the stack under the cursor, or half of it with shift held
+fn start_slot_drag(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    slot_buttons: Query<(&Interaction, &InventorySlotButton)>,
+    inventories: Query<&Inventory>,
+    mut drag: ResMut<DragState>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Some((_, button)) = slot_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+    else {
+        return;
+    };
+
+    let Some(Some(stack)) = inventories
+        .get(button.owner)
+        .ok()
+        .and_then(|inventory| inventory.slots.get(button.index))
+    else {
+        return;
+    };
+
+    let quantity = if keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight)
+    {
+        (stack.quantity / 2).max(1)
+    } else {
+        stack.quantity
+    };
+
+    drag.dragging = Some(DraggedStack {
+        owner: button.owner,
+        index: button.index,
+        quantity,
+    });
+}
+
+/// Orders the dragged stack moved to the slot under the cursor
+fn drop_slot_drag(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    slot_buttons: Query<(&Interaction, &InventorySlotButton)>,
+    panel_interactions: PanelInteractions,
+    mut drag: ResMut<DragState>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_released(MouseButton::Left) {
+        return;
+    }
+
+    let Some(dragged) = drag.dragging.take() else {
+        return;
+    };
+
+    let Some((_, target)) = slot_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+    else {
+        // Dropping on the map leaves the stack on the ground, anywhere else in the panel puts it back
+        if !pointer_over_ui(&panel_interactions) {
+            game_commands.send(GameCommand::DropSlot {
+                owner: dragged.owner,
+                slot: dragged.index,
+                quantity: dragged.quantity,
+            });
+        }
+        return;
+    };
+
+    if target.owner == dragged.owner && target.index == dragged.index {
+        return;
+    }
+
+    game_commands.send(GameCommand::MoveSlot {
+        from: dragged.owner,
+        slot: dragged.index,
+        quantity: dragged.quantity,
+        to: target.owner,
+        to_slot: target.index,
+    });
+}
+
+/// Sorts, moves and drops stacks as ordered
+fn execute_inventory_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut inventories: Query<&mut Inventory>,
+    filters: Query<&StorageFilter>,
+    coords: Query<&GridCoords>,
+    mut drop_events: EventWriter<DropItems>,
+    items: Res<ItemRegistry>,
+) {
+    for command in game_commands.read() {
+        match *command {
+            GameCommand::SortInventory { owner } => {
+                if let Ok(mut inventory) = inventories.get_mut(owner) {
+                    inventory.sort(&items);
+                    info!(
+                        "<execute_inventory_commands> Sorted the inventory of {:?}",
+                        owner
+                    );
+                }
+            }
+            GameCommand::MoveSlot {
+                from,
+                slot,
+                quantity,
+                to,
+                to_slot,
+            } if from == to => {
+                let Ok(mut inventory) = inventories.get_mut(from) else {
+                    continue;
+                };
+                move_within_inventory(&mut inventory, slot, quantity, to_slot, &items);
+            }
+            GameCommand::MoveSlot {
+                from,
+                slot,
+                quantity,
+                to,
+                to_slot,
+            } => {
+                let Ok([mut from_inventory, mut to_inventory]) =
+                    inventories.get_many_mut([from, to])
+                else {
+                    continue;
+                };
+
+                // Storage only takes what its filter allows
+                if let (Ok(filter), Some(Some(stack))) =
+                    (filters.get(to), from_inventory.slots.get(slot))
+                {
+                    if !filter.allows(&stack.item) {
+                        info!(
+                            "<execute_inventory_commands> {:?} doesn't accept {}",
+                            to, stack.item
+                        );
+                        continue;
+                    }
+                }
+
+                move_between_inventories(
+                    &mut from_inventory,
+                    &mut to_inventory,
+                    slot,
+                    quantity,
+                    to_slot,
+                    &items,
+                );
+            }
+            GameCommand::DropSlot {
+                owner,
+                slot,
+                quantity,
+            } => {
+                drop_on_ground(
+                    owner,
+                    slot,
+                    quantity,
+                    &mut inventories,
+                    &coords,
+                    &mut drop_events,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Takes part of a stack out of its inventory and drops it where the owner stands
+fn drop_on_ground(
+    owner: Entity,
+    slot: usize,
+    quantity: u32,
+    inventories: &mut Query<&mut Inventory>,
+    coords: &Query<&GridCoords>,
+    drop_events: &mut EventWriter<DropItems>,
+) {
+    let Ok(owner_coords) = coords.get(owner) else {
+        return;
+    };
+
+    let Ok(mut inventory) = inventories.get_mut(owner) else {
+        return;
+    };
+
+    let Some(stack) = inventory.take_from_slot(slot, quantity) else {
+        return;
+    };
+
+    info!(
+        "<execute_inventory_commands> Dropped {} {} at {:?}",
+        stack.quantity, stack.item, owner_coords
+    );
+
+    drop_events.send(DropItems {
+        coords: *owner_coords,
+        item: stack.item,
+        quantity: stack.quantity,
+    });
+}
+
+/// Moves or merges part of a stack into another slot of the same inventory, swapping different items
+fn move_within_inventory(
+    inventory: &mut Inventory,
+    slot: usize,
+    quantity: u32,
+    target_index: usize,
+    items: &ItemRegistry,
+) {
+    let (Some(Some(source)), Some(target)) =
+        (inventory.slots.get(slot), inventory.slots.get(target_index))
+    else {
+        return;
+    };
+
+    if let Some(target) = target {
+        if target.item != source.item {
+            // Only whole stacks can swap places
+            if quantity == source.quantity {
+                inventory.slots.swap(slot, target_index);
+            }
+            return;
+        }
+    }
+
+    let Some(stack) = inventory.take_from_slot(slot, quantity) else {
+        return;
+    };
+    let item = stack.item.clone();
+
+    let leftover = inventory.put_in_slot(target_index, stack, items);
+    if leftover > 0 {
+        inventory.put_in_slot(
+            slot,
+            InventorySlot {
+                item,
+                quantity: leftover,
+            },
+            items,
+        );
+    }
+}
+
+/// Moves part of a stack into a slot of another inventory, keeping whatever doesn't fit
+fn move_between_inventories(
+    from: &mut Inventory,
+    to: &mut Inventory,
+    slot: usize,
+    quantity: u32,
+    target_index: usize,
+    items: &ItemRegistry,
+) {
+    let Some(stack) = from.take_from_slot(slot, quantity) else {
+        return;
+    };
+    let item = stack.item.clone();
+    let moved = stack.quantity;
+
+    let leftover = to.put_in_slot(target_index, stack, items);
+    if leftover > 0 {
+        from.put_in_slot(
+            slot,
+            InventorySlot {
+                item: item.clone(),
+                quantity: leftover,
+            },
+            items,
+        );
+    }
+
+    info!(
+        "<execute_inventory_commands> Moved {} {} between inventories",
+        moved - leftover,
+        item
+    );
+}
+
+/// Keeps the drag icon under the cursor while a stack is being dragged
+fn update_drag_icon(
+    drag: Res<DragState>,
+    windows: Query<&Window>,
+    inventories: Query<&Inventory>,
+    items: Res<ItemRegistry>,
     asset_server: Res<AssetServer>,
+    mut icons: Query<(&mut Node, &mut ImageNode), With<DragIcon>>,
+) {
+    let Ok((mut node, mut image)) = icons.get_single_mut() else {
+        return;
+    };
+
+    let cursor_position = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+
+    let dragged_item = drag.dragging.and_then(|dragged| {
+        inventories
+            .get(dragged.owner)
+            .ok()
+            .and_then(|inventory| inventory.slots.get(dragged.index).cloned().flatten())
+    });
+
+    let (Some(cursor_position), Some(stack)) = (cursor_position, dragged_item) else {
+        node.display = Display::None;
+        return;
+    };
+
+    if let Some(definition) = items.get(&stack.item) {
+        image.image = asset_server.load(definition.icon.clone());
+    }
+
+    node.display = Display::Flex;
+    node.left = Val::Px(cursor_position.x - ICON_SIZE / 2.0);
+    node.top = Val::Px(cursor_position.y - ICON_SIZE / 2.0);
+}
+
+/// Shows details of the item in the hovered slot
+fn update_slot_tooltip(
+    drag: Res<DragState>,
+    windows: Query<&Window>,
+    slot_buttons: Query<(&Interaction, &InventorySlotButton)>,
+    inventories: Query<&Inventory>,
+    items: Res<ItemRegistry>,
+    mut tooltips: Query<(&mut Node, &mut Text), With<SlotTooltip>>,
 ) {
-    if let Ok(panel_entity) = panel_query.get_single() {
-        // If there's a selected entity with inventory
-        if let Ok((entity, inventory)) = selected_entities.get_single() {
-            // Create a longer-lived value to avoid temporary value issue
-            let default_settings = InventorySettings::default();
-            let settings = inventory_settings.get(entity).unwrap_or(&default_settings);
-
-            // Create inventory UI within the panel
-            commands.entity(panel_entity).with_children(|parent| {
-                // Inventory title header
-                parent.spawn((
-                    Text::new("Inventory"),
-                    TextFont {
-                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                        font_size: 16.0,
+    let Ok((mut node, mut text)) = tooltips.get_single_mut() else {
+        return;
+    };
+
+    let cursor_position = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+
+    let hovered_stack = slot_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Hovered)
+        .and_then(|(_, button)| {
+            inventories
+                .get(button.owner)
+                .ok()
+                .and_then(|inventory| inventory.slots.get(button.index).cloned().flatten())
+        });
+
+    // No tooltips while dragging, they'd cover the drop target
+    let (Some(cursor_position), Some(stack), None) =
+        (cursor_position, hovered_stack, drag.dragging)
+    else {
+        node.display = Display::None;
+        return;
+    };
+
+    let mut tooltip = format!("{} x{}", items.name(&stack.item), stack.quantity);
+    if let Some(definition) = items.get(&stack.item) {
+        tooltip.push_str(&format!(
+            "\n{:?}, weight {:.1} each\nStacks up to {}",
+            definition.category, definition.weight, definition.max_stack
+        ));
+    }
+    tooltip.push_str("\nDrag to move, shift-drag to split, drag onto the map to drop");
+
+    *text = Text::new(tooltip);
+    node.display = Display::Flex;
+    node.left = Val::Px(cursor_position.x + 16.0);
+    node.top = Val::Px(cursor_position.y + 16.0);
+}
+
+/// Adds a titled slot grid for one inventory to the panel
+fn spawn_inventory_grid(
+    parent: &mut ChildBuilder,
+    title: &str,
+    owner: Entity,
+    inventory: &Inventory,
+    drag: &DragState,
+    items: &ItemRegistry,
+    asset_server: &AssetServer,
+) {
+    parent
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(8.0),
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|header| {
+            header.spawn((
+                Text::new(title),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+
+            header
+                .spawn((
+                    Button,
+                    // Let the panel see the click too, so it doesn't reach the map
+                    FocusPolicy::Pass,
+                    Node {
+                        padding: UiRect::all(Val::Px(4.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                    SortInventoryButton(owner),
+                ))
+                .with_children(|button| {
+                    button.spawn((
+                        Text::new("Sort"),
+                        TextFont {
+                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                            font_size: 12.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                });
+        });
+
+    if let Some(max_weight) = inventory.max_weight {
+        let load = inventory.load_ratio(items).unwrap_or_default();
+        let color = if load >= ENCUMBRANCE_THRESHOLD {
+            Color::srgb(1.0, 0.5, 0.0)
+        } else {
+            Color::WHITE
+        };
+
+        parent.spawn((
+            Text::new(format!(
+                "Load: {:.1}/{:.1}",
+                inventory.total_weight(items),
+                max_weight
+            )),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(color),
+        ));
+    }
+
+    parent
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            column_gap: Val::Px(4.0),
+            row_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|grid| {
+            for (index, slot) in inventory.slots.iter().enumerate() {
+                let dragged_from_here = drag
+                    .dragging
+                    .is_some_and(|dragged| dragged.owner == owner && dragged.index == index);
+
+                grid.spawn((
+                    Button,
+                    // Let the panel see the click too, so it doesn't reach the map
+                    FocusPolicy::Pass,
+                    Node {
+                        width: Val::Px(SLOT_SIZE),
+                        height: Val::Px(SLOT_SIZE),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
                         ..default()
                     },
-                    TextColor(Color::WHITE),
-                ));
-
-                // Inventory grid - list resources
-                for (i, slot) in inventory.slots.iter().enumerate() {
-                    if let Some(inv_slot) = slot {
-                        let resource_name = match inv_slot.resource_type {
-                            ResourceType::Gold => "Gold",
-                            ResourceType::Wood => "Wood",
-                            ResourceType::Stone => "Stone",
-                        };
-
-                        // Example of adding an icon to resource names
-                        let resource_icon = match inv_slot.resource_type {
-                            ResourceType::Gold => "🪙",
-                            ResourceType::Wood => "🪵",
-                            ResourceType::Stone => "🪨",
-                        };
-
-                        parent.spawn((
-                            Text::new(format!(
-                                "Slot {}: {} {} x{}/{}",
-                                i + 1,
-                                resource_icon,
-                                resource_name,
-                                inv_slot.quantity,
-                                settings.max_stack_size
-                            )),
-                            TextFont {
-                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                                font_size: 14.0,
-                                ..default()
-                            },
-                            TextColor(Color::WHITE),
-                        ));
-                    } else {
-                        parent.spawn((
-                            Text::new(format!("Slot {}: Empty", i + 1)),
-                            TextFont {
-                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                                font_size: 14.0,
-                                ..default()
-                            },
-                            TextColor(Color::srgba(0.7, 0.7, 0.7, 1.0)),
-                        ));
+                    ImageNode::new(asset_server.load("empty_slot.png")),
+                    InventorySlotButton { owner, index },
+                ))
+                .with_children(|slot_node| {
+                    let Some(stack) = slot else {
+                        return;
+                    };
+
+                    let mut icon = match items.get(&stack.item) {
+                        Some(definition) => {
+                            ImageNode::new(asset_server.load(definition.icon.clone()))
+                        }
+                        None => ImageNode::new(asset_server.load("unknown.png")),
+                    };
+                    if dragged_from_here {
+                        icon.color = Color::srgba(1.0, 1.0, 1.0, 0.4);
                     }
-                }
-            });
+
+                    slot_node.spawn((
+                        icon,
+                        Node {
+                            width: Val::Px(ICON_SIZE),
+                            height: Val::Px(ICON_SIZE),
+                            ..default()
+                        },
+                    ));
+
+                    slot_node.spawn((
+                        Text::new(stack.quantity.to_string()),
+                        TextFont {
+                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                            font_size: 12.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                        Node {
+                            position_type: PositionType::Absolute,
+                            right: Val::Px(2.0),
+                            bottom: Val::Px(0.0),
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+/// Shows the selected entity's inventory as a slot grid, plus any chest right next to it
+fn update_inventory_ui(
+    selected_entities: Query<(Entity, &Inventory, Option<&GridCoords>), With<Selected>>,
+    chests: Query<(Entity, &Inventory, &GridCoords), (With<Chest>, Without<Selected>)>,
+    mut commands: Commands,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    asset_server: Res<AssetServer>,
+    drag: Res<DragState>,
+    items: Res<ItemRegistry>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((entity, inventory, coords)) = selected_entities.get_single() else {
+        return;
+    };
+
+    let nearby_chest = coords.and_then(|coords| {
+        chests
+            .iter()
+            .find(|(_, _, chest_coords)| grid_distance(coords, chest_coords) <= 1)
+    });
+
+    commands.entity(panel_entity).with_children(|parent| {
+        spawn_inventory_grid(
+            parent,
+            "Inventory",
+            entity,
+            inventory,
+            &drag,
+            &items,
+            &asset_server,
+        );
+
+        if let Some((chest, chest_inventory, _)) = nearby_chest {
+            spawn_inventory_grid(
+                parent,
+                "Chest",
+                chest,
+                chest_inventory,
+                &drag,
+                &items,
+                &asset_server,
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// A chest each for two players, both holding 10 wood
+    fn two_chests() -> App {
+        let mut app = App::new();
+        app.init_resource::<ItemRegistry>();
+        let items = ItemRegistry::default();
+        for owner in [Owner(0), Owner(1)] {
+            let mut inventory = Inventory::new(4);
+            inventory.add_item(&ItemId::WOOD, 10, &items);
+            app.world_mut().spawn((inventory, Stockpile, owner));
         }
+        app
+    }
+
+    fn count(app: &mut App, owner: Owner) -> u32 {
+        app.world_mut()
+            .run_system_once(
+                move |stockpile: Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>| {
+                    stockpile_count(&stockpile, owner, &ItemId::WOOD)
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn players_only_use_their_own_chests() {
+        let mut app = two_chests();
+        assert_eq!(count(&mut app, Owner(0)), 10);
+
+        let taken = app
+            .world_mut()
+            .run_system_once(
+                |mut stockpile: Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>| {
+                    stockpile_take(&mut stockpile, Owner(1), &ItemId::WOOD, 15)
+                },
+            )
+            .unwrap();
+        assert_eq!(taken, 10);
+        assert_eq!(count(&mut app, Owner(0)), 10);
+        assert_eq!(count(&mut app, Owner(1)), 0);
+
+        let overflow = app
+            .world_mut()
+            .run_system_once(
+                |mut stockpile: Query<
+                    (&mut Inventory, Option<&StorageFilter>, Option<&Owner>),
+                    With<Stockpile>,
+                >,
+                 items: Res<ItemRegistry>| {
+                    stockpile_add(&mut stockpile, Owner(1), &ItemId::WOOD, 5, &items)
+                },
+            )
+            .unwrap();
+        assert_eq!(overflow, 0);
+        assert_eq!(count(&mut app, Owner(0)), 10);
+        assert_eq!(count(&mut app, Owner(1)), 5);
     }
 }
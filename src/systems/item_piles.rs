@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use crate::components::entities::Character;
+use crate::components::inventory::Inventory;
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::movement::{MoveTarget, Moving};
+use crate::components::unit::Selectable;
+use crate::systems::game_command::GameCommand;
+use crate::systems::movement::grid_to_translation;
+use crate::systems::scene::find_entity_layer;
+use crate::systems::simulation::SimulationSet;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for items lying on the ground.
+pub struct ItemPilesPlugin;
+
+impl Plugin for ItemPilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DropItems>()
+            .add_systems(
+                FixedUpdate,
+                execute_pickup_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                (pick_up_on_arrival, complete_pickup_orders).in_set(SimulationSet::Actions),
+            )
+            .add_systems(
+                FixedUpdate,
+                spawn_dropped_items.in_set(SimulationSet::Resolve),
+            )
+            .add_systems(Update, update_pile_sprites);
+    }
+}
+
+/// Size of a pile's sprite, smaller than a tile so the cell still reads as walkable.
+const PILE_SIZE: f32 = 32.0;
+
+/// Items lying on the ground, their contents are the entity's `Inventory`.
+#[derive(Component, Debug, Default)]
+pub struct ItemPile;
+
+/// Leaves items on the ground at a cell, merging them into any pile already there.
+#[derive(Event, Debug, Clone)]
+pub struct DropItems {
+    pub coords: GridCoords,
+    pub item: ItemId,
+    pub quantity: u32,
+}
+
+// Component for a unit walking to a pile to pick it up
+#[derive(Component, Debug)]
+pub struct PickupOrder {
+    pub pile: Entity,
+}
+
+/// Adds items to a pile, piles grow extra slots instead of overflowing
+fn add_to_pile(inventory: &mut Inventory, item: &ItemId, quantity: u32, items: &ItemRegistry) {
+    if items.max_stack(item) == 0 {
+        return;
+    }
+
+    let mut remaining = inventory.add_item(item, quantity, items);
+    while remaining > 0 {
+        inventory.slots.push(None);
+        inventory.max_slots += 1;
+        remaining = inventory.add_item(item, remaining, items);
+    }
+}
+
+/// Moves as much of a pile as fits into a unit's inventory, returns how many items moved
+fn pick_up_pile(unit: &mut Inventory, pile: &mut Inventory, items: &ItemRegistry) -> u32 {
+    let mut moved = 0;
+
+    for slot in &mut pile.slots {
+        let Some(stack) = slot else {
+            continue;
+        };
+
+        let leftover = unit.add_item(&stack.item, stack.quantity, items);
+        moved += stack.quantity - leftover;
+
+        if leftover == 0 {
+            *slot = None;
+        } else {
+            stack.quantity = leftover;
+        }
+    }
+
+    moved
+}
+
+/// Turns drop events into piles, one pile per cell
+fn spawn_dropped_items(
+    mut commands: Commands,
+    mut events: EventReader<DropItems>,
+    mut piles: Query<(&GridCoords, &mut Inventory), With<ItemPile>>,
+    layers: Query<(Entity, &LayerMetadata)>,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+) {
+    // Group drops by cell first, so several drops in one frame make a single pile
+    let mut drops: HashMap<GridCoords, Vec<DropItems>> = HashMap::new();
+    for event in events.read() {
+        if event.quantity > 0 {
+            drops.entry(event.coords).or_default().push(event.clone());
+        }
+    }
+
+    // Spawn piles in map order, so they're numbered the same in every game
+    let mut drops: Vec<_> = drops.into_iter().collect();
+    drops.sort_by_key(|(coords, _)| (coords.y, coords.x));
+
+    for (coords, cell_drops) in drops {
+        if let Some((_, mut inventory)) = piles
+            .iter_mut()
+            .find(|(pile_coords, _)| **pile_coords == coords)
+        {
+            for drop in &cell_drops {
+                add_to_pile(&mut inventory, &drop.item, drop.quantity, &items);
+            }
+            continue;
+        }
+
+        let Some(layer) = find_entity_layer(&layers) else {
+            info!(
+                "<spawn_dropped_items> No entity layer, items at {:?} are lost",
+                coords
+            );
+            continue;
+        };
+
+        let mut inventory = Inventory::new(1);
+        for drop in &cell_drops {
+            add_to_pile(&mut inventory, &drop.item, drop.quantity, &items);
+        }
+
+        spawn_pile(
+            &mut commands,
+            &asset_server,
+            layer,
+            coords,
+            inventory,
+            &items,
+        );
+
+        info!("<spawn_dropped_items> Dropped a pile at {:?}", coords);
+    }
+}
+
+/// Spawns a pile holding the given items in the entity layer
+pub fn spawn_pile(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    layer: Entity,
+    coords: GridCoords,
+    inventory: Inventory,
+    items: &ItemRegistry,
+) -> Entity {
+    let pile = commands
+        .spawn((
+            ItemPile,
+            Name::new("Item pile"),
+            Selectable,
+            Sprite {
+                image: asset_server.load(pile_icon(&inventory, items)),
+                custom_size: Some(Vec2::splat(PILE_SIZE)),
+                ..default()
+            },
+            // Just below units, like the selection ring
+            Transform::from_translation(grid_to_translation(coords, -0.1)),
+            coords,
+            inventory,
+        ))
+        .id();
+    commands.entity(layer).add_child(pile);
+    pile
+}
+
+/// Icon of the first item in a pile
+fn pile_icon(inventory: &Inventory, items: &ItemRegistry) -> String {
+    inventory
+        .slots
+        .iter()
+        .flatten()
+        .next()
+        .and_then(|stack| items.get(&stack.item))
+        .map(|definition| definition.icon.clone())
+        .unwrap_or_else(|| "unknown.png".to_string())
+}
+
+/// Sends units to the pile they were ordered to pick up, any other order calls it off
+fn execute_pickup_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut units: Query<&mut MoveTarget, (With<Character>, With<Inventory>)>,
+    picking_up: Query<(), With<PickupOrder>>,
+    piles: Query<&GridCoords, With<ItemPile>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::PickUp { unit, pile } = command else {
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| picking_up.contains(*unit))
+            {
+                commands.entity(unit).remove::<PickupOrder>();
+            }
+            continue;
+        };
+
+        let (Ok(mut move_target), Ok(pile_coords)) = (units.get_mut(*unit), piles.get(*pile))
+        else {
+            continue;
+        };
+
+        // Piles don't block movement, so walk right onto it
+        move_target.destination = Some(*pile_coords);
+        move_target.path.clear();
+
+        commands.entity(*unit).insert(PickupOrder { pile: *pile });
+        info!(
+            "<execute_pickup_commands> {:?} is going to pick up {:?}",
+            unit, pile
+        );
+    }
+}
+
+/// Units pick up piles they walk onto
+fn pick_up_on_arrival(
+    mut units: Query<
+        (Entity, &GridCoords, &mut Inventory),
+        (With<Character>, Changed<GridCoords>, Without<ItemPile>),
+    >,
+    mut piles: Query<(&GridCoords, &mut Inventory), With<ItemPile>>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, coords, mut inventory) in &mut units {
+        for (pile_coords, mut pile_inventory) in &mut piles {
+            if pile_coords != coords {
+                continue;
+            }
+
+            let moved = pick_up_pile(&mut inventory, &mut pile_inventory, &items);
+            if moved > 0 {
+                info!(
+                    "<pick_up_on_arrival> {:?} picked up {} items",
+                    entity, moved
+                );
+            }
+        }
+    }
+}
+
+/// Finishes pickup orders for units standing on their pile
+fn complete_pickup_orders(
+    mut commands: Commands,
+    mut units: Query<
+        (
+            Entity,
+            &PickupOrder,
+            &GridCoords,
+            &MoveTarget,
+            &mut Inventory,
+        ),
+        (Without<Moving>, Without<ItemPile>),
+    >,
+    mut piles: Query<(&GridCoords, &mut Inventory), With<ItemPile>>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, order, coords, move_target, mut inventory) in &mut units {
+        let Ok((pile_coords, mut pile_inventory)) = piles.get_mut(order.pile) else {
+            commands.entity(entity).remove::<PickupOrder>();
+            continue;
+        };
+
+        if pile_coords != coords {
+            // Still waiting on a path, or the walk ended somewhere else
+            if move_target.destination.is_none() && move_target.path.is_empty() {
+                info!(
+                    "<complete_pickup_orders> {:?} couldn't reach {:?}",
+                    entity, order.pile
+                );
+                commands.entity(entity).remove::<PickupOrder>();
+            }
+            continue;
+        }
+
+        commands.entity(entity).remove::<PickupOrder>();
+
+        let moved = pick_up_pile(&mut inventory, &mut pile_inventory, &items);
+        info!(
+            "<complete_pickup_orders> {:?} picked up {} items",
+            entity, moved
+        );
+    }
+}
+
+/// Keeps pile sprites matching their contents and clears away empty piles
+fn update_pile_sprites(
+    mut commands: Commands,
+    mut piles: Query<(Entity, &Inventory, &mut Sprite), (With<ItemPile>, Changed<Inventory>)>,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, inventory, mut sprite) in &mut piles {
+        if inventory.slots.iter().all(|slot| slot.is_none()) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        sprite.image = asset_server.load(pile_icon(inventory, &items));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::components::items::{ItemDefinition, ItemRegistry};
+use crate::systems::ron_asset::RonAssetLoader;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Plugin that loads item definitions into the `ItemRegistry`.
+pub struct ItemsPlugin;
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ItemDefinitions>()
+            .register_asset_loader(RonAssetLoader::<ItemDefinitions>::new(&["items.ron"]))
+            .init_resource::<ItemRegistry>()
+            .add_systems(Startup, load_item_definitions)
+            .add_systems(Update, update_item_registry);
+    }
+}
+
+/// Contents of an `.items.ron` file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct ItemDefinitions {
+    pub items: Vec<ItemDefinition>,
+}
+
+/// Handle keeping the item definitions loaded.
+#[derive(Resource)]
+struct ItemDefinitionsHandle(Handle<ItemDefinitions>);
+
+fn load_item_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ItemDefinitionsHandle(
+        asset_server.load("data/core.items.ron"),
+    ));
+}
+
+/// Rebuilds the registry whenever the definitions are loaded or edited
+fn update_item_registry(
+    mut events: EventReader<AssetEvent<ItemDefinitions>>,
+    definitions: Res<Assets<ItemDefinitions>>,
+    handle: Option<Res<ItemDefinitionsHandle>>,
+    mut registry: ResMut<ItemRegistry>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+
+        let Some(definitions) = definitions.get(&handle.0) else {
+            continue;
+        };
+
+        registry.load(&definitions.items);
+
+        info!(
+            "<update_item_registry> Loaded {} item definitions",
+            definitions.items.len()
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+
+use crate::components::entities::{Forest, Mine, Quarry, Worker};
+use crate::components::inventory::{Inventory, Stockpile};
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::movement::{Collider, MoveTarget};
+use crate::components::resources::ResourceNode;
+use crate::components::skills::Skills;
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::Selected;
+use crate::systems::construction::{construction_time, Constructing, ConstructionSite};
+use crate::systems::crafting::CraftingStation;
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::{Garrison, GarrisonIntent};
+use crate::systems::idle::{AutoBehaviour, IdleFilter};
+use crate::systems::item_piles::{ItemPile, PickupOrder};
+use crate::systems::movement::grid_distance;
+use crate::systems::resource_gathering::{find_adjacent_positions, resource_item, GatheringIntent};
+use crate::systems::simulation::{SimId, SimulationSet};
+use crate::systems::storage::DropOff;
+use crate::systems::ui::InfoPanelSet;
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for the job board that idle workers take work from.
+pub struct JobsPlugin;
+
+impl Plugin for JobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<JobBoard>()
+            .add_systems(Update, handle_priority_buttons.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                (execute_designation_commands, execute_priority_commands)
+                    .in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    assign_job_priorities,
+                    post_jobs,
+                    release_finished_jobs,
+                    claim_jobs,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Orders),
+            )
+            .add_systems(Update, draw_designations.in_set(ClientSet))
+            .add_systems(Update, update_jobs_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Lowest priority a job can have before it is switched off.
+const LOWEST_PRIORITY: u8 = 4;
+
+/// Kinds of work posted on the job board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JobKind {
+    Haul,
+    Gather,
+    Construct,
+    Craft,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 4] = [
+        JobKind::Haul,
+        JobKind::Gather,
+        JobKind::Construct,
+        JobKind::Craft,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::Haul => "Haul",
+            JobKind::Gather => "Gather",
+            JobKind::Construct => "Construct",
+            JobKind::Craft => "Craft",
+        }
+    }
+
+    /// Skill that makes a worker good at this job, hauling needs none
+    fn skill(&self, skills: &Skills) -> Option<f32> {
+        match self {
+            JobKind::Haul => None,
+            JobKind::Gather => Some(skills.woodcutting.max(skills.mining).max(skills.harvesting)),
+            JobKind::Construct => Some(skills.construction),
+            JobKind::Craft => Some(skills.crafting),
+        }
+    }
+}
+
+/// A piece of work waiting for a worker.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub kind: JobKind,
+    pub target: Entity,
+    pub coords: GridCoords,
+    /// Item to gather, for gather jobs
+    pub item: Option<ItemId>,
+}
+
+/// Every open job, and which worker has claimed each target.
+#[derive(Resource, Debug, Default)]
+pub struct JobBoard {
+    pub jobs: Vec<Job>,
+    pub reservations: HashMap<Entity, Entity>,
+}
+
+/// How keen a worker is on each kind of job, 1 is most keen and 0 means never.
+#[derive(Component, Debug, Clone)]
+pub struct JobPriorities {
+    priorities: HashMap<JobKind, u8>,
+}
+
+impl JobPriorities {
+    /// Workers start out keenest on what they are best at
+    pub fn from_skills(skills: &Skills) -> Self {
+        let priorities = JobKind::ALL
+            .iter()
+            .map(|kind| {
+                let priority = match kind.skill(skills) {
+                    None => 3,
+                    Some(skill) if skill >= 2.0 => 1,
+                    Some(skill) if skill >= 1.0 => 2,
+                    Some(skill) if skill >= 0.5 => 3,
+                    Some(_) => LOWEST_PRIORITY,
+                };
+                (*kind, priority)
+            })
+            .collect();
+
+        Self { priorities }
+    }
+
+    pub fn get(&self, kind: JobKind) -> u8 {
+        self.priorities.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// Steps to the next lower priority, wrapping from off back to the top
+    pub fn cycle(&mut self, kind: JobKind) {
+        let next = match self.get(kind) {
+            0 => 1,
+            priority if priority >= LOWEST_PRIORITY => 0,
+            priority => priority + 1,
+        };
+        self.priorities.insert(kind, next);
+    }
+}
+
+// Component for a worker busy with a job from the board
+#[derive(Component, Debug)]
+pub struct AssignedJob {
+    pub job: Job,
+    // Haul jobs carry the pile to storage once it is picked up
+    hauled: bool,
+}
+
+// Marks a resource node the player wants gathered
+#[derive(Component, Debug)]
+pub struct GatherDesignation;
+
+/// Button in the info panel that cycles the priority of a job kind.
+#[derive(Component, Debug)]
+struct JobPriorityButton(JobKind);
+
+/// Marks or unmarks resources for gathering as ordered
+fn execute_designation_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    resources: Query<Has<GatherDesignation>, With<ResourceNode>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::ToggleGatherDesignation { resource } = command else {
+            continue;
+        };
+
+        let Ok(designated) = resources.get(*resource) else {
+            continue;
+        };
+
+        if designated {
+            commands.entity(*resource).remove::<GatherDesignation>();
+            info!(
+                "<execute_designation_commands> {:?} no longer marked",
+                resource
+            );
+        } else {
+            commands.entity(*resource).insert(GatherDesignation);
+            info!(
+                "<execute_designation_commands> {:?} marked for gathering",
+                resource
+            );
+        }
+    }
+}
+
+/// Gives new workers priorities based on their skills
+fn assign_job_priorities(
+    mut commands: Commands,
+    workers: Query<(Entity, &Skills), (With<Worker>, Without<JobPriorities>)>,
+) {
+    for (entity, skills) in &workers {
+        commands
+            .entity(entity)
+            .insert(JobPriorities::from_skills(skills));
+    }
+}
+
+/// Rebuilds the list of open jobs from designations, building sites, workshops and piles
+fn post_jobs(
+    mut board: ResMut<JobBoard>,
+    assigned: Query<(), With<AssignedJob>>,
+    designations: Query<
+        (Entity, &GridCoords, Has<Forest>, Has<Mine>, Has<Quarry>),
+        With<GatherDesignation>,
+    >,
+    sites: Query<(Entity, &GridCoords), With<ConstructionSite>>,
+    builders: Query<&Constructing>,
+    stations: Query<(Entity, &GridCoords, &CraftingStation, &Garrison)>,
+    workers: Query<(), With<Worker>>,
+    piles: Query<(Entity, &GridCoords), With<ItemPile>>,
+    stockpile: Query<(), With<Stockpile>>,
+) {
+    // Forget claims from workers that finished or are gone
+    board
+        .reservations
+        .retain(|_, worker| assigned.contains(*worker));
+
+    let mut jobs = Vec::new();
+
+    for (resource, coords, is_tree, is_mine, is_quarry) in &designations {
+        if let Some(item) = resource_item(is_tree, is_mine, is_quarry) {
+            jobs.push(Job {
+                kind: JobKind::Gather,
+                target: resource,
+                coords: *coords,
+                item: Some(item),
+            });
+        }
+    }
+
+    for (site, coords) in &sites {
+        // Sites someone is already building don't need a job
+        if builders
+            .iter()
+            .any(|constructing| constructing.site == site)
+        {
+            continue;
+        }
+
+        jobs.push(Job {
+            kind: JobKind::Construct,
+            target: site,
+            coords: *coords,
+            item: None,
+        });
+    }
+
+    for (station, coords, crafting, garrison) in &stations {
+        let has_crafter = garrison
+            .occupants
+            .iter()
+            .any(|occupant| workers.contains(*occupant));
+
+        if crafting.queue.is_empty() || has_crafter || garrison.occupants.len() >= garrison.capacity
+        {
+            continue;
+        }
+
+        jobs.push(Job {
+            kind: JobKind::Craft,
+            target: station,
+            coords: *coords,
+            item: None,
+        });
+    }
+
+    // Hauling only makes sense with somewhere to haul to
+    if !stockpile.is_empty() {
+        for (pile, coords) in &piles {
+            jobs.push(Job {
+                kind: JobKind::Haul,
+                target: pile,
+                coords: *coords,
+                item: None,
+            });
+        }
+    }
+
+    // Query order isn't the same in every game, map order is, and ties go to the first job
+    jobs.sort_by_key(|job| (job.coords.y, job.coords.x));
+    board.jobs = jobs;
+}
+
+/// Frees workers whose job is done, a haul goes on to storage once the pile is picked up
+fn release_finished_jobs(
+    mut commands: Commands,
+    mut workers: Query<(Entity, &mut AssignedJob, &Inventory), (With<Worker>, IdleFilter)>,
+    mut board: ResMut<JobBoard>,
+) {
+    for (entity, mut assigned, inventory) in &mut workers {
+        let carrying = inventory.slots.iter().any(|slot| slot.is_some());
+
+        if assigned.job.kind == JobKind::Haul && !assigned.hauled && carrying {
+            assigned.hauled = true;
+            commands.entity(entity).insert(DropOff::haul());
+            continue;
+        }
+
+        board.reservations.remove(&assigned.job.target);
+        commands.entity(entity).remove::<AssignedJob>();
+        info!(
+            "<release_finished_jobs> {:?} finished a {} job",
+            entity,
+            assigned.job.kind.name()
+        );
+    }
+}
+
+/// Whether a worker can carry what a job produces, so full workers don't take jobs they can't finish
+fn has_room_for(job: &Job, inventory: &Inventory, items: &ItemRegistry) -> bool {
+    match (job.kind, &job.item) {
+        (JobKind::Haul, _) => inventory.slots.iter().any(|slot| slot.is_none()),
+        (JobKind::Gather, Some(item)) => inventory.room_for(item, items) > 0,
+        _ => true,
+    }
+}
+
+/// Idle workers take the open job they are keenest on, nearest first
+#[allow(clippy::type_complexity)]
+fn claim_jobs(
+    mut commands: Commands,
+    mut workers: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Skills,
+            &JobPriorities,
+            &AutoBehaviour,
+            &Inventory,
+            &mut MoveTarget,
+            Option<&SimId>,
+        ),
+        (With<Worker>, IdleFilter, Without<AssignedJob>),
+    >,
+    sites: Query<&ConstructionSite>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    mut board: ResMut<JobBoard>,
+    items: Res<ItemRegistry>,
+) {
+    // Jobs go first come first served, so everyone has to queue up in the same order
+    let mut workers: Vec<_> = workers.iter_mut().collect();
+    workers.sort_by_key(|(.., sim_id)| sim_id.copied());
+
+    for (entity, coords, skills, priorities, behaviour, inventory, mut move_target, _) in workers {
+        // Workers told to walk somewhere finish the walk first
+        if *behaviour != AutoBehaviour::TakeJobs || move_target.destination.is_some() {
+            continue;
+        }
+
+        let Some(job) = board
+            .jobs
+            .iter()
+            .filter(|job| priorities.get(job.kind) > 0)
+            .filter(|job| !board.reservations.contains_key(&job.target))
+            .filter(|job| has_room_for(job, inventory, &items))
+            .min_by_key(|job| (priorities.get(job.kind), grid_distance(coords, &job.coords)))
+            .cloned()
+        else {
+            continue;
+        };
+
+        // Piles are walked onto, everything else is worked from a neighbouring cell
+        let destination = if job.kind == JobKind::Haul {
+            Some(job.coords)
+        } else if grid_distance(coords, &job.coords) <= 1 {
+            None
+        } else {
+            let mut approach_positions = find_adjacent_positions(job.coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - coords.x).pow(2) + (pos.y - coords.y).pow(2));
+
+            let Some(dest) = approach_positions.first() else {
+                continue;
+            };
+            Some(*dest)
+        };
+
+        if let Some(destination) = destination {
+            move_target.destination = Some(destination);
+            move_target.path.clear();
+        }
+
+        let mut worker = commands.entity(entity);
+        match job.kind {
+            JobKind::Haul => {
+                worker.insert(PickupOrder { pile: job.target });
+            }
+            JobKind::Gather => {
+                let Some(item) = job.item.clone() else {
+                    continue;
+                };
+                worker.insert(GatheringIntent {
+                    target: job.target,
+                    item,
+                });
+            }
+            JobKind::Construct => {
+                let Ok(site) = sites.get(job.target) else {
+                    continue;
+                };
+                worker.insert(Constructing {
+                    building_type: site.building_type,
+                    progress: 0.0,
+                    required_time: construction_time(skills.construction),
+                    site: job.target,
+                });
+            }
+            JobKind::Craft => {
+                worker.insert(GarrisonIntent {
+                    building: job.target,
+                });
+            }
+        }
+
+        worker.insert(AssignedJob {
+            job: job.clone(),
+            hauled: false,
+        });
+        board.reservations.insert(job.target, entity);
+
+        info!(
+            "<claim_jobs> {:?} took a {} job at {:?}",
+            entity,
+            job.kind.name(),
+            job.coords
+        );
+    }
+}
+
+/// Outlines resources marked for gathering
+fn draw_designations(
+    mut gizmos: Gizmos,
+    designations: Query<&GlobalTransform, With<GatherDesignation>>,
+) {
+    for transform in &designations {
+        gizmos.rect(
+            transform.translation(),
+            Vec2::new(56.0, 56.0),
+            Color::srgb(1.0, 0.8, 0.0),
+        );
+    }
+}
+
+/// Orders a job priority cycled for the selected worker
+fn handle_priority_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &JobPriorityButton)>,
+    selected: Query<Entity, (With<JobPriorities>, With<Selected>)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(unit) = selected.get_single() else {
+        return;
+    };
+
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            game_commands.send(GameCommand::CyclePriority {
+                unit,
+                kind: button.0,
+            });
+        }
+    }
+}
+
+/// Cycles job priorities as ordered
+fn execute_priority_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut workers: Query<&mut JobPriorities>,
+) {
+    for command in game_commands.read() {
+        if let GameCommand::CyclePriority { unit, kind } = command {
+            if let Ok(mut priorities) = workers.get_mut(*unit) {
+                priorities.cycle(*kind);
+            }
+        }
+    }
+}
+
+/// Shows the selected worker's current job and job priorities
+fn update_jobs_ui(
+    selected: Query<(&JobPriorities, Option<&AssignedJob>), With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((priorities, assigned)) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        let current = assigned
+            .map(|assigned| assigned.job.kind.name())
+            .unwrap_or("None");
+
+        parent.spawn((
+            Text::new(format!("Job: {}", current)),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                flex_wrap: FlexWrap::Wrap,
+                column_gap: Val::Px(4.0),
+                row_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for kind in JobKind::ALL {
+                    let label = match priorities.get(kind) {
+                        0 => format!("{}: off", kind.name()),
+                        priority => format!("{}: {}", kind.name(), priority),
+                    };
+
+                    row.spawn((
+                        Button,
+                        // Let the panel see the click too, so it doesn't reach the map
+                        FocusPolicy::Pass,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                        JobPriorityButton(kind),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(label),
+                            TextFont {
+                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                font_size: 12.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                }
+            });
+    });
+}
@@ -1,10 +1,33 @@
+pub mod ai;
 pub mod audio;
 pub mod camera;
+pub mod combat;
 pub mod construction;
+pub mod crafting;
+pub mod game_command;
+pub mod garrison;
+pub mod housing;
+pub mod idle;
+pub mod input;
 pub mod inventory;
+pub mod item_piles;
+pub mod items;
+pub mod jobs;
 pub mod movement;
+pub mod multiplayer;
+pub mod production;
+pub mod replay;
 pub mod resource_gathering;
+pub mod ron_asset;
+pub mod save;
+pub mod save_menu;
 pub mod scene;
 pub mod selection;
 pub mod setup_window;
+pub mod simulation;
+pub mod skills;
+pub mod storage;
+pub mod transfer;
 pub mod ui;
+pub mod victory;
+pub mod wildlife;
@@ -1,4 +1,9 @@
+use crate::components::inventory::Inventory;
+use crate::components::items::ItemRegistry;
 use crate::components::movement::{Movable, MoveTarget, Moving};
+use crate::systems::game_command::GameCommand;
+use crate::systems::simulation::SimulationSet;
+use crate::ClientSet;
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use pathfinding::prelude::astar;
@@ -8,41 +13,48 @@ pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_movement_input)
-            .add_systems(Update, update_movement.after(handle_movement_input))
-            .add_systems(Update, calculate_path.after(handle_movement_input))
-            .add_systems(Update, move_along_path.after(calculate_path));
+        app.add_systems(
+            FixedUpdate,
+            execute_move_commands.in_set(SimulationSet::Commands),
+        )
+        .add_systems(
+            FixedUpdate,
+            (update_movement, calculate_path, move_along_path)
+                .chain()
+                .in_set(SimulationSet::Movement),
+        )
+        .add_systems(Update, interpolate_movement.in_set(ClientSet));
     }
 }
 
-/// Calculates a grid position from cursor position and checks if it's valid
-pub fn calculate_cursor_grid_position(
-    cursor_position: Vec2,
-    camera_q: &Query<(&Camera, &GlobalTransform)>,
-    ldtk_worlds: &Query<&GlobalTransform, With<LdtkProjectHandle>>,
-) -> Option<GridCoords> {
-    // Process the cursor ray and get world position
-    let (camera, camera_transform) = camera_q.single();
-    let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return None;
-    };
-
-    // Get the world position of the cursor
-    let cursor_world_pos = cursor_ray.origin.truncate();
-
-    // Get the LDTK world transform
-    let ldtk_world_transform = ldtk_worlds.single();
-
-    // To get a position relative to LDTK world, we need to account for the world transform
-    let ldtk_relative_pos = cursor_world_pos - ldtk_world_transform.translation().truncate();
-
-    // Convert to grid coordinates with fixed grid offset as specified
-    let target_grid = GridCoords {
-        x: ((ldtk_relative_pos.x) / 64.0).floor() as i32,
-        y: ((ldtk_relative_pos.y) / 64.0).floor() as i32,
-    };
-
-    Some(target_grid)
+/// Load ratio above which carrying weight starts to slow a unit down.
+pub const ENCUMBRANCE_THRESHOLD: f32 = 0.75;
+
+/// Speed multiplier for a unit carrying its full weight limit.
+const FULLY_LOADED_SPEED: f32 = 0.5;
+
+/// Speed multiplier for a unit whose inventory is at the given load ratio
+pub fn encumbrance_speed(load_ratio: f32) -> f32 {
+    if load_ratio <= ENCUMBRANCE_THRESHOLD {
+        return 1.0;
+    }
+
+    let overload = (load_ratio - ENCUMBRANCE_THRESHOLD) / (1.0 - ENCUMBRANCE_THRESHOLD);
+    1.0 - overload.min(1.0) * (1.0 - FULLY_LOADED_SPEED)
+}
+
+/// Converts grid coordinates to the world position at the centre of that tile
+pub fn grid_to_translation(coords: GridCoords, z: f32) -> Vec3 {
+    Vec3::new(
+        coords.x as f32 * 64.0 + 32.0,
+        coords.y as f32 * 64.0 + 32.0,
+        z,
+    )
+}
+
+/// Chebyshev distance between two cells, so diagonal neighbours count as adjacent
+pub fn grid_distance(a: &GridCoords, b: &GridCoords) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
 }
 
 /// Sets a movement target for an entity if the target position is valid
@@ -61,7 +73,7 @@ pub fn set_movement_target(
     // If the distance is too large, exit early
     if distance > 30.0 {
         info!(
-            "Movement distance too large ({:.1}), ignoring click",
+            "Movement distance too large ({:.1}), ignoring the order",
             distance
         );
         return false;
@@ -96,61 +108,36 @@ pub fn set_movement_target(
     false
 }
 
-/// Handles movement input from the user
-#[allow(clippy::too_many_arguments)]
-fn handle_movement_input(
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
-    selected_units: Query<(Entity, &GridCoords), With<crate::components::unit::Selected>>,
+/// Walks units to the cell they were ordered to, and halts stopped units
+fn execute_move_commands(
+    mut game_commands: EventReader<GameCommand>,
+    units: Query<&GridCoords>,
     mut move_targets: Query<&mut MoveTarget>,
     ldtk_tile_query: Query<&GridCoords, With<crate::components::movement::Collider>>,
-    gatherers: Query<Entity, With<crate::systems::resource_gathering::Gathering>>,
-    ldtk_worlds: Query<&GlobalTransform, With<LdtkProjectHandle>>,
 ) {
-    // Only process right-click inputs
-    if !mouse_button.just_pressed(MouseButton::Right) {
-        return;
-    }
-
-    let window = windows.single();
-    let Some(cursor_position) = window.cursor_position() else {
-        return;
-    };
-
-    // Get the first selected unit
-    let Some((entity, current_pos)) = selected_units.iter().next() else {
-        return;
-    };
-
-    // Skip if this entity is gathering (movement interruption is handled in resource_gathering.rs)
-    if gatherers.contains(entity) {
-        info!("Entity is currently gathering, ignoring movement command");
-        return;
+    for command in game_commands.read() {
+        match command {
+            GameCommand::Move { unit, destination } => {
+                let Ok(current_pos) = units.get(*unit) else {
+                    continue;
+                };
+                set_movement_target(
+                    *unit,
+                    *destination,
+                    current_pos,
+                    &ldtk_tile_query,
+                    &mut move_targets,
+                );
+            }
+            GameCommand::Stop { unit } => {
+                if let Ok(mut move_target) = move_targets.get_mut(*unit) {
+                    move_target.destination = None;
+                    move_target.path.clear();
+                }
+            }
+            _ => {}
+        }
     }
-
-    // Calculate grid position from cursor
-    let Some(target_grid) =
-        calculate_cursor_grid_position(cursor_position, &camera_q, &ldtk_worlds)
-    else {
-        return;
-    };
-
-    info!("Raw cursor world position: {:?}", cursor_position);
-    info!("Target grid coordinates: {:?}", target_grid);
-    info!(
-        "Current position: {:?}, Target: {:?}",
-        current_pos, target_grid
-    );
-
-    // Try to set movement target
-    set_movement_target(
-        entity,
-        target_grid,
-        current_pos,
-        &ldtk_tile_query,
-        &mut move_targets,
-    );
 }
 
 /// System to calculate a path when a destination is set
@@ -304,6 +291,7 @@ fn move_along_path(
                 from: current_world_pos,
                 to: next_world_pos,
                 progress: 0.0,
+                previous_progress: 0.0,
             });
 
             // Remove the position we're moving to from the path
@@ -318,13 +306,26 @@ fn move_along_path(
 /// System to update entity position while moving
 fn update_movement(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &mut Moving, &Movable)>,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Moving,
+        &Movable,
+        Option<&Inventory>,
+    )>,
     mut grid_coords: Query<&mut GridCoords>,
     time: Res<Time>,
+    items: Res<ItemRegistry>,
 ) {
-    for (entity, mut transform, mut moving, movable) in &mut query {
+    for (entity, mut transform, mut moving, movable, inventory) in &mut query {
+        // Heavy loads slow units down
+        let encumbrance = inventory
+            .and_then(|inventory| inventory.load_ratio(&items))
+            .map_or(1.0, encumbrance_speed);
+
         // Update progress
-        moving.progress += time.delta_secs() * movable.speed;
+        moving.previous_progress = moving.progress;
+        moving.progress += time.delta_secs() * movable.speed * encumbrance;
 
         if moving.progress >= 1.0 {
             // Movement complete
@@ -349,3 +350,14 @@ fn update_movement(
         }
     }
 }
+
+/// Draws moving units between their last two tick positions, so they glide
+/// smoothly however far apart ticks and frames fall
+fn interpolate_movement(mut query: Query<(&mut Transform, &Moving)>, fixed_time: Res<Time<Fixed>>) {
+    let overstep = fixed_time.overstep_fraction();
+    for (mut transform, moving) in &mut query {
+        let progress =
+            moving.previous_progress + (moving.progress - moving.previous_progress) * overstep;
+        transform.translation = moving.from.lerp(moving.to, progress.min(1.0));
+    }
+}
@@ -0,0 +1,855 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use crate::components::ui::HudPanel;
+use crate::components::unit::Owner;
+use crate::systems::game_command::GameCommand;
+use crate::systems::replay::ReplayCommand;
+use crate::systems::save::map_name;
+use crate::systems::simulation::{
+    state_hash, SimId, SimIds, SimState, SimTick, SimulationSet, TickGate,
+};
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for lockstep multiplayer between a host and one other player.
+///
+/// Both games run the same simulation and only trade commands. Every tick
+/// waits until it has both players' commands for it, and each side sends a
+/// hash of its state so the two games drifting apart is caught straight away.
+pub struct MultiplayerPlugin;
+
+impl Plugin for MultiplayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LocalPlayer>()
+            .add_systems(
+                PreUpdate,
+                receive_messages.run_if(resource_exists::<Lockstep>),
+            )
+            .add_systems(
+                FixedUpdate,
+                (take_local_commands, hold_for_turn)
+                    .chain()
+                    .run_if(resource_exists::<Lockstep>)
+                    .in_set(SimulationSet::Gate),
+            )
+            .add_systems(
+                FixedUpdate,
+                exchange_turns
+                    .run_if(resource_exists::<Lockstep>)
+                    .in_set(SimulationSet::Input),
+            )
+            .add_systems(
+                Update,
+                (check_desync, catch_up).run_if(resource_exists::<Lockstep>),
+            )
+            .add_systems(
+                PostUpdate,
+                (queue_local_turns, send_messages)
+                    .chain()
+                    .run_if(resource_exists::<Lockstep>),
+            )
+            .add_systems(
+                PostStartup,
+                setup_network_text
+                    .run_if(resource_exists::<Lockstep>)
+                    .in_set(ClientSet),
+            )
+            .add_systems(
+                Update,
+                update_network_text
+                    .run_if(resource_exists::<Lockstep>)
+                    .in_set(ClientSet),
+            );
+    }
+}
+
+/// Bumped whenever games from different versions can't play together.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Port the host listens on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// Players in a game, the host is player 0.
+const PLAYERS: usize = 2;
+
+/// Ticks between a command being given and carried out, so it has time to
+/// reach the other player.
+const INPUT_DELAY: u64 = 3;
+
+/// Most turns sent in one message, so catching up a rejoining player doesn't
+/// need one huge datagram.
+const MAX_TURNS_PER_MESSAGE: usize = 32;
+
+/// State hashes sent with every message.
+const HASHES_PER_MESSAGE: usize = 8;
+
+/// How long the other player can go quiet before they count as disconnected.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a joining game asks the host for a seat.
+const HELLO_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Ticks behind the other player before running faster to catch up.
+const CATCH_UP_THRESHOLD: u64 = 10;
+
+/// Game speed while catching up.
+const CATCH_UP_SPEED: f32 = 8.0;
+
+/// The player sitting at this computer.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct LocalPlayer(pub Owner);
+
+/// One player's commands for one tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Turn {
+    tick: u64,
+    player: u8,
+    commands: Vec<ReplayCommand>,
+}
+
+/// What the two games send each other.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Message {
+    /// A joining game asking for a seat
+    Hello { version: u32, map: String },
+    /// The host giving the joining game its player number
+    Welcome { player: u8 },
+    /// The host turning a joining game away
+    Refused { reason: String },
+    /// Sent every frame once connected, doubling as a keepalive
+    Turns {
+        // Every tick up to this one has all its turns on the sender's side
+        complete: u64,
+        // The sender's current tick
+        tick: u64,
+        turns: Vec<Turn>,
+        hashes: Vec<(u64, u64)>,
+    },
+}
+
+#[derive(Debug)]
+enum Role {
+    Host,
+    Join { host: SocketAddr },
+}
+
+/// The connection to the other player and every turn of the game so far.
+///
+/// Turns are kept for the whole game, so a player who drops out and starts
+/// their game again can be sent everything and replay their way back in.
+#[derive(Resource, Debug)]
+pub struct Lockstep {
+    socket: UdpSocket,
+    role: Role,
+    peer: Option<SocketAddr>,
+    local: u8,
+    welcomed: bool,
+    refused: Option<String>,
+    last_heard: Option<Instant>,
+    last_hello: Option<Instant>,
+    // Whether the other player's turns have started arriving
+    synced: bool,
+    turns: BTreeMap<u64, [Option<Vec<ReplayCommand>>; PLAYERS]>,
+    // Every tick up to this one has all its turns
+    complete: u64,
+    // Next tick this player has no turn for yet
+    next_local_tick: u64,
+    // Commands given too late for the turn they were meant for
+    pending: Vec<ReplayCommand>,
+    peer_complete: u64,
+    peer_tick: u64,
+    hashes: BTreeMap<u64, u64>,
+    peer_hashes: BTreeMap<u64, u64>,
+    desync: Option<u64>,
+}
+
+impl Lockstep {
+    /// Hosts a game on the given port
+    pub fn host(port: u16) -> io::Result<Self> {
+        Self::new(SocketAddr::from(([0, 0, 0, 0], port)), Role::Host)
+    }
+
+    /// Joins the game hosted at `host`
+    pub fn join(host: SocketAddr) -> io::Result<Self> {
+        Self::new(SocketAddr::from(([0, 0, 0, 0], 0)), Role::Join { host })
+    }
+
+    fn new(bind: SocketAddr, role: Role) -> io::Result<Self> {
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+
+        // Nobody can give orders in the first few ticks, so those turns are empty
+        let turns = (1..=INPUT_DELAY)
+            .map(|tick| (tick, std::array::from_fn(|_| Some(Vec::new()))))
+            .collect();
+
+        Ok(Self {
+            socket,
+            peer: match role {
+                Role::Host => None,
+                Role::Join { host } => Some(host),
+            },
+            role,
+            local: 0,
+            welcomed: false,
+            refused: None,
+            last_heard: None,
+            last_hello: None,
+            synced: false,
+            turns,
+            complete: INPUT_DELAY,
+            next_local_tick: INPUT_DELAY + 1,
+            pending: Vec::new(),
+            peer_complete: 0,
+            peer_tick: 0,
+            hashes: BTreeMap::new(),
+            peer_hashes: BTreeMap::new(),
+            desync: None,
+        })
+    }
+
+    /// Whether the other player is there and has been heard from lately
+    fn connected(&self) -> bool {
+        self.welcomed
+            && self
+                .last_heard
+                .is_some_and(|heard| heard.elapsed() < TIMEOUT)
+    }
+
+    /// Stores a turn unless it's already known, the first copy to arrive wins
+    fn add_turn(&mut self, turn: Turn) {
+        let player = turn.player as usize;
+        if player >= PLAYERS {
+            return;
+        }
+
+        let slot = &mut self.turns.entry(turn.tick).or_default()[player];
+        if slot.is_some() {
+            return;
+        }
+        *slot = Some(turn.commands);
+
+        while self
+            .turns
+            .get(&(self.complete + 1))
+            .is_some_and(|players| players.iter().all(Option::is_some))
+        {
+            self.complete += 1;
+        }
+    }
+
+    fn send(&self, message: &Message, to: SocketAddr) {
+        let Ok(contents) = ron::to_string(message) else {
+            return;
+        };
+        if let Err(error) = self.socket.send_to(contents.as_bytes(), to) {
+            if error.kind() != ErrorKind::WouldBlock {
+                warn!("<send> Could not send to {}: {}", to, error);
+            }
+        }
+    }
+
+    /// Turns the other player hasn't confirmed, oldest first
+    fn unconfirmed_turns(&self) -> Vec<Turn> {
+        self.turns
+            .range(self.peer_complete + 1..)
+            .flat_map(|(tick, players)| {
+                players
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(player, commands)| {
+                        Some(Turn {
+                            tick: *tick,
+                            player: player as u8,
+                            commands: commands.clone()?,
+                        })
+                    })
+            })
+            .take(MAX_TURNS_PER_MESSAGE)
+            .collect()
+    }
+}
+
+/// Reads everything the other game sent
+fn receive_messages(mut lockstep: ResMut<Lockstep>, mut local_player: ResMut<LocalPlayer>) {
+    let mut buffer = [0u8; 65536];
+    loop {
+        let (length, from) = match lockstep.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            // A closed port on the other end shows up as a receive error on some systems
+            Err(_) => continue,
+        };
+
+        let Some(message) = std::str::from_utf8(&buffer[..length])
+            .ok()
+            .and_then(|contents| ron::from_str::<Message>(contents).ok())
+        else {
+            warn!("<receive_messages> Unreadable message from {}", from);
+            continue;
+        };
+
+        match message {
+            Message::Hello { version, map } => {
+                if !matches!(lockstep.role, Role::Host) {
+                    continue;
+                }
+
+                let reason = if version != PROTOCOL_VERSION {
+                    Some(format!(
+                        "the host runs version {}, you run {}",
+                        PROTOCOL_VERSION, version
+                    ))
+                } else if map != map_name() {
+                    Some(format!("the host is playing {}", map_name()))
+                } else if lockstep.connected() && lockstep.peer != Some(from) {
+                    Some("the game is full".to_string())
+                } else {
+                    None
+                };
+
+                if let Some(reason) = reason {
+                    info!("<receive_messages> Turned {} away: {}", from, reason);
+                    lockstep.send(&Message::Refused { reason }, from);
+                    continue;
+                }
+
+                if lockstep.peer != Some(from) {
+                    info!("<receive_messages> Player 2 joined from {}", from);
+                }
+                // A rejoining game starts over, so it needs every turn again. Its
+                // turns past what both sides finished were never played, it gives
+                // those afresh
+                let complete = lockstep.complete;
+                for players in lockstep.turns.range_mut(complete + 1..) {
+                    players.1[1] = None;
+                }
+                lockstep.peer = Some(from);
+                lockstep.peer_complete = 0;
+                lockstep.welcomed = true;
+                lockstep.last_heard = Some(Instant::now());
+                lockstep.send(&Message::Welcome { player: 1 }, from);
+            }
+            Message::Welcome { player } => {
+                if lockstep.peer != Some(from) || lockstep.welcomed {
+                    continue;
+                }
+                info!("<receive_messages> Joined as player {}", player + 1);
+                lockstep.local = player;
+                lockstep.welcomed = true;
+                lockstep.last_heard = Some(Instant::now());
+                local_player.0 = Owner(player);
+            }
+            Message::Refused { reason } => {
+                if lockstep.peer == Some(from) && !lockstep.welcomed {
+                    warn!("<receive_messages> The host refused us: {}", reason);
+                    lockstep.refused = Some(reason);
+                }
+            }
+            Message::Turns {
+                complete,
+                tick,
+                turns,
+                hashes,
+            } => {
+                if lockstep.peer != Some(from) || !lockstep.welcomed {
+                    continue;
+                }
+                lockstep.last_heard = Some(Instant::now());
+                lockstep.synced = true;
+                lockstep.peer_complete = lockstep.peer_complete.max(complete);
+                lockstep.peer_tick = tick;
+                // A rejoining player already gave every turn the other side has
+                // finished, those come back with the history rather than anew
+                lockstep.next_local_tick = lockstep.next_local_tick.max(complete + 1);
+                for turn in turns {
+                    lockstep.add_turn(turn);
+                }
+                lockstep.peer_hashes.extend(hashes);
+            }
+        }
+    }
+}
+
+/// Holds back this player's orders for their next turn. Runs every tick, even
+/// ones the gate keeps shut, so orders given while waiting on the other player
+/// aren't dropped when their events expire
+fn take_local_commands(
+    mut lockstep: ResMut<Lockstep>,
+    mut game_commands: ResMut<Events<GameCommand>>,
+    sim_ids: Query<&SimId>,
+) {
+    let given: Vec<ReplayCommand> = game_commands
+        .drain()
+        .filter_map(|command| ReplayCommand::from_command(&command, &sim_ids))
+        .collect();
+    lockstep.pending.extend(given);
+}
+
+/// Holds the tick back until both players' turns for it are in, and notes the
+/// state the last tick left behind
+fn hold_for_turn(
+    mut lockstep: ResMut<Lockstep>,
+    mut gate: ResMut<TickGate>,
+    tick: Res<SimTick>,
+    entities: SimState,
+) {
+    lockstep
+        .hashes
+        .entry(tick.0)
+        .or_insert_with(|| state_hash(&entities));
+
+    gate.open = lockstep.welcomed && lockstep.complete > tick.0;
+}
+
+/// Gives the orders both players settled on for this tick, anything else was
+/// already taken for a later turn
+fn exchange_turns(
+    lockstep: Res<Lockstep>,
+    mut game_commands: ResMut<Events<GameCommand>>,
+    tick: Res<SimTick>,
+    ids: Res<SimIds>,
+    owners: Query<Option<&Owner>>,
+) {
+    game_commands.clear();
+
+    let Some(players) = lockstep.turns.get(&tick.0) else {
+        return;
+    };
+
+    for (player, commands) in players.iter().enumerate() {
+        for recorded in commands.iter().flatten() {
+            let Some(command) = recorded.to_command(&ids) else {
+                continue;
+            };
+
+            // Players can only order their own units and buildings
+            let owner = command
+                .subject()
+                .map(|subject| owners.get(subject).ok().flatten().copied());
+            if owner.is_some_and(|owner| owner.unwrap_or_default() != Owner(player as u8)) {
+                info!(
+                    "<exchange_turns> Player {} can't give {:?}",
+                    player + 1,
+                    command
+                );
+                continue;
+            }
+
+            game_commands.send(command);
+        }
+    }
+}
+
+/// Fills in this player's turns up to a few ticks ahead, the first one taking
+/// any orders given since the last
+fn queue_local_turns(mut lockstep: ResMut<Lockstep>, tick: Res<SimTick>) {
+    if !lockstep.welcomed || !lockstep.synced {
+        return;
+    }
+
+    // Run ahead as far as the other player is, so a rejoining game doesn't hold them up
+    let target = tick.0.max(lockstep.peer_tick) + INPUT_DELAY;
+    while lockstep.next_local_tick <= target {
+        let turn = Turn {
+            tick: lockstep.next_local_tick,
+            player: lockstep.local,
+            commands: std::mem::take(&mut lockstep.pending),
+        };
+        lockstep.next_local_tick += 1;
+        lockstep.add_turn(turn);
+    }
+}
+
+/// Sends turns, acknowledgements and state hashes to the other player, or asks
+/// the host for a seat
+fn send_messages(mut lockstep: ResMut<Lockstep>, tick: Res<SimTick>) {
+    let Some(peer) = lockstep.peer else {
+        return;
+    };
+
+    if !lockstep.welcomed {
+        if lockstep.refused.is_some() {
+            return;
+        }
+        let due = lockstep
+            .last_hello
+            .is_none_or(|sent| sent.elapsed() >= HELLO_INTERVAL);
+        if due {
+            lockstep.last_hello = Some(Instant::now());
+            let hello = Message::Hello {
+                version: PROTOCOL_VERSION,
+                map: map_name().to_string(),
+            };
+            lockstep.send(&hello, peer);
+        }
+        return;
+    }
+
+    let message = Message::Turns {
+        complete: lockstep.complete,
+        tick: tick.0,
+        turns: lockstep.unconfirmed_turns(),
+        hashes: lockstep
+            .hashes
+            .iter()
+            .rev()
+            .take(HASHES_PER_MESSAGE)
+            .map(|(tick, hash)| (*tick, *hash))
+            .collect(),
+    };
+    lockstep.send(&message, peer);
+}
+
+/// Compares state hashes for ticks both games have run
+fn check_desync(mut lockstep: ResMut<Lockstep>) {
+    let lockstep = &mut *lockstep;
+    let mismatch = lockstep
+        .peer_hashes
+        .iter()
+        .find(|(tick, hash)| lockstep.hashes.get(tick).is_some_and(|own| own != *hash))
+        .map(|(tick, _)| *tick);
+
+    if let Some(tick) = mismatch {
+        if lockstep.desync.is_none() {
+            error!(
+                "<check_desync> The games went out of sync at tick {}, save the replays from both sides",
+                tick
+            );
+            lockstep.desync = Some(tick);
+        }
+    }
+
+    // Hashes both sides have moved well past are no use any more
+    let oldest = lockstep.complete.saturating_sub(100);
+    lockstep.hashes.retain(|tick, _| *tick >= oldest);
+    lockstep.peer_hashes.retain(|tick, _| *tick >= oldest);
+}
+
+/// Runs the game faster while far behind the other player, after rejoining
+fn catch_up(lockstep: Res<Lockstep>, tick: Res<SimTick>, mut time: ResMut<Time<Virtual>>) {
+    let speed = if lockstep.complete > tick.0 + CATCH_UP_THRESHOLD {
+        CATCH_UP_SPEED
+    } else {
+        1.0
+    };
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// HUD text showing the state of the connection.
+#[derive(Component)]
+struct NetworkText;
+
+/// Adds the connection status line to the HUD
+fn setup_network_text(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::srgb(0.6, 0.85, 1.0)),
+            NetworkText,
+        ));
+    });
+}
+
+/// Keeps the connection status line up to date
+fn update_network_text(
+    lockstep: Res<Lockstep>,
+    tick: Res<SimTick>,
+    mut text_query: Query<(&mut Text, &mut TextColor), With<NetworkText>>,
+) {
+    let Ok((mut text, mut color)) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let (status, is_error) = if let Some(desync) = lockstep.desync {
+        (format!("Out of sync since tick {}", desync), true)
+    } else if let Some(reason) = &lockstep.refused {
+        (format!("Could not join: {}", reason), true)
+    } else if !lockstep.welcomed {
+        match lockstep.role {
+            Role::Host => (
+                format!(
+                    "Waiting for a player on port {}",
+                    lockstep
+                        .socket
+                        .local_addr()
+                        .map(|addr| addr.port())
+                        .unwrap_or_default()
+                ),
+                false,
+            ),
+            Role::Join { host } => (format!("Joining {}...", host), false),
+        }
+    } else if !lockstep.connected() {
+        (
+            format!("Lost player {}, waiting for them", 2 - lockstep.local),
+            true,
+        )
+    } else if lockstep.complete > tick.0 + CATCH_UP_THRESHOLD {
+        (
+            format!("Catching up, {} ticks behind", lockstep.complete - tick.0),
+            false,
+        )
+    } else {
+        (format!("Playing as player {}", lockstep.local + 1), false)
+    };
+
+    *text = Text::new(status);
+    color.0 = if is_error {
+        Color::srgb(1.0, 0.4, 0.4)
+    } else {
+        Color::srgb(0.6, 0.85, 1.0)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs_ldtk::prelude::GridCoords;
+
+    /// Frames either game gets to reach what a test waits for.
+    const MAX_FRAMES: u32 = 5000;
+
+    fn advance(mut tick: ResMut<SimTick>) {
+        tick.0 += 1;
+    }
+
+    /// Carries out moves by putting the unit straight there, enough to change the hash
+    fn apply_moves(mut game_commands: EventReader<GameCommand>, mut units: Query<&mut GridCoords>) {
+        for command in game_commands.read() {
+            if let GameCommand::Move { unit, destination } = command {
+                if let Ok(mut coords) = units.get_mut(*unit) {
+                    *coords = *destination;
+                }
+            }
+        }
+    }
+
+    /// One side of the game, with a unit for each player and the lockstep
+    /// systems in the order a frame runs them
+    fn endpoint(lockstep: Lockstep) -> App {
+        let mut app = App::new();
+        app.add_event::<GameCommand>()
+            .init_resource::<LocalPlayer>()
+            .init_resource::<TickGate>()
+            .init_resource::<SimTick>()
+            .init_resource::<SimIds>()
+            .insert_resource(lockstep)
+            .add_systems(
+                Update,
+                (
+                    receive_messages,
+                    take_local_commands,
+                    hold_for_turn,
+                    (advance, exchange_turns, apply_moves)
+                        .chain()
+                        .run_if(|gate: Res<TickGate>| gate.open),
+                    check_desync,
+                    queue_local_turns,
+                    send_messages,
+                )
+                    .chain(),
+            );
+
+        for (player, x) in [(0, 0), (1, 5)] {
+            let unit = app
+                .world_mut()
+                .spawn((GridCoords::new(x, 0), Owner(player)))
+                .id();
+            let id = app.world_mut().resource_mut::<SimIds>().assign(unit);
+            app.world_mut().entity_mut(unit).insert(id);
+        }
+        app
+    }
+
+    fn host() -> (App, SocketAddr) {
+        let lockstep = Lockstep::host(0).unwrap();
+        let port = lockstep.socket.local_addr().unwrap().port();
+        (endpoint(lockstep), SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    fn join(host: SocketAddr) -> App {
+        endpoint(Lockstep::join(host).unwrap())
+    }
+
+    fn tick(app: &App) -> u64 {
+        app.world().resource::<SimTick>().0
+    }
+
+    /// Has a player walk their unit somewhere
+    fn order_move(app: &mut App, unit: SimId, destination: GridCoords) {
+        let unit = app.world().resource::<SimIds>().entity(unit).unwrap();
+        app.world_mut()
+            .send_event(GameCommand::Move { unit, destination });
+    }
+
+    /// Runs frames on both games until `done`
+    fn run_until(host: &mut App, join: &mut App, mut done: impl FnMut(&App, &App) -> bool) {
+        for _ in 0..MAX_FRAMES {
+            host.update();
+            join.update();
+            if done(host, join) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!(
+            "the games stalled at ticks {} and {}",
+            tick(host),
+            tick(join)
+        );
+    }
+
+    /// Checks both games hashed the same state on every tick they both ran
+    fn assert_in_sync(host: &App, join: &App) {
+        let host = host.world().resource::<Lockstep>();
+        let join = join.world().resource::<Lockstep>();
+        assert_eq!(host.desync, None);
+        assert_eq!(join.desync, None);
+
+        let mut compared = 0;
+        for (tick, hash) in &host.hashes {
+            if let Some(other) = join.hashes.get(tick) {
+                assert_eq!(hash, other, "the games differ at tick {}", tick);
+                compared += 1;
+            }
+        }
+        assert!(compared > 0, "the games have no ticks in common");
+    }
+
+    #[test]
+    fn both_players_orders_play_out_the_same() {
+        let (mut host, address) = host();
+        let mut join = join(address);
+
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= 10 && tick(join) >= 10
+        });
+        assert_eq!(join.world().resource::<LocalPlayer>().0, Owner(1));
+
+        order_move(&mut host, SimId(0), GridCoords::new(2, 2));
+        order_move(&mut join, SimId(1), GridCoords::new(7, 3));
+        // Neither player may move the other's unit
+        order_move(&mut join, SimId(0), GridCoords::new(9, 9));
+
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= 60 && tick(join) >= 60
+        });
+        assert_in_sync(&host, &join);
+
+        for app in [&mut host, &mut join] {
+            let world = app.world_mut();
+            let mut units = world.query::<(&SimId, &GridCoords)>();
+            let mut positions: Vec<_> = units.iter(world).map(|(id, c)| (*id, *c)).collect();
+            positions.sort_by_key(|(id, _)| *id);
+            assert_eq!(
+                positions,
+                vec![
+                    (SimId(0), GridCoords::new(2, 2)),
+                    (SimId(1), GridCoords::new(7, 3)),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn orders_given_while_waiting_are_kept() {
+        let (mut host, address) = host();
+        let mut join = join(address);
+
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= 10 && tick(join) >= 10
+        });
+
+        // The second player goes quiet, so the host runs out of their turns
+        for _ in 0..20 {
+            host.update();
+        }
+        let stalled_at = tick(&host);
+        assert!(!host.world().resource::<TickGate>().open);
+
+        // An order given now outlives many frames of the gate being shut
+        order_move(&mut host, SimId(0), GridCoords::new(2, 2));
+        for _ in 0..20 {
+            host.update();
+        }
+        assert_eq!(tick(&host), stalled_at);
+
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= stalled_at + 20 && tick(join) >= stalled_at + 20
+        });
+        assert_in_sync(&host, &join);
+
+        for app in [&host, &join] {
+            let unit = app.world().resource::<SimIds>().entity(SimId(0)).unwrap();
+            assert_eq!(
+                app.world().get::<GridCoords>(unit),
+                Some(&GridCoords::new(2, 2))
+            );
+        }
+    }
+
+    #[test]
+    fn rejoining_player_catches_up() {
+        let (mut host, address) = host();
+        let mut join = join(address);
+
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= 10 && tick(join) >= 10
+        });
+        order_move(&mut host, SimId(0), GridCoords::new(2, 2));
+        order_move(&mut join, SimId(1), GridCoords::new(7, 3));
+        run_until(&mut host, &mut join, |host, join| {
+            tick(host) >= 40 && tick(join) >= 40
+        });
+        let left_at = tick(&host);
+
+        // The second player's game closes, and the host has to notice before a
+        // new game can take the seat
+        drop(join);
+        let gone_by = Instant::now() + TIMEOUT * 2;
+        while host.world().resource::<Lockstep>().connected() {
+            assert!(Instant::now() < gone_by, "the host never noticed");
+            host.update();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut rejoined = self::join(address);
+        run_until(&mut host, &mut rejoined, |host, rejoined| {
+            tick(rejoined) >= left_at + 20
+        });
+        assert_eq!(rejoined.world().resource::<LocalPlayer>().0, Owner(1));
+        assert_in_sync(&host, &rejoined);
+
+        // The old orders were replayed on the way back in
+        let unit = rejoined
+            .world()
+            .resource::<SimIds>()
+            .entity(SimId(1))
+            .unwrap();
+        assert_eq!(
+            rejoined.world().get::<GridCoords>(unit),
+            Some(&GridCoords::new(7, 3))
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use crate::components::entities::{Character, Warrior, Worker};
+use crate::components::inventory::{Inventory, Stockpile};
+use crate::components::movement::{Collider, MoveTarget};
+use crate::components::skills::SkillProgression;
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selectable, Selected, Unit, UnitType};
+use crate::systems::game_command::GameCommand;
+use crate::systems::garrison::Garrison;
+use crate::systems::housing::Population;
+use crate::systems::inventory::{stockpile_count, stockpile_take};
+use crate::systems::movement::grid_to_translation;
+use crate::systems::resource_gathering::find_adjacent_positions;
+use crate::systems::scene::find_entity_layer;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use crate::systems::victory::MatchStats;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for unit production systems.
+pub struct ProductionPlugin;
+
+impl Plugin for ProductionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_train_buttons.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                execute_production_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                process_production.in_set(SimulationSet::Actions),
+            )
+            .add_systems(Update, update_production_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// How many units a single building can have waiting to be trained.
+const MAX_QUEUE_LENGTH: usize = 5;
+
+/// Queue of units being trained by a building.
+#[derive(Component, Debug, Default)]
+pub struct ProductionQueue {
+    pub queue: Vec<UnitType>,
+    pub progress: f32,
+    pub rally_point: Option<GridCoords>,
+}
+
+/// Button in the info panel that queues a unit.
+#[derive(Component)]
+pub struct TrainUnitButton(pub UnitType);
+
+/// Spawns a freshly trained unit in the entity layer at the given cell
+pub fn spawn_unit(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    layer: Entity,
+    unit_type: UnitType,
+    owner: Owner,
+    coords: GridCoords,
+) -> Entity {
+    let mut unit = commands.spawn((
+        Unit,
+        Character,
+        Name::new(unit_type.name()),
+        owner,
+        Selectable,
+        Collider,
+        Sprite {
+            image: asset_server.load(unit_type.sprite_path()),
+            custom_size: Some(Vec2::new(64.0, 64.0)),
+            ..default()
+        },
+        Transform::from_translation(grid_to_translation(coords, 0.0)),
+        coords,
+        unit_type.movable(),
+        MoveTarget::default(),
+        unit_type.inventory(),
+        unit_type.base_skills(),
+        SkillProgression::default(),
+        unit_type.health(),
+    ));
+
+    match unit_type {
+        UnitType::Worker => {
+            unit.insert(Worker);
+        }
+        UnitType::Warrior => {
+            unit.insert(Warrior);
+        }
+    }
+
+    if let Some(attack_stats) = unit_type.attack_stats() {
+        unit.insert(attack_stats);
+    }
+
+    let unit = unit.id();
+    commands.entity(layer).add_child(unit);
+    unit
+}
+
+/// Orders the selected building to train a unit when one of the train buttons is clicked
+fn handle_train_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &TrainUnitButton)>,
+    producers: Query<Entity, (With<Selected>, With<ProductionQueue>)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(building) = producers.get_single() else {
+        return;
+    };
+
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            game_commands.send(GameCommand::Train {
+                building,
+                unit_type: button.0,
+            });
+        }
+    }
+}
+
+/// Queues ordered units, paying from the stockpile, and moves rally points
+fn execute_production_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut producers: Query<(Option<&Owner>, &mut ProductionQueue)>,
+    mut stockpile: Query<(&mut Inventory, Option<&Owner>), With<Stockpile>>,
+    population: Res<Population>,
+) {
+    // The population counts are only refreshed after the tick, so units queued
+    // here are counted as they go
+    let mut queued: HashMap<Owner, u32> = HashMap::new();
+
+    for command in game_commands.read() {
+        match command {
+            GameCommand::SetRallyPoint { building, at } => {
+                if let Ok((_, mut production)) = producers.get_mut(*building) {
+                    production.rally_point = Some(*at);
+                    info!("<execute_production_commands> Rally point set to {:?}", at);
+                }
+            }
+            GameCommand::Train {
+                building,
+                unit_type,
+            } => {
+                let Ok((owner, mut production)) = producers.get_mut(*building) else {
+                    continue;
+                };
+
+                if production.queue.len() >= MAX_QUEUE_LENGTH {
+                    info!("<execute_production_commands> Production queue is full");
+                    continue;
+                }
+
+                let owner = owner.copied().unwrap_or_default();
+
+                // Queued units count towards the cap too
+                let mut player = population.get(owner);
+                player.queued += queued.get(&owner).copied().unwrap_or(0);
+                if player.is_capped() {
+                    info!(
+                        "<execute_production_commands> Population cap reached, build more houses"
+                    );
+                    continue;
+                }
+
+                let cost = unit_type.get_cost();
+                let has_resources = cost
+                    .iter()
+                    .all(|(item, amount)| stockpile_count(&stockpile, owner, item) >= *amount);
+
+                if !has_resources {
+                    info!(
+                        "<execute_production_commands> Not enough resources in the stockpile for {:?}",
+                        unit_type
+                    );
+                    continue;
+                }
+
+                for (item, amount) in cost {
+                    stockpile_take(&mut stockpile, owner, &item, amount);
+                }
+
+                production.queue.push(*unit_type);
+                *queued.entry(owner).or_default() += 1;
+                info!("<execute_production_commands> Queued {:?}", unit_type);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Advances training and spawns units next to their building when done
+fn process_production(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut producers: Query<(
+        Entity,
+        &GridCoords,
+        Option<&Owner>,
+        &mut ProductionQueue,
+        Option<&Garrison>,
+    )>,
+    workers: Query<(), With<Worker>>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    layers: Query<(Entity, &LayerMetadata)>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for (entity, coords, owner, mut production, garrison) in &mut producers {
+        let Some(&unit_type) = production.queue.first() else {
+            continue;
+        };
+
+        // Workers inside the building help out
+        let work_speed = garrison.map_or(1.0, |garrison| garrison.work_speed(&workers));
+        production.progress += time.delta_secs() * work_speed;
+
+        if production.progress < unit_type.training_time() {
+            continue;
+        }
+
+        let Some(layer) = find_entity_layer(&layers) else {
+            continue;
+        };
+
+        // Wait until there's room next to the building
+        let Some(spawn_coords) = find_adjacent_positions(*coords, &obstacles)
+            .first()
+            .copied()
+        else {
+            info!(
+                "<process_production> No free cell next to {:?}, waiting",
+                entity
+            );
+            continue;
+        };
+
+        production.queue.remove(0);
+        production.progress = 0.0;
+
+        let owner = owner.copied().unwrap_or_default();
+        let unit = spawn_unit(
+            &mut commands,
+            &asset_server,
+            layer,
+            unit_type,
+            owner,
+            spawn_coords,
+        );
+        stats.record_trained(owner);
+
+        if let Some(rally_point) = production.rally_point {
+            commands.entity(unit).insert(MoveTarget {
+                destination: Some(rally_point),
+                path: Vec::new(),
+            });
+        }
+
+        info!(
+            "<process_production> Trained {:?} at {:?}",
+            unit_type, spawn_coords
+        );
+    }
+}
+
+/// Shows training progress and the train buttons for the selected building
+fn update_production_ui(
+    selected: Query<&ProductionQueue, With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok(production) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new("Production"),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        if let Some(unit_type) = production.queue.first() {
+            let progress_percent = (production.progress / unit_type.training_time()) * 100.0;
+
+            parent.spawn((
+                Text::new(format!(
+                    "Training {}: {:.1}% ({}/{} queued)",
+                    unit_type.name(),
+                    progress_percent,
+                    production.queue.len(),
+                    MAX_QUEUE_LENGTH
+                )),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.0, 1.0, 0.0)),
+            ));
+        }
+
+        if let Some(rally_point) = production.rally_point {
+            parent.spawn((
+                Text::new(format!("Rally point: {}, {}", rally_point.x, rally_point.y)),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        }
+
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for unit_type in [UnitType::Worker, UnitType::Warrior] {
+                    let gold = unit_type
+                        .get_cost()
+                        .iter()
+                        .map(|(_, amount)| amount)
+                        .sum::<u32>();
+
+                    row.spawn((
+                        Button,
+                        // Let the panel see the click too, so it doesn't reach the map
+                        FocusPolicy::Pass,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                        TrainUnitButton(unit_type),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(format!("{} ({} gold)", unit_type.name(), gold)),
+                            TextFont {
+                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                font_size: 12.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                }
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::items::{ItemId, ItemRegistry};
+    use crate::systems::housing::{update_population, BASE_POPULATION_CAP};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn training_in_one_tick_stops_at_the_cap() {
+        let mut app = App::new();
+        app.add_event::<GameCommand>().init_resource::<Population>();
+
+        let world = app.world_mut();
+        // One slot short of the cap, and gold for several units
+        for _ in 0..BASE_POPULATION_CAP - 1 {
+            world.spawn((Character, Owner(0)));
+        }
+        let mut gold = Inventory::new(1);
+        gold.add_item(&ItemId::GOLD, 20, &ItemRegistry::default());
+        world.spawn((gold, Stockpile, Owner(0)));
+        let houses: Vec<Entity> = (0..2)
+            .map(|_| world.spawn((ProductionQueue::default(), Owner(0))).id())
+            .collect();
+        world.run_system_once(update_population).unwrap();
+
+        for building in &houses {
+            world.send_event(GameCommand::Train {
+                building: *building,
+                unit_type: UnitType::Worker,
+            });
+        }
+        world.run_system_once(execute_production_commands).unwrap();
+
+        let queued: usize = houses
+            .iter()
+            .map(|house| world.get::<ProductionQueue>(*house).unwrap().queue.len())
+            .sum();
+        assert_eq!(queued, 1);
+    }
+}
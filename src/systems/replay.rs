@@ -0,0 +1,697 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::components::items::ItemId;
+use crate::components::ui::HudPanel;
+use crate::components::unit::UnitType;
+use crate::systems::construction::BuildingType;
+use crate::systems::crafting::Recipe;
+use crate::systems::game_command::GameCommand;
+use crate::systems::idle::AutoBehaviour;
+use crate::systems::jobs::JobKind;
+use crate::systems::save::{map_name, now};
+use crate::systems::simulation::{SimId, SimIds, SimTick, SimulationSet};
+use crate::systems::transfer::{TransferAmount, TransferDirection};
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Plugin that records every game's commands and plays recorded games back.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(
+                Update,
+                start_recording.run_if(not(resource_exists::<ReplayPlayback>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                record_commands
+                    .run_if(not(resource_exists::<ReplayPlayback>))
+                    .in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                play_back_commands
+                    .run_if(resource_exists::<ReplayPlayback>)
+                    .in_set(SimulationSet::Input),
+            )
+            .add_systems(Last, write_replay_on_exit.in_set(ClientSet))
+            .add_systems(
+                PostStartup,
+                setup_replay_text
+                    .run_if(resource_exists::<ReplayPlayback>)
+                    .in_set(ClientSet),
+            )
+            .add_systems(
+                Update,
+                (replay_controls, update_replay_text)
+                    .chain()
+                    .run_if(resource_exists::<ReplayPlayback>)
+                    .in_set(ClientSet),
+            );
+    }
+}
+
+/// Bumped whenever replays from an older version can no longer be played,
+/// including when `TICK_RATE` or the order of the simulation changes.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Folder replays are written to.
+const REPLAY_DIR: &str = "replays";
+
+/// Playback speeds the speed keys step through.
+const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// `GameCommand` with entities named by `SimId` and cells as plain pairs, so it
+/// can be written down and given again in another run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayCommand {
+    Move {
+        unit: SimId,
+        destination: (i32, i32),
+    },
+    Gather {
+        unit: SimId,
+        resource: SimId,
+    },
+    Build {
+        unit: SimId,
+        building_type: BuildingType,
+        at: (i32, i32),
+    },
+    Transfer {
+        unit: SimId,
+        target: SimId,
+        item: ItemId,
+        amount: TransferAmount,
+        direction: TransferDirection,
+    },
+    Attack {
+        unit: SimId,
+        target: SimId,
+    },
+    Garrison {
+        unit: SimId,
+        building: SimId,
+    },
+    PickUp {
+        unit: SimId,
+        pile: SimId,
+    },
+    Stop {
+        unit: SimId,
+    },
+    SetRallyPoint {
+        building: SimId,
+        at: (i32, i32),
+    },
+    Train {
+        building: SimId,
+        unit_type: UnitType,
+    },
+    Craft {
+        station: SimId,
+        recipe: Recipe,
+    },
+    Eject {
+        building: SimId,
+    },
+    ToggleGatherDesignation {
+        resource: SimId,
+    },
+    SetAutoBehaviour {
+        unit: SimId,
+        behaviour: AutoBehaviour,
+    },
+    SortInventory {
+        owner: SimId,
+    },
+    MoveSlot {
+        from: SimId,
+        slot: usize,
+        quantity: u32,
+        to: SimId,
+        to_slot: usize,
+    },
+    DropSlot {
+        owner: SimId,
+        slot: usize,
+        quantity: u32,
+    },
+    ToggleFilter {
+        storage: SimId,
+        item: ItemId,
+    },
+    CyclePriority {
+        unit: SimId,
+        kind: JobKind,
+    },
+}
+
+impl ReplayCommand {
+    /// Swaps the command's entities for their ids, `None` if one has no id
+    pub fn from_command(command: &GameCommand, ids: &Query<&SimId>) -> Option<Self> {
+        let id = |entity: Entity| ids.get(entity).ok().copied();
+        let cell = |coords: GridCoords| (coords.x, coords.y);
+
+        Some(match command {
+            GameCommand::Move { unit, destination } => ReplayCommand::Move {
+                unit: id(*unit)?,
+                destination: cell(*destination),
+            },
+            GameCommand::Gather { unit, resource } => ReplayCommand::Gather {
+                unit: id(*unit)?,
+                resource: id(*resource)?,
+            },
+            GameCommand::Build {
+                unit,
+                building_type,
+                at,
+            } => ReplayCommand::Build {
+                unit: id(*unit)?,
+                building_type: *building_type,
+                at: cell(*at),
+            },
+            GameCommand::Transfer {
+                unit,
+                target,
+                item,
+                amount,
+                direction,
+            } => ReplayCommand::Transfer {
+                unit: id(*unit)?,
+                target: id(*target)?,
+                item: item.clone(),
+                amount: *amount,
+                direction: *direction,
+            },
+            GameCommand::Attack { unit, target } => ReplayCommand::Attack {
+                unit: id(*unit)?,
+                target: id(*target)?,
+            },
+            GameCommand::Garrison { unit, building } => ReplayCommand::Garrison {
+                unit: id(*unit)?,
+                building: id(*building)?,
+            },
+            GameCommand::PickUp { unit, pile } => ReplayCommand::PickUp {
+                unit: id(*unit)?,
+                pile: id(*pile)?,
+            },
+            GameCommand::Stop { unit } => ReplayCommand::Stop { unit: id(*unit)? },
+            GameCommand::SetRallyPoint { building, at } => ReplayCommand::SetRallyPoint {
+                building: id(*building)?,
+                at: cell(*at),
+            },
+            GameCommand::Train {
+                building,
+                unit_type,
+            } => ReplayCommand::Train {
+                building: id(*building)?,
+                unit_type: *unit_type,
+            },
+            GameCommand::Craft { station, recipe } => ReplayCommand::Craft {
+                station: id(*station)?,
+                recipe: *recipe,
+            },
+            GameCommand::Eject { building } => ReplayCommand::Eject {
+                building: id(*building)?,
+            },
+            GameCommand::ToggleGatherDesignation { resource } => {
+                ReplayCommand::ToggleGatherDesignation {
+                    resource: id(*resource)?,
+                }
+            }
+            GameCommand::SetAutoBehaviour { unit, behaviour } => ReplayCommand::SetAutoBehaviour {
+                unit: id(*unit)?,
+                behaviour: *behaviour,
+            },
+            GameCommand::SortInventory { owner } => {
+                ReplayCommand::SortInventory { owner: id(*owner)? }
+            }
+            GameCommand::MoveSlot {
+                from,
+                slot,
+                quantity,
+                to,
+                to_slot,
+            } => ReplayCommand::MoveSlot {
+                from: id(*from)?,
+                slot: *slot,
+                quantity: *quantity,
+                to: id(*to)?,
+                to_slot: *to_slot,
+            },
+            GameCommand::DropSlot {
+                owner,
+                slot,
+                quantity,
+            } => ReplayCommand::DropSlot {
+                owner: id(*owner)?,
+                slot: *slot,
+                quantity: *quantity,
+            },
+            GameCommand::ToggleFilter { storage, item } => ReplayCommand::ToggleFilter {
+                storage: id(*storage)?,
+                item: item.clone(),
+            },
+            GameCommand::CyclePriority { unit, kind } => ReplayCommand::CyclePriority {
+                unit: id(*unit)?,
+                kind: *kind,
+            },
+        })
+    }
+
+    /// Finds the entities again, `None` if one was never numbered in this run
+    pub fn to_command(&self, ids: &SimIds) -> Option<GameCommand> {
+        let entity = |id: SimId| ids.entity(id);
+        let cell = |(x, y): (i32, i32)| GridCoords::new(x, y);
+
+        Some(match self {
+            ReplayCommand::Move { unit, destination } => GameCommand::Move {
+                unit: entity(*unit)?,
+                destination: cell(*destination),
+            },
+            ReplayCommand::Gather { unit, resource } => GameCommand::Gather {
+                unit: entity(*unit)?,
+                resource: entity(*resource)?,
+            },
+            ReplayCommand::Build {
+                unit,
+                building_type,
+                at,
+            } => GameCommand::Build {
+                unit: entity(*unit)?,
+                building_type: *building_type,
+                at: cell(*at),
+            },
+            ReplayCommand::Transfer {
+                unit,
+                target,
+                item,
+                amount,
+                direction,
+            } => GameCommand::Transfer {
+                unit: entity(*unit)?,
+                target: entity(*target)?,
+                item: item.clone(),
+                amount: *amount,
+                direction: *direction,
+            },
+            ReplayCommand::Attack { unit, target } => GameCommand::Attack {
+                unit: entity(*unit)?,
+                target: entity(*target)?,
+            },
+            ReplayCommand::Garrison { unit, building } => GameCommand::Garrison {
+                unit: entity(*unit)?,
+                building: entity(*building)?,
+            },
+            ReplayCommand::PickUp { unit, pile } => GameCommand::PickUp {
+                unit: entity(*unit)?,
+                pile: entity(*pile)?,
+            },
+            ReplayCommand::Stop { unit } => GameCommand::Stop {
+                unit: entity(*unit)?,
+            },
+            ReplayCommand::SetRallyPoint { building, at } => GameCommand::SetRallyPoint {
+                building: entity(*building)?,
+                at: cell(*at),
+            },
+            ReplayCommand::Train {
+                building,
+                unit_type,
+            } => GameCommand::Train {
+                building: entity(*building)?,
+                unit_type: *unit_type,
+            },
+            ReplayCommand::Craft { station, recipe } => GameCommand::Craft {
+                station: entity(*station)?,
+                recipe: *recipe,
+            },
+            ReplayCommand::Eject { building } => GameCommand::Eject {
+                building: entity(*building)?,
+            },
+            ReplayCommand::ToggleGatherDesignation { resource } => {
+                GameCommand::ToggleGatherDesignation {
+                    resource: entity(*resource)?,
+                }
+            }
+            ReplayCommand::SetAutoBehaviour { unit, behaviour } => GameCommand::SetAutoBehaviour {
+                unit: entity(*unit)?,
+                behaviour: *behaviour,
+            },
+            ReplayCommand::SortInventory { owner } => GameCommand::SortInventory {
+                owner: entity(*owner)?,
+            },
+            ReplayCommand::MoveSlot {
+                from,
+                slot,
+                quantity,
+                to,
+                to_slot,
+            } => GameCommand::MoveSlot {
+                from: entity(*from)?,
+                slot: *slot,
+                quantity: *quantity,
+                to: entity(*to)?,
+                to_slot: *to_slot,
+            },
+            ReplayCommand::DropSlot {
+                owner,
+                slot,
+                quantity,
+            } => GameCommand::DropSlot {
+                owner: entity(*owner)?,
+                slot: *slot,
+                quantity: *quantity,
+            },
+            ReplayCommand::ToggleFilter { storage, item } => GameCommand::ToggleFilter {
+                storage: entity(*storage)?,
+                item: item.clone(),
+            },
+            ReplayCommand::CyclePriority { unit, kind } => GameCommand::CyclePriority {
+                unit: entity(*unit)?,
+                kind: *kind,
+            },
+        })
+    }
+}
+
+/// A command and the simulation tick it was carried out on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedCommand {
+    pub tick: u64,
+    pub command: ReplayCommand,
+}
+
+/// Everything needed to play a game again: the map it started from and every
+/// command given, by tick.
+///
+/// Sorting, moving and dropping items in the info panel are commands too, so
+/// they're recorded like any other order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    // Seconds since the Unix epoch
+    #[serde(default)]
+    pub recorded_at: u64,
+    pub map: String,
+    // Ticks the recording ran for
+    pub length: u64,
+    pub commands: Vec<TimedCommand>,
+}
+
+/// Just the version, read first so old replays fail with a clear error.
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not access replay: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write replay: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not read replay: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("replay is from version {found}, this game plays version {expected}")]
+    Version { found: u32, expected: u32 },
+    #[error("replay was recorded on {found}, this game plays {expected}")]
+    WrongMap { found: String, expected: String },
+}
+
+impl Replay {
+    pub fn to_ron(&self) -> Result<String, ReplayError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, ReplayError> {
+        let header: ReplayHeader = ron::from_str(contents)?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::Version {
+                found: header.version,
+                expected: REPLAY_VERSION,
+            });
+        }
+
+        let replay: Replay = ron::from_str(contents)?;
+        if replay.map != map_name() {
+            return Err(ReplayError::WrongMap {
+                found: replay.map,
+                expected: map_name().to_string(),
+            });
+        }
+        Ok(replay)
+    }
+}
+
+pub fn read_replay(path: &Path) -> Result<Replay, ReplayError> {
+    Replay::from_ron(&fs::read_to_string(path)?)
+}
+
+/// Writes a replay to the replays folder and returns where it went
+pub fn write_replay(replay: &Replay) -> Result<PathBuf, ReplayError> {
+    fs::create_dir_all(REPLAY_DIR)?;
+    let path = PathBuf::from(REPLAY_DIR).join(format!("replay-{}.ron", replay.recorded_at));
+    fs::write(&path, replay.to_ron()?)?;
+    Ok(path)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum RecorderState {
+    /// Waiting for the level to spawn
+    #[default]
+    Waiting,
+    Recording,
+    /// The level was spawned again, which a replay can't follow
+    Stopped,
+}
+
+/// Commands given so far this game, by tick.
+#[derive(Resource, Debug, Default)]
+pub struct ReplayRecorder {
+    state: RecorderState,
+    length: u64,
+    commands: Vec<TimedCommand>,
+}
+
+impl ReplayRecorder {
+    /// The recording so far as a replay
+    pub fn replay(&self) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            recorded_at: now(),
+            map: map_name().to_string(),
+            length: self.length,
+            commands: self.commands.clone(),
+        }
+    }
+
+    /// Stops recording and writes what there is
+    fn finish(&mut self) {
+        if self.state != RecorderState::Recording {
+            return;
+        }
+        self.state = RecorderState::Stopped;
+
+        match write_replay(&self.replay()) {
+            Ok(path) => info!("<finish> Replay written to {}", path.display()),
+            Err(error) => warn!("<finish> Could not write the replay: {}", error),
+        }
+    }
+}
+
+/// A replay being played instead of taking orders from the player.
+#[derive(Resource, Debug)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    next: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next: 0 }
+    }
+
+    /// Whether the recording has run out by this tick
+    pub fn is_finished(&self, tick: SimTick) -> bool {
+        tick.0 >= self.replay.length
+    }
+}
+
+/// Starts recording when the level first spawns. Loading a save spawns it again
+/// and ends the recording, since replays always start from the map
+fn start_recording(
+    mut level_events: EventReader<LevelEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    for event in level_events.read() {
+        if !matches!(event, LevelEvent::Transformed(_)) {
+            continue;
+        }
+
+        match recorder.state {
+            RecorderState::Waiting => {
+                info!("<start_recording> Recording a replay");
+                recorder.state = RecorderState::Recording;
+            }
+            RecorderState::Recording => {
+                info!("<start_recording> The level was reloaded, ending the replay here");
+                recorder.finish();
+            }
+            RecorderState::Stopped => {}
+        }
+    }
+}
+
+/// Writes down the commands this tick carries out, once `SimulationSet::Input`
+/// has settled what they are
+fn record_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut recorder: ResMut<ReplayRecorder>,
+    tick: Res<SimTick>,
+    ids: Query<&SimId>,
+) {
+    if recorder.state != RecorderState::Recording {
+        game_commands.clear();
+        return;
+    }
+
+    recorder.length = tick.0;
+    for command in game_commands.read() {
+        match ReplayCommand::from_command(command, &ids) {
+            Some(command) => recorder.commands.push(TimedCommand {
+                tick: tick.0,
+                command,
+            }),
+            None => warn!(
+                "<record_commands> {:?} points at something without an id, it won't be in the replay",
+                command
+            ),
+        }
+    }
+}
+
+/// Gives the commands recorded for this tick. The replay is the only source of
+/// orders while it plays, anything else is dropped
+fn play_back_commands(
+    mut playback: ResMut<ReplayPlayback>,
+    mut game_commands: ResMut<Events<GameCommand>>,
+    tick: Res<SimTick>,
+    ids: Res<SimIds>,
+) {
+    game_commands.clear();
+
+    while let Some(recorded) = playback.replay.commands.get(playback.next).cloned() {
+        if recorded.tick > tick.0 {
+            break;
+        }
+        playback.next += 1;
+
+        match recorded.command.to_command(&ids) {
+            Some(command) => {
+                game_commands.send(command);
+            }
+            None => warn!(
+                "<play_back_commands> {:?} points at something this run doesn't have",
+                recorded.command
+            ),
+        }
+    }
+}
+
+/// Writes the replay when the game closes. Headless runs take theirs from
+/// `ReplayRecorder` instead of leaving files behind
+fn write_replay_on_exit(mut exits: EventReader<AppExit>, mut recorder: ResMut<ReplayRecorder>) {
+    if exits.read().next().is_some() {
+        recorder.finish();
+    }
+}
+
+/// HUD text showing where the replay is.
+#[derive(Component)]
+struct ReplayText;
+
+/// Adds the replay status line to the HUD
+fn setup_replay_text(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::srgb(1.0, 0.85, 0.3)),
+            ReplayText,
+        ));
+    });
+}
+
+/// Space pauses and resumes the replay, [ and ] slow it down and speed it up
+fn replay_controls(keyboard: Res<ButtonInput<KeyCode>>, mut time: ResMut<Time<Virtual>>) {
+    if keyboard.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+
+    let current = PLAYBACK_SPEEDS
+        .iter()
+        .position(|speed| *speed >= time.relative_speed())
+        .unwrap_or(PLAYBACK_SPEEDS.len() - 1);
+    let next = if keyboard.just_pressed(KeyCode::BracketRight) {
+        (current + 1).min(PLAYBACK_SPEEDS.len() - 1)
+    } else if keyboard.just_pressed(KeyCode::BracketLeft) {
+        current.saturating_sub(1)
+    } else {
+        return;
+    };
+
+    time.set_relative_speed(PLAYBACK_SPEEDS[next]);
+    info!("<replay_controls> Playing at {}x", PLAYBACK_SPEEDS[next]);
+}
+
+/// Keeps the replay status line up to date
+fn update_replay_text(
+    playback: Res<ReplayPlayback>,
+    tick: Res<SimTick>,
+    time: Res<Time<Virtual>>,
+    mut text_query: Query<&mut Text, With<ReplayText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let status = if playback.is_finished(*tick) {
+        "finished".to_string()
+    } else if time.is_paused() {
+        "paused".to_string()
+    } else {
+        format!("{}x", time.relative_speed())
+    };
+    *text = Text::new(format!(
+        "Replay {}/{} ({}), Space pauses, [ ] change speed",
+        tick.0.min(playback.replay.length),
+        playback.replay.length,
+        status
+    ));
+}
@@ -1,42 +1,74 @@
-use crate::components::entities::{Forest, Mine, Quarry};
+use crate::components::entities::{Forest, Mine, Quarry, Worker};
 use crate::components::inventory::*;
+use crate::components::items::{ItemId, ItemRegistry};
 use crate::components::movement::{MoveTarget, Moving};
-use crate::components::skills::{SkillProgression, Skills};
+use crate::components::skills::{PerkEffect, SkillProgression, Skills};
 use crate::components::ui::EntityInfoPanel;
-use crate::components::unit::Selected;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::game_command::GameCommand;
+use crate::systems::housing::{Homeless, HOMELESS_EFFICIENCY};
+use crate::systems::item_piles::DropItems;
+use crate::systems::movement::grid_distance;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::skills::{SkillAction, SkillRegistry, SkillXpGained};
+use crate::systems::storage::DropOff;
+use crate::systems::ui::InfoPanelSet;
+use crate::systems::victory::MatchStats;
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::GridCoords;
-use bevy_ecs_ldtk::prelude::LdtkProjectHandle;
 
 /// Plugin for resource gathering systems.
 pub struct ResourceGatheringPlugin;
 
 impl Plugin for ResourceGatheringPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, gather_resources)
-            .add_systems(Update, start_gathering)
-            .add_systems(Update, check_gathering_proximity)
-            .add_systems(Update, update_skills_from_activities)
-            .add_systems(Update, update_character_info_ui)
-            .add_systems(Update, handle_resource_transfer);
+        app.add_systems(
+            FixedUpdate,
+            execute_gather_commands.in_set(SimulationSet::Commands),
+        )
+        .add_systems(
+            FixedUpdate,
+            (check_gathering_proximity, gather_resources)
+                .chain()
+                .in_set(SimulationSet::Actions),
+        )
+        .add_systems(
+            Update,
+            update_character_info_ui.in_set(InfoPanelSet::Rebuild),
+        );
     }
 }
 
 // Component to track gathering progress
 #[derive(Component, Debug)]
 pub struct Gathering {
-    pub resource_type: ResourceType,
+    pub item: ItemId,
     pub progress: f32,
     pub target: Entity,
     pub base_time: f32,
     pub skill_modifier: f32,
+    // Share of a doubled yield built up so far, one is due whenever it reaches 1
+    pub double_yield: f32,
 }
 
 // New component to track gathering intent
 #[derive(Component, Debug)]
 pub struct GatheringIntent {
     pub target: Entity,
-    pub resource_type: ResourceType,
+    pub item: ItemId,
+}
+
+/// Item a resource node yields
+pub fn resource_item(is_tree: bool, is_mine: bool, is_quarry: bool) -> Option<ItemId> {
+    if is_tree {
+        Some(ItemId::WOOD)
+    } else if is_mine {
+        Some(ItemId::GOLD)
+    } else if is_quarry {
+        Some(ItemId::STONE)
+    } else {
+        None
+    }
 }
 
 /// This system handles the gathering of resources by characters
@@ -47,15 +79,24 @@ fn gather_resources(
         Entity,
         &mut Gathering,
         &mut Inventory,
-        &InventorySettings,
         &Skills,
+        &SkillProgression,
+        &GridCoords,
+        Option<&Homeless>,
+        Option<&Owner>,
     )>,
-    mut skill_progression: Query<&mut SkillProgression>,
     trees: Query<Entity, With<Forest>>,
     mines: Query<Entity, With<Mine>>,
     quarries: Query<Entity, With<Quarry>>,
+    items: Res<ItemRegistry>,
+    skill_registry: Res<SkillRegistry>,
+    mut drop_events: EventWriter<DropItems>,
+    mut xp_events: EventWriter<SkillXpGained>,
+    mut stats: ResMut<MatchStats>,
 ) {
-    for (entity, mut gathering, mut inventory, settings, skills) in &mut gatherers {
+    for (entity, mut gathering, mut inventory, skills, progression, coords, homeless, owner) in
+        &mut gatherers
+    {
         let target_exists = trees.contains(gathering.target)
             || mines.contains(gathering.target)
             || quarries.contains(gathering.target);
@@ -65,245 +106,136 @@ fn gather_resources(
             continue;
         }
 
-        let progress_rate = gathering.skill_modifier * time.delta_secs();
+        let efficiency = if homeless.is_some() {
+            HOMELESS_EFFICIENCY
+        } else {
+            1.0
+        };
+        let progress_rate = gathering.skill_modifier * efficiency * time.delta_secs();
         gathering.progress += progress_rate;
 
         if gathering.progress >= gathering.base_time {
-            let resource_type = gathering.resource_type;
+            let item = gathering.item.clone();
 
-            let skill_value = match resource_type {
-                ResourceType::Wood => skills.woodcutting,
-                ResourceType::Gold => skills.mining,
-                ResourceType::Stone => skills.harvesting,
-            };
+            let action = SkillAction::Gather(item.clone());
+            let skill = action.skill();
+            let skill_value = skills.get(skill);
 
             let base_yield = 1;
             let bonus_yield = (skill_value / 3.0).floor() as u32;
-            let total_yield = base_yield + bonus_yield;
+            let mut total_yield = base_yield + bonus_yield;
+
+            // Perks double a share of yields, spread out evenly rather than rolled
+            gathering.double_yield +=
+                skill_registry.perk_bonus(progression, skill, PerkEffect::DoubleYield);
+            if gathering.double_yield >= 1.0 {
+                gathering.double_yield -= 1.0;
+                total_yield *= 2;
+            }
 
-            let overflow =
-                inventory.add_resource(resource_type, total_yield, settings.max_stack_size);
+            let overflow = inventory.add_item(&item, total_yield, &items);
+            stats.record_gathered(owner.copied().unwrap_or_default(), &item, total_yield);
 
-            if let Ok(mut progression) = skill_progression.get_mut(entity) {
-                match resource_type {
-                    ResourceType::Wood => progression.woodcutting_xp += 5.0,
-                    ResourceType::Gold => progression.mining_xp += 5.0,
-                    ResourceType::Stone => progression.harvesting_xp += 5.0,
-                }
-            }
+            xp_events.send(SkillXpGained { entity, action });
 
             if overflow == 0 {
                 gathering.progress = 0.0;
-                info!("Gathered {} {:?}", total_yield, resource_type);
+                info!("Gathered {} {}", total_yield, item);
             } else {
-                info!("Inventory full, stopping gathering");
-                commands.entity(entity).remove::<Gathering>();
+                // What doesn't fit is left on the ground
+                drop_events.send(DropItems {
+                    coords: *coords,
+                    item: item.clone(),
+                    quantity: overflow,
+                });
+                info!(
+                    "Inventory full, dropped {} {} and heading to storage",
+                    overflow, item
+                );
+                commands
+                    .entity(entity)
+                    .remove::<Gathering>()
+                    .insert(DropOff::new(gathering.target, item));
             }
         }
     }
 }
 
-/// This system adds a GatheringIntent component and sets up movement to the resource
-fn start_gathering(
+/// Sends workers off to gather, any other order stops them gathering
+fn execute_gather_commands(
     mut commands: Commands,
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
-    selected_characters: Query<(Entity, &Skills, &GridCoords, Option<&Gathering>), With<Selected>>,
-    mut move_targets: Query<&mut MoveTarget>,
-    resource_nodes: Query<(
-        Entity,
-        &GlobalTransform,
-        &Sprite,
-        Option<&Forest>,
-        Option<&Mine>,
-        Option<&Quarry>,
-    )>,
-    gathering_intent_query: Query<&GatheringIntent>,
+    mut game_commands: EventReader<GameCommand>,
+    mut workers: Query<(&GridCoords, &mut MoveTarget, Option<&GatheringIntent>), With<Worker>>,
+    gatherers: Query<(), Or<(With<Gathering>, With<GatheringIntent>)>>,
+    resources: Query<(&GridCoords, Has<Forest>, Has<Mine>, Has<Quarry>)>,
     obstacles: Query<&GridCoords, With<crate::components::movement::Collider>>,
-    ldtk_worlds: Query<&GlobalTransform, With<LdtkProjectHandle>>,
-    ldtk_tile_query: Query<&GridCoords, With<crate::components::movement::Collider>>,
 ) {
-    if !mouse_button.just_pressed(MouseButton::Right) {
-        return;
-    }
-
-    let window = windows.single();
-    let Some(cursor_position) = window.cursor_position() else {
-        return;
-    };
-
-    let (camera, camera_transform) = camera_q.single();
-    let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
-    };
-
-    let cursor_pos = cursor_ray.origin.truncate();
-
-    info!(
-        "<start_gathering> Resource gathering cursor position: {:?}",
-        cursor_pos
-    );
+    for command in game_commands.read() {
+        let GameCommand::Gather { unit, resource } = command else {
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| gatherers.contains(*unit))
+            {
+                info!("<execute_gather_commands> {:?} stops gathering", unit);
+                commands
+                    .entity(unit)
+                    .remove::<(Gathering, GatheringIntent)>();
+            }
+            continue;
+        };
 
-    let Some((character_entity, _skills, character_coords, is_gathering)) =
-        selected_characters.iter().next()
-    else {
-        return;
-    };
+        let Ok((unit_coords, mut move_target, intent)) = workers.get_mut(*unit) else {
+            continue;
+        };
 
-    let mut found_resource = false;
+        let Ok((resource_coords, is_tree, is_mine, is_quarry)) = resources.get(*resource) else {
+            continue;
+        };
+        let Some(item) = resource_item(is_tree, is_mine, is_quarry) else {
+            info!(
+                "<execute_gather_commands> {:?} isn't a resource, ignoring",
+                resource
+            );
+            continue;
+        };
 
-    for (node_entity, transform, sprite, is_tree, is_mine, is_quarry) in &resource_nodes {
-        let size = sprite.custom_size.unwrap_or(Vec2::new(64.0, 64.0));
-        let pos = transform.translation().truncate();
+        // Already on the way there
+        if intent.is_some_and(|intent| intent.target == *resource) {
+            continue;
+        }
 
-        let min_x = pos.x - size.x / 2.0;
-        let max_x = pos.x + size.x / 2.0;
-        let min_y = pos.y - size.y / 2.0;
-        let max_y = pos.y + size.y / 2.0;
+        if grid_distance(unit_coords, resource_coords) > 1 {
+            let mut approach_positions = find_adjacent_positions(*resource_coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - unit_coords.x).pow(2) + (pos.y - unit_coords.y).pow(2));
 
-        if cursor_pos.x >= min_x
-            && cursor_pos.x <= max_x
-            && cursor_pos.y >= min_y
-            && cursor_pos.y <= max_y
-        {
-            if is_tree.is_none() && is_mine.is_none() && is_quarry.is_none() {
+            let Some(dest) = approach_positions.first() else {
                 info!(
-                    "<start_gathering>  Clicked on a sprite that isn't a valid resource, ignoring"
+                    "<execute_gather_commands> No valid adjacent positions found for resource at {:?}",
+                    resource_coords
                 );
                 continue;
-            }
-
-            // Only set found_resource to true AFTER confirming it's a valid resource
-            found_resource = true;
-
-            let (resource_type, resource_name) = if is_tree.is_some() {
-                (ResourceType::Wood, "wood from tree")
-            } else if is_mine.is_some() {
-                (ResourceType::Gold, "gold from mine")
-            } else if is_quarry.is_some() {
-                (ResourceType::Stone, "stone from quarry")
-            } else {
-                continue;
             };
-
-            if let Ok(gathering_intent) = gathering_intent_query.get(character_entity) {
-                if gathering_intent.target == node_entity {
-                    return;
-                }
-            }
-
-            // If character is currently gathering, stop it
-            if is_gathering.is_some() {
-                info!("<start_gathering> Interrupting current gathering to gather a different resource");
-                commands.entity(character_entity).remove::<Gathering>();
-            }
-
-            commands.entity(character_entity).insert(GatheringIntent {
-                target: node_entity,
-                resource_type,
-            });
-
-            commands.entity(character_entity).remove::<Gathering>();
-
-            if let Ok(mut move_target) = move_targets.get_mut(character_entity) {
-                let world_to_grid_pos = pos;
-
-                let raw_grid_x = (world_to_grid_pos.x / 64.0).floor() as i32;
-                let raw_grid_y = (world_to_grid_pos.y / 64.0).floor() as i32;
-
-                let resource_grid = GridCoords {
-                    x: raw_grid_x,
-                    y: raw_grid_y,
-                };
-
-                let character_grid = character_coords;
-
-                info!("<start_gathering> Character position: {:?}", character_grid);
-                info!("<start_gathering> Resource position: {:?}", resource_grid);
-
-                let adjacent_positions = find_adjacent_positions(resource_grid, &obstacles);
-
-                if adjacent_positions.is_empty() {
-                    info!(
-                        "<start_gathering> No valid adjacent positions found for resource at {:?}",
-                        resource_grid
-                    );
-                    commands
-                        .entity(character_entity)
-                        .remove::<GatheringIntent>();
-                    return;
-                }
-
-                let mut sorted_positions = adjacent_positions.clone();
-                sorted_positions.sort_by(|a, b| {
-                    let dist_a =
-                        ((a.x - character_grid.x).pow(2) + (a.y - character_grid.y).pow(2)) as f32;
-                    let dist_b =
-                        ((b.x - character_grid.x).pow(2) + (b.y - character_grid.y).pow(2)) as f32;
-                    dist_a.partial_cmp(&dist_b).unwrap()
-                });
-
-                info!(
-                    "<start_gathering> Found {} possible approach positions for resource at {:?}",
-                    sorted_positions.len(),
-                    resource_grid
-                );
-
-                if let Some(dest) = sorted_positions.first() {
-                    move_target.destination = Some(*dest);
-                    move_target.path.clear();
-
-                    info!(
-                        "<start_gathering> Trying movement destination to {:?} to approach resource at {:?}",
-                        dest, resource_grid
-                    );
-
-                    info!("<start_gathering> Moving to gather {}", resource_name);
-                }
-            }
-
-            break;
-        }
-    }
-
-    if !found_resource {
-        // If clicking on an empty area, interrupt gathering if in progress
-        if is_gathering.is_some() {
-            info!("<start_gathering> Interrupting gathering to move elsewhere");
-            commands.entity(character_entity).remove::<Gathering>();
-
-            // Use the helper functions from movement.rs to set the new destination
-            if let Some(target_grid) = crate::systems::movement::calculate_cursor_grid_position(
-                cursor_position,
-                &camera_q,
-                &ldtk_worlds,
-            ) {
-                crate::systems::movement::set_movement_target(
-                    character_entity,
-                    target_grid,
-                    character_coords,
-                    &ldtk_tile_query,
-                    &mut move_targets,
-                );
-            }
-        }
-
-        // Also remove gathering intent if it exists
-        if gathering_intent_query.contains(character_entity) {
-            commands
-                .entity(character_entity)
-                .remove::<GatheringIntent>();
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
         }
 
+        commands
+            .entity(*unit)
+            .remove::<Gathering>()
+            .insert(GatheringIntent {
+                target: *resource,
+                item: item.clone(),
+            });
         info!(
-            "<start_gathering> No resource found at click position, deferring to movement system"
+            "<execute_gather_commands> {:?} is off to gather {} from {:?}",
+            unit, item, resource
         );
     }
 }
 
 /// Helper function to find adjacent positions for resource gathering
-fn find_adjacent_positions(
+pub fn find_adjacent_positions(
     resource_pos: GridCoords,
     obstacles: &Query<&GridCoords, With<crate::components::movement::Collider>>,
 ) -> Vec<GridCoords> {
@@ -361,6 +293,7 @@ fn check_gathering_proximity(
         Option<&Mine>,
         Option<&Quarry>,
     )>,
+    items: Res<ItemRegistry>,
 ) {
     const GATHERING_RANGE_GRID: f32 = 1.5;
     const GATHERING_RANGE_WORLD: f32 = 300.0;
@@ -375,22 +308,22 @@ fn check_gathering_proximity(
                 continue;
             }
 
-            let actual_resource_type = if is_tree.is_some() {
-                ResourceType::Wood
+            let actual_item = if is_tree.is_some() {
+                ItemId::WOOD
             } else if is_mine.is_some() {
-                ResourceType::Gold
+                ItemId::GOLD
             } else if is_quarry.is_some() {
-                ResourceType::Stone
+                ItemId::STONE
             } else {
                 info!("<check_gathering_proximity> Resource doesn't match any known type, removing gathering intent");
                 commands.entity(entity).remove::<GatheringIntent>();
                 continue;
             };
 
-            if actual_resource_type != intent.resource_type {
+            if actual_item != intent.item {
                 info!(
-                    "<check_gathering_proximity> Resource type mismatch: expected {:?}, found {:?}",
-                    intent.resource_type, actual_resource_type
+                    "<check_gathering_proximity> Resource type mismatch: expected {}, found {}",
+                    intent.item, actual_item
                 );
                 commands.entity(entity).remove::<GatheringIntent>();
                 continue;
@@ -425,27 +358,20 @@ fn check_gathering_proximity(
 
             // Allow gathering if adjacent (Chebyshev distance = 1) or within range
             if chebyshev_distance <= 1 || grid_distance <= GATHERING_RANGE_GRID {
-                let skill_value = match intent.resource_type {
-                    ResourceType::Wood => skills.woodcutting,
-                    ResourceType::Gold => skills.mining,
-                    ResourceType::Stone => skills.harvesting,
-                };
+                let skill_value = skills.get(SkillAction::Gather(intent.item.clone()).skill());
 
                 commands.entity(entity).insert(Gathering {
-                    resource_type: intent.resource_type,
+                    item: intent.item.clone(),
                     progress: 0.0,
                     target: intent.target,
                     base_time: 3.0,
                     skill_modifier: skill_value,
+                    double_yield: 0.0,
                 });
 
                 commands.entity(entity).remove::<GatheringIntent>();
 
-                let resource_name = match intent.resource_type {
-                    ResourceType::Gold => "Gold",
-                    ResourceType::Wood => "Wood",
-                    ResourceType::Stone => "Stone",
-                };
+                let resource_name = items.name(&intent.item);
 
                 info!("<check_gathering_proximity> Started gathering {} (Grid dist: {:.1}, World dist: {:.1})",
                       resource_name, grid_distance, world_distance);
@@ -457,66 +383,20 @@ fn check_gathering_proximity(
     }
 }
 
-/// This system updates the skills of characters based on their activities
-fn update_skills_from_activities(
-    mut characters: Query<(&mut Skills, &mut SkillProgression)>,
-    gatherers: Query<(Entity, &Gathering)>,
-    time: Res<Time>,
-) {
-    for (entity, gathering) in &gatherers {
-        if let Ok((mut skills, mut progression)) = characters.get_mut(entity) {
-            match gathering.resource_type {
-                ResourceType::Wood => {
-                    progression.woodcutting_xp += time.delta_secs() * 0.2;
-                    if progression.woodcutting_xp >= 100.0 * skills.woodcutting {
-                        progression.woodcutting_xp = 0.0;
-                        skills.woodcutting += 0.1;
-                        info!(
-                            "Character {:?} improved woodcutting to {:.1}",
-                            entity, skills.woodcutting
-                        );
-                    }
-                }
-                ResourceType::Gold => {
-                    progression.mining_xp += time.delta_secs() * 0.2;
-                    if progression.mining_xp >= 100.0 * skills.mining {
-                        progression.mining_xp = 0.0;
-                        skills.mining += 0.1;
-                        info!(
-                            "Character {:?} improved mining to {:.1}",
-                            entity, skills.mining
-                        );
-                    }
-                }
-                ResourceType::Stone => {
-                    progression.harvesting_xp += time.delta_secs() * 0.2;
-                    if progression.harvesting_xp >= 100.0 * skills.harvesting {
-                        progression.harvesting_xp = 0.0;
-                        skills.harvesting += 0.1;
-                        info!(
-                            "Character {:?} improved harvesting to {:.1}",
-                            entity, skills.harvesting
-                        );
-                    }
-                }
-            }
-        }
-    }
-}
-
-/// This system updates the character info UI with skills and inventory
+/// This system updates the character info UI, skills and the inventory grid are added by their own plugins
 fn update_character_info_ui(
-    selected_entities: Query<(Entity, &Skills, Option<&Inventory>), With<Selected>>,
+    selected_entities: Query<Entity, (With<Selected>, With<Skills>)>,
     panel_query: Query<Entity, With<EntityInfoPanel>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     gathering_query: Query<&Gathering>,
     gathering_intent_query: Query<&GatheringIntent>,
+    items: Res<ItemRegistry>,
 ) {
     if let Ok(panel_entity) = panel_query.get_single() {
         commands.entity(panel_entity).despawn_descendants();
 
-        if let Ok((entity, skills, inventory)) = selected_entities.get_single() {
+        if let Ok(entity) = selected_entities.get_single() {
             commands.entity(panel_entity).with_children(|parent| {
                 parent.spawn((
                     Text::new("Character Info"),
@@ -528,53 +408,9 @@ fn update_character_info_ui(
                     TextColor(Color::WHITE),
                 ));
 
-                parent.spawn((
-                    Text::new("Skills:"),
-                    TextFont {
-                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                        font_size: 16.0,
-                        ..default()
-                    },
-                    TextColor(Color::WHITE),
-                ));
-
-                parent.spawn((
-                    Text::new(format!("Mining: {:.1}", skills.mining)),
-                    TextFont {
-                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                        font_size: 14.0,
-                        ..default()
-                    },
-                    TextColor(Color::WHITE),
-                ));
-
-                parent.spawn((
-                    Text::new(format!("Woodcutting: {:.1}", skills.woodcutting)),
-                    TextFont {
-                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                        font_size: 14.0,
-                        ..default()
-                    },
-                    TextColor(Color::WHITE),
-                ));
-
-                parent.spawn((
-                    Text::new(format!("Harvesting: {:.1}", skills.harvesting)),
-                    TextFont {
-                        font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                        font_size: 14.0,
-                        ..default()
-                    },
-                    TextColor(Color::WHITE),
-                ));
-
                 if let Ok(gathering) = gathering_query.get(entity) {
                     let progress_percent = (gathering.progress / gathering.base_time) * 100.0;
-                    let resource_name = match gathering.resource_type {
-                        ResourceType::Gold => "Gold",
-                        ResourceType::Wood => "Wood",
-                        ResourceType::Stone => "Stone",
-                    };
+                    let resource_name = items.name(&gathering.item);
 
                     parent.spawn((
                         Text::new(format!(
@@ -589,11 +425,7 @@ fn update_character_info_ui(
                         TextColor(Color::srgb(0.0, 1.0, 0.0)),
                     ));
                 } else if let Ok(intent) = gathering_intent_query.get(entity) {
-                    let resource_name = match intent.resource_type {
-                        ResourceType::Gold => "Gold",
-                        ResourceType::Wood => "Wood",
-                        ResourceType::Stone => "Stone",
-                    };
+                    let resource_name = items.name(&intent.item);
 
                     parent.spawn((
                         Text::new(format!("Moving to gather {}", resource_name)),
@@ -605,118 +437,7 @@ fn update_character_info_ui(
                         TextColor(Color::srgb(1.0, 1.0, 0.0)),
                     ));
                 }
-
-                if let Some(inv) = inventory {
-                    let used_slots = inv.slots.iter().filter(|slot| slot.is_some()).count();
-                    let total_slots = inv.max_slots;
-
-                    parent.spawn((
-                        Text::new(format!("Inventory ({}/{})", used_slots, total_slots)),
-                        TextFont {
-                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                            font_size: 16.0,
-                            ..default()
-                        },
-                        TextColor(Color::WHITE),
-                    ));
-
-                    for (i, slot) in inv.slots.iter().enumerate() {
-                        if let Some(inv_slot) = slot {
-                            let resource_name = match inv_slot.resource_type {
-                                ResourceType::Gold => "Gold",
-                                ResourceType::Wood => "Wood",
-                                ResourceType::Stone => "Stone",
-                            };
-
-                            let resource_icon = match inv_slot.resource_type {
-                                ResourceType::Gold => "🪙",
-                                ResourceType::Wood => "🪵",
-                                ResourceType::Stone => "🪨",
-                            };
-
-                            parent.spawn((
-                                Text::new(format!(
-                                    "{} {} x{}",
-                                    resource_icon, resource_name, inv_slot.quantity
-                                )),
-                                TextFont {
-                                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                                    font_size: 14.0,
-                                    ..default()
-                                },
-                                TextColor(Color::WHITE),
-                            ));
-                        } else {
-                            parent.spawn((
-                                Text::new(format!("Slot {}: Empty", i + 1)),
-                                TextFont {
-                                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                                    font_size: 14.0,
-                                    ..default()
-                                },
-                                TextColor(Color::srgba(0.7, 0.7, 0.7, 1.0)),
-                            ));
-                        }
-                    }
-                } else {
-                    parent.spawn((
-                        Text::new("No inventory available"),
-                        TextFont {
-                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
-                            font_size: 14.0,
-                            ..default()
-                        },
-                        TextColor(Color::srgba(0.9, 0.3, 0.3, 1.0)),
-                    ));
-                }
             });
         }
     }
 }
-
-/// This system handles the transfer of resources between characters
-fn handle_resource_transfer(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    mut selected_entity: Query<(Entity, &mut Inventory, &InventorySettings), With<Selected>>,
-    mut entities_with_inventory: Query<
-        (Entity, &mut Inventory, &InventorySettings, &GlobalTransform),
-        Without<Selected>,
-    >,
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
-) {
-    if keyboard.pressed(KeyCode::KeyT) && mouse_button.just_pressed(MouseButton::Right) {
-        if let Ok((_selected_entity, mut selected_inventory, _selected_settings)) =
-            selected_entity.get_single_mut()
-        {
-            let window = windows.single();
-            if let Some(cursor_position) = window.cursor_position() {
-                let (camera, camera_transform) = camera_q.single();
-                if let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, cursor_position)
-                {
-                    let cursor_pos = cursor_ray.origin.truncate();
-                    for (entity, mut inventory, settings, transform) in &mut entities_with_inventory
-                    {
-                        let entity_pos = transform.translation().truncate();
-                        let distance = cursor_pos.distance(entity_pos);
-                        if distance < 100.0
-                            && selected_inventory.count_resource(ResourceType::Wood) > 0
-                        {
-                            let amount = selected_inventory.transfer_to(
-                                &mut inventory,
-                                ResourceType::Wood,
-                                1,
-                                settings.max_stack_size,
-                            );
-                            info!("Transferred {} Wood to entity {:?}", amount, entity);
-                            if amount > 0 {
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
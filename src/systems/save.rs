@@ -0,0 +1,1398 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::components::combat::Health;
+use crate::components::entities::{Character, Warrior};
+use crate::components::inventory::{Inventory, StorageFilter};
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::movement::MoveTarget;
+use crate::components::resources::ResourceNode;
+use crate::components::skills::{SkillProgression, Skills};
+use crate::components::unit::{Owner, SelectionRing, UnitType};
+use crate::components::wildlife::Creature;
+use crate::systems::camera::CameraPanState;
+use crate::systems::construction::{
+    finish_building, spawn_construction_site, Building, BuildingType, Constructing,
+    ConstructionSite,
+};
+use crate::systems::crafting::CraftingStation;
+use crate::systems::garrison::{shelter_unit, Garrison};
+use crate::systems::housing::{Home, Homeless, Housing};
+use crate::systems::item_piles::{spawn_pile, ItemPile};
+use crate::systems::jobs::JobBoard;
+use crate::systems::movement::grid_to_translation;
+use crate::systems::multiplayer::Lockstep;
+use crate::systems::production::{spawn_unit, ProductionQueue};
+use crate::systems::resource_gathering::{Gathering, GatheringIntent};
+use crate::systems::scene::{find_entity_layer, MAP_PATH};
+use crate::systems::simulation::{track_level, SimTick};
+use crate::systems::victory::{MatchState, MatchStats};
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy::utils::SystemTime;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Plugin for saving the game and loading it back on top of the LDtk level.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BaseLevel>()
+            .init_resource::<SaveSettings>()
+            .init_resource::<SaveDir>()
+            .init_resource::<PlayTime>()
+            .init_resource::<AutosaveTimer>()
+            .add_event::<SaveNotice>()
+            .add_systems(Update, record_level_entities)
+            .add_systems(Update, track_play_time)
+            .add_systems(Update, quicksave.in_set(ClientSet))
+            // Loading on one side only would split a multiplayer game in two
+            .add_systems(
+                Update,
+                quickload
+                    .run_if(not(resource_exists::<Lockstep>))
+                    .in_set(ClientSet),
+            )
+            .add_systems(Update, autosave.in_set(ClientSet))
+            .add_systems(Update, apply_pending_load.after(track_level));
+    }
+}
+
+/// Bumped whenever saves from an older version can no longer be read.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Slot used by the quicksave and quickload keys.
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+
+/// Autosaves go to slots named this plus a number.
+const AUTOSAVE_PREFIX: &str = "autosave-";
+
+/// Where saves are kept, `saves/` next to the game unless told otherwise.
+#[derive(Resource, Debug, Clone)]
+pub struct SaveDir(pub PathBuf);
+
+impl Default for SaveDir {
+    fn default() -> Self {
+        Self(PathBuf::from("saves"))
+    }
+}
+
+/// How often to autosave and how many autosaves to keep.
+#[derive(Resource, Debug, Clone)]
+pub struct SaveSettings {
+    // Seconds of play between autosaves, 0 turns autosaving off
+    pub autosave_interval: f32,
+    pub autosave_slots: usize,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            autosave_interval: 300.0,
+            autosave_slots: 3,
+        }
+    }
+}
+
+/// Seconds played on this game, carried over by saves.
+#[derive(Resource, Debug, Default)]
+pub struct PlayTime(pub f32);
+
+/// Seconds since the last autosave.
+#[derive(Resource, Debug, Default)]
+struct AutosaveTimer(f32);
+
+/// Sent when saving or loading succeeds or fails, so the player can be told.
+#[derive(Event, Debug, Clone)]
+pub struct SaveNotice {
+    pub message: String,
+    pub is_error: bool,
+}
+
+impl SaveNotice {
+    pub fn info(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            is_error: false,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            is_error: true,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write save: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not read save: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("save is from version {found}, this game reads version {expected}")]
+    Version { found: u32, expected: u32 },
+    #[error("there is no save called {0}")]
+    Missing(String),
+    #[cfg(target_arch = "wasm32")]
+    #[error("local storage is not available")]
+    Storage,
+    #[error("no level is loaded")]
+    NoLevel,
+}
+
+/// Refers to an entity across a save, since `Entity` ids don't survive a reload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SaveId {
+    /// Placed in the LDtk level, by its iid
+    Placed(String),
+    /// Spawned while playing, numbered within the save
+    Spawned(u32),
+    /// A resource cell of the level's int grid, like a forest, by its coordinates
+    Cell(i32, i32),
+}
+
+/// What to spawn for an entity that isn't part of the level.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SpawnedKind {
+    Unit(UnitType),
+    ConstructionSite(BuildingType),
+    Building(BuildingType),
+    ItemPile,
+}
+
+/// Saved `Gathering`, `GatheringIntent` or `Constructing`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SavedActivity {
+    Gathering {
+        item: ItemId,
+        progress: f32,
+        target: SaveId,
+        base_time: f32,
+        skill_modifier: f32,
+        double_yield: f32,
+    },
+    GatheringIntent {
+        item: ItemId,
+        target: SaveId,
+    },
+    Constructing {
+        building_type: BuildingType,
+        progress: f32,
+        required_time: f32,
+        site: SaveId,
+    },
+}
+
+/// Saved `ProductionQueue`, with the rally point as a plain cell.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedProduction {
+    pub queue: Vec<UnitType>,
+    pub progress: f32,
+    pub rally_point: Option<(i32, i32)>,
+}
+
+/// Saved `MoveTarget`, as plain cells.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedMoveTarget {
+    pub destination: Option<(i32, i32)>,
+    pub path: Vec<(i32, i32)>,
+}
+
+/// State of one entity. For level entities these replace what the level spawned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub id: SaveId,
+    // Only set for entities spawned while playing
+    #[serde(default)]
+    pub kind: Option<SpawnedKind>,
+    #[serde(default)]
+    pub owner: Option<u8>,
+    pub coords: (i32, i32),
+    #[serde(default)]
+    pub health: Option<Health>,
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+    #[serde(default)]
+    pub filter: Option<StorageFilter>,
+    #[serde(default)]
+    pub skills: Option<Skills>,
+    #[serde(default)]
+    pub progression: Option<SkillProgression>,
+    #[serde(default)]
+    pub activity: Option<SavedActivity>,
+    #[serde(default)]
+    pub move_target: Option<SavedMoveTarget>,
+    #[serde(default)]
+    pub homeless: bool,
+    // Who lives in a house and who is inside a building, in order
+    #[serde(default)]
+    pub residents: Option<Vec<SaveId>>,
+    #[serde(default)]
+    pub occupants: Option<Vec<SaveId>>,
+    #[serde(default)]
+    pub production: Option<SavedProduction>,
+    #[serde(default)]
+    pub crafting: Option<CraftingStation>,
+    // The spot a creature wanders around, which isn't where it stands once it has moved
+    #[serde(default)]
+    pub creature_home: Option<(i32, i32)>,
+}
+
+/// Where the camera was looking.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+}
+
+/// Contents of a save. The LDtk level is the base, `removed` lists level
+/// entities that are gone and `entities` holds everything that may have changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    // Seconds since the Unix epoch
+    #[serde(default)]
+    pub saved_at: u64,
+    #[serde(default)]
+    pub map: String,
+    #[serde(default)]
+    pub play_time: f32,
+    pub camera: SavedCamera,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    pub entities: Vec<SavedEntity>,
+    // Ticks run so far, which defeats, results and timed wins are counted in
+    #[serde(default)]
+    pub tick: u64,
+    #[serde(default)]
+    pub match_state: MatchState,
+    #[serde(default)]
+    pub match_stats: MatchStats,
+}
+
+/// Just the version, read first so old saves fail with a clear error.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// What the load menu shows about a save, without reading the whole thing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SaveInfo {
+    #[serde(default)]
+    pub saved_at: u64,
+    #[serde(default)]
+    pub map: String,
+    #[serde(default)]
+    pub play_time: f32,
+}
+
+/// A save slot and what could be read from it.
+#[derive(Debug)]
+pub struct SaveSummary {
+    pub slot: String,
+    pub info: Result<SaveInfo, SaveError>,
+}
+
+impl SaveGame {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, SaveError> {
+        check_version(contents)?;
+        Ok(ron::from_str(contents)?)
+    }
+}
+
+/// Checks the version before trusting anything else in a save
+fn check_version(contents: &str) -> Result<(), SaveError> {
+    let header: SaveHeader = ron::from_str(contents)?;
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::Version {
+            found: header.version,
+            expected: SAVE_VERSION,
+        });
+    }
+    Ok(())
+}
+
+/// Saves are files in the save directory natively.
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use super::SaveError;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    fn path(dir: &Path, slot: &str) -> PathBuf {
+        dir.join(format!("{}.ron", slot))
+    }
+
+    pub fn write(dir: &Path, slot: &str, contents: &str) -> Result<(), SaveError> {
+        fs::create_dir_all(dir)?;
+        fs::write(path(dir, slot), contents)?;
+        Ok(())
+    }
+
+    pub fn read(dir: &Path, slot: &str) -> Result<String, SaveError> {
+        fs::read_to_string(path(dir, slot)).map_err(|error| match error.kind() {
+            ErrorKind::NotFound => SaveError::Missing(slot.to_string()),
+            _ => SaveError::Io(error),
+        })
+    }
+
+    pub fn list(dir: &Path) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect()
+    }
+}
+
+/// Saves are kept in the browser's local storage on the web, under keys named
+/// after the save directory.
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use super::SaveError;
+    use std::path::Path;
+
+    const KEY_PREFIX: &str = "my-rts-game/";
+
+    fn prefix(dir: &Path) -> String {
+        format!("{}{}/", KEY_PREFIX, dir.display())
+    }
+
+    fn storage() -> Result<web_sys::Storage, SaveError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or(SaveError::Storage)
+    }
+
+    pub fn write(dir: &Path, slot: &str, contents: &str) -> Result<(), SaveError> {
+        storage()?
+            .set_item(&format!("{}{}", prefix(dir), slot), contents)
+            .map_err(|_| SaveError::Storage)
+    }
+
+    pub fn read(dir: &Path, slot: &str) -> Result<String, SaveError> {
+        storage()?
+            .get_item(&format!("{}{}", prefix(dir), slot))
+            .map_err(|_| SaveError::Storage)?
+            .ok_or_else(|| SaveError::Missing(slot.to_string()))
+    }
+
+    pub fn list(dir: &Path) -> Vec<String> {
+        let Ok(storage) = storage() else {
+            return Vec::new();
+        };
+        let length = storage.length().unwrap_or_default();
+        let prefix = prefix(dir);
+
+        (0..length)
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| Some(key.strip_prefix(&prefix)?.to_string()))
+            .collect()
+    }
+}
+
+pub fn write_save(dir: &SaveDir, slot: &str, save: &SaveGame) -> Result<(), SaveError> {
+    backend::write(&dir.0, slot, &save.to_ron()?)
+}
+
+pub fn read_save(dir: &SaveDir, slot: &str) -> Result<SaveGame, SaveError> {
+    SaveGame::from_ron(&backend::read(&dir.0, slot)?)
+}
+
+pub fn read_save_info(dir: &SaveDir, slot: &str) -> Result<SaveInfo, SaveError> {
+    let contents = backend::read(&dir.0, slot)?;
+    check_version(&contents)?;
+    Ok(ron::from_str(&contents)?)
+}
+
+/// Every save there is, newest first, with unreadable ones at the end
+pub fn list_saves(dir: &SaveDir) -> Vec<SaveSummary> {
+    let mut saves: Vec<SaveSummary> = backend::list(&dir.0)
+        .into_iter()
+        .map(|slot| SaveSummary {
+            info: read_save_info(dir, &slot),
+            slot,
+        })
+        .collect();
+
+    saves.sort_by_key(|save| {
+        std::cmp::Reverse(
+            save.info
+                .as_ref()
+                .map(|info| info.saved_at)
+                .unwrap_or_default(),
+        )
+    });
+    saves
+}
+
+/// Seconds since the Unix epoch, 0 if the clock is before it
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Name of the map without its extension
+pub fn map_name() -> &'static str {
+    MAP_PATH.trim_end_matches(".ldtk")
+}
+
+/// The autosave slot to write next: an unused one, or else the oldest
+fn next_autosave_slot(dir: &SaveDir, slots: usize) -> String {
+    (1..=slots.max(1))
+        .map(|number| format!("{}{}", AUTOSAVE_PREFIX, number))
+        .min_by_key(|slot| match read_save_info(dir, slot) {
+            Ok(info) => (1, info.saved_at),
+            // Missing or unreadable slots are the first to be reused
+            Err(_) => (0, 0),
+        })
+        .unwrap_or_else(|| format!("{}1", AUTOSAVE_PREFIX))
+}
+
+/// Iids of every entity the LDtk level spawned, to tell which ones are gone.
+#[derive(Resource, Debug, Default)]
+pub struct BaseLevel {
+    iids: HashSet<String>,
+}
+
+/// A save waiting for the level to respawn before it's applied.
+#[derive(Resource, Debug)]
+pub struct PendingLoad(pub SaveGame);
+
+/// Remembers level entities as the level spawns them
+fn record_level_entities(
+    mut base_level: ResMut<BaseLevel>,
+    entities: Query<&EntityIid, Added<EntityIid>>,
+) {
+    for iid in &entities {
+        base_level.iids.insert(iid.as_str().to_string());
+    }
+}
+
+/// Counts up play time while a level is loaded
+fn track_play_time(time: Res<Time>, base_level: Res<BaseLevel>, mut play_time: ResMut<PlayTime>) {
+    if !base_level.iids.is_empty() {
+        play_time.0 += time.delta_secs();
+    }
+}
+
+/// Writes the game to a slot and tells the player how it went
+fn save_to_slot(
+    snapshot: &SaveSnapshot,
+    dir: &SaveDir,
+    slot: &str,
+    notices: &mut EventWriter<SaveNotice>,
+) {
+    match snapshot
+        .capture()
+        .and_then(|save| write_save(dir, slot, &save))
+    {
+        Ok(()) => {
+            info!("<save_to_slot> Saved to {}", slot);
+            notices.send(SaveNotice::info(format!("Saved to {}", slot)));
+        }
+        Err(error) => {
+            warn!("<save_to_slot> Could not save to {}: {}", slot, error);
+            notices.send(SaveNotice::error(format!("Could not save: {}", error)));
+        }
+    }
+}
+
+/// Pressing F5 saves to the quicksave slot
+fn quicksave(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    snapshot: SaveSnapshot,
+    save_dir: Res<SaveDir>,
+    mut notices: EventWriter<SaveNotice>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        save_to_slot(&snapshot, &save_dir, QUICKSAVE_SLOT, &mut notices);
+    }
+}
+
+/// Pressing F9 loads the quicksave slot
+fn quickload(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    worlds: Query<Entity, With<LdtkProjectHandle>>,
+    save_dir: Res<SaveDir>,
+    mut notices: EventWriter<SaveNotice>,
+) {
+    if keyboard.just_pressed(KeyCode::F9) {
+        load_from_slot(
+            &mut commands,
+            &worlds,
+            &save_dir,
+            QUICKSAVE_SLOT,
+            &mut notices,
+        );
+    }
+}
+
+/// Saves to the next rotating autosave slot every so often, in the client only
+/// so headless runs don't fill the save folder
+fn autosave(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    mut timer: ResMut<AutosaveTimer>,
+    pending: Option<Res<PendingLoad>>,
+    snapshot: SaveSnapshot,
+    save_dir: Res<SaveDir>,
+    mut notices: EventWriter<SaveNotice>,
+) {
+    // Nothing worth saving before the level is in, or halfway through a load
+    if settings.autosave_interval <= 0.0 || snapshot.base_level.iids.is_empty() || pending.is_some()
+    {
+        return;
+    }
+
+    timer.0 += time.delta_secs();
+    if timer.0 < settings.autosave_interval {
+        return;
+    }
+    timer.0 = 0.0;
+
+    let slot = next_autosave_slot(&save_dir, settings.autosave_slots);
+    save_to_slot(&snapshot, &save_dir, &slot, &mut notices);
+}
+
+/// Starts loading a slot and tells the player if it can't be loaded
+pub fn load_from_slot(
+    commands: &mut Commands,
+    worlds: &Query<Entity, With<LdtkProjectHandle>>,
+    dir: &SaveDir,
+    slot: &str,
+    notices: &mut EventWriter<SaveNotice>,
+) {
+    if let Err(error) = start_load(commands, worlds, dir, slot) {
+        warn!("<load_from_slot> Could not load {}: {}", slot, error);
+        notices.send(SaveNotice::error(format!(
+            "Could not load {}: {}",
+            slot, error
+        )));
+    }
+}
+
+/// Reads a save and respawns the level, the save is applied once the level is back
+pub fn start_load(
+    commands: &mut Commands,
+    worlds: &Query<Entity, With<LdtkProjectHandle>>,
+    dir: &SaveDir,
+    slot: &str,
+) -> Result<(), SaveError> {
+    let world = worlds.get_single().map_err(|_| SaveError::NoLevel)?;
+    let save = read_save(dir, slot)?;
+
+    commands.entity(world).insert(Respawn);
+    commands.insert_resource(PendingLoad(save));
+    info!("<start_load> Loading {}", slot);
+    Ok(())
+}
+
+/// Everything needed to write a save.
+#[derive(bevy::ecs::system::SystemParam)]
+pub struct SaveSnapshot<'w, 's> {
+    base_level: Res<'w, BaseLevel>,
+    play_time: Res<'w, PlayTime>,
+    tick: Res<'w, SimTick>,
+    match_state: Res<'w, MatchState>,
+    match_stats: Res<'w, MatchStats>,
+    pan_state: Res<'w, CameraPanState>,
+    cameras: Query<'w, 's, &'static Transform, With<Camera>>,
+    entities: Query<
+        'w,
+        's,
+        (
+            Entity,
+            Option<&'static EntityIid>,
+            &'static GridCoords,
+            Option<&'static Owner>,
+            Option<&'static Health>,
+            Option<&'static Inventory>,
+            Option<&'static StorageFilter>,
+            Option<&'static Skills>,
+            Option<&'static SkillProgression>,
+            Option<&'static MoveTarget>,
+        ),
+    >,
+    kinds: Query<
+        'w,
+        's,
+        (
+            Has<Character>,
+            Has<Warrior>,
+            Option<&'static ConstructionSite>,
+            Option<&'static Building>,
+            Has<ItemPile>,
+        ),
+    >,
+    activities: Query<
+        'w,
+        's,
+        (
+            Option<&'static Gathering>,
+            Option<&'static GatheringIntent>,
+            Option<&'static Constructing>,
+        ),
+    >,
+    buildings: Query<
+        'w,
+        's,
+        (
+            Option<&'static Housing>,
+            Option<&'static Garrison>,
+            Option<&'static ProductionQueue>,
+            Option<&'static CraftingStation>,
+            Has<Homeless>,
+        ),
+    >,
+    cells: Query<'w, 's, &'static GridCoords, (With<ResourceNode>, Without<EntityIid>)>,
+    creatures: Query<'w, 's, &'static Creature>,
+    // Every level entity still around, including those without a cell
+    level_entities: Query<'w, 's, &'static EntityIid>,
+}
+
+impl SaveSnapshot<'_, '_> {
+    /// Works out what kind of thing an entity spawned while playing is
+    fn spawned_kind(&self, entity: Entity) -> Option<SpawnedKind> {
+        let (character, warrior, site, building, pile) = self.kinds.get(entity).ok()?;
+
+        if character {
+            Some(SpawnedKind::Unit(if warrior {
+                UnitType::Warrior
+            } else {
+                UnitType::Worker
+            }))
+        } else if let Some(site) = site {
+            Some(SpawnedKind::ConstructionSite(site.building_type))
+        } else if let Some(building) = building {
+            Some(SpawnedKind::Building(building.building_type))
+        } else if pile {
+            Some(SpawnedKind::ItemPile)
+        } else {
+            None
+        }
+    }
+
+    pub fn capture(&self) -> Result<SaveGame, SaveError> {
+        let camera_transform = self.cameras.get_single().map_err(|_| SaveError::NoLevel)?;
+        if self.base_level.iids.is_empty() {
+            return Err(SaveError::NoLevel);
+        }
+
+        // Give everything worth saving an id first, so references can be written
+        let mut ids: HashMap<Entity, (SaveId, Option<SpawnedKind>)> = HashMap::new();
+        let mut next_spawned = 0;
+        for (entity, iid, ..) in &self.entities {
+            if let Some(iid) = iid {
+                ids.insert(entity, (SaveId::Placed(iid.as_str().to_string()), None));
+            } else if let Some(kind) = self.spawned_kind(entity) {
+                ids.insert(entity, (SaveId::Spawned(next_spawned), Some(kind)));
+                next_spawned += 1;
+            }
+        }
+
+        let save_id = |entity: Entity| {
+            ids.get(&entity).map(|(id, _)| id.clone()).or_else(|| {
+                let coords = self.cells.get(entity).ok()?;
+                Some(SaveId::Cell(coords.x, coords.y))
+            })
+        };
+        let save_ids = |entities: &[Entity]| -> Vec<SaveId> {
+            entities
+                .iter()
+                .filter_map(|entity| save_id(*entity))
+                .collect()
+        };
+
+        let mut entities = Vec::new();
+        for (
+            entity,
+            _,
+            coords,
+            owner,
+            health,
+            inventory,
+            filter,
+            skills,
+            progression,
+            move_target,
+        ) in &self.entities
+        {
+            let Some((id, kind)) = ids.get(&entity) else {
+                continue;
+            };
+
+            let activity =
+                self.activities
+                    .get(entity)
+                    .ok()
+                    .and_then(|(gathering, intent, constructing)| {
+                        if let Some(gathering) = gathering {
+                            Some(SavedActivity::Gathering {
+                                item: gathering.item.clone(),
+                                progress: gathering.progress,
+                                target: save_id(gathering.target)?,
+                                base_time: gathering.base_time,
+                                skill_modifier: gathering.skill_modifier,
+                                double_yield: gathering.double_yield,
+                            })
+                        } else if let Some(intent) = intent {
+                            Some(SavedActivity::GatheringIntent {
+                                item: intent.item.clone(),
+                                target: save_id(intent.target)?,
+                            })
+                        } else {
+                            constructing.and_then(|constructing| {
+                                Some(SavedActivity::Constructing {
+                                    building_type: constructing.building_type,
+                                    progress: constructing.progress,
+                                    required_time: constructing.required_time,
+                                    site: save_id(constructing.site)?,
+                                })
+                            })
+                        }
+                    });
+
+            let (housing, garrison, production, crafting, homeless) = self
+                .buildings
+                .get(entity)
+                .unwrap_or((None, None, None, None, false));
+
+            entities.push(SavedEntity {
+                id: id.clone(),
+                kind: *kind,
+                owner: owner.map(|owner| owner.0),
+                coords: (coords.x, coords.y),
+                health: health.cloned(),
+                inventory: inventory.cloned(),
+                filter: filter.cloned(),
+                skills: skills.cloned(),
+                progression: progression.cloned(),
+                activity,
+                move_target: move_target.map(|move_target| SavedMoveTarget {
+                    destination: move_target.destination.map(|cell| (cell.x, cell.y)),
+                    path: move_target
+                        .path
+                        .iter()
+                        .map(|cell| (cell.x, cell.y))
+                        .collect(),
+                }),
+                homeless,
+                residents: housing.map(|housing| save_ids(&housing.residents)),
+                occupants: garrison.map(|garrison| save_ids(&garrison.occupants)),
+                production: production.map(|production| SavedProduction {
+                    queue: production.queue.clone(),
+                    progress: production.progress,
+                    rally_point: production.rally_point.map(|cell| (cell.x, cell.y)),
+                }),
+                crafting: crafting.cloned(),
+                creature_home: self
+                    .creatures
+                    .get(entity)
+                    .ok()
+                    .and_then(|creature| creature.home)
+                    .map(|home| (home.x, home.y)),
+            });
+        }
+
+        let present: HashSet<&str> = self.level_entities.iter().map(EntityIid::as_str).collect();
+        let mut removed: Vec<String> = self
+            .base_level
+            .iids
+            .iter()
+            .filter(|iid| !present.contains(iid.as_str()))
+            .cloned()
+            .collect();
+        removed.sort();
+
+        Ok(SaveGame {
+            version: SAVE_VERSION,
+            saved_at: now(),
+            map: map_name().to_string(),
+            play_time: self.play_time.0,
+            camera: SavedCamera {
+                x: camera_transform.translation.x,
+                y: camera_transform.translation.y,
+                zoom: self.pan_state.zoom_level(),
+            },
+            removed,
+            entities,
+            tick: self.tick.0,
+            match_state: self.match_state.clone(),
+            match_stats: self.match_stats.clone(),
+        })
+    }
+}
+
+/// How far the game had got, put back when a save is loaded.
+#[derive(bevy::ecs::system::SystemParam)]
+struct GameProgress<'w> {
+    play_time: ResMut<'w, PlayTime>,
+    tick: ResMut<'w, SimTick>,
+    match_state: ResMut<'w, MatchState>,
+    match_stats: ResMut<'w, MatchStats>,
+}
+
+/// Applies a pending save once the respawned level has finished spawning
+fn apply_pending_load(
+    mut commands: Commands,
+    mut level_events: EventReader<LevelEvent>,
+    pending: Option<Res<PendingLoad>>,
+    level_entities: Query<(Entity, &EntityIid)>,
+    cells: Query<(Entity, &GridCoords), (With<ResourceNode>, Without<EntityIid>)>,
+    mut transforms: Query<&mut Transform, Without<Camera>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+    pan_state: Option<ResMut<CameraPanState>>,
+    mut job_board: ResMut<JobBoard>,
+    mut progress: GameProgress,
+    mut autosave_timer: ResMut<AutosaveTimer>,
+    mut notices: EventWriter<SaveNotice>,
+    selection_rings: Query<Entity, With<SelectionRing>>,
+    layers: Query<(Entity, &LayerMetadata)>,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+) {
+    let transformed = level_events
+        .read()
+        .any(|event| matches!(event, LevelEvent::Transformed(_)));
+
+    let Some(pending) = pending else {
+        return;
+    };
+    if !transformed {
+        return;
+    }
+
+    commands.remove_resource::<PendingLoad>();
+    let save = &pending.0;
+
+    let Some(layer) = find_entity_layer(&layers) else {
+        warn!("<apply_pending_load> No entity layer to load into");
+        notices.send(SaveNotice::error(
+            "Could not load: the map has no entity layer",
+        ));
+        return;
+    };
+
+    let placed: HashMap<&str, Entity> = level_entities
+        .iter()
+        .map(|(entity, iid)| (iid.as_str(), entity))
+        .collect();
+    let cells: HashMap<(i32, i32), Entity> = cells
+        .iter()
+        .map(|(entity, coords)| ((coords.x, coords.y), entity))
+        .collect();
+
+    for iid in &save.removed {
+        if let Some(entity) = placed.get(iid.as_str()) {
+            commands.entity(*entity).despawn_recursive();
+        }
+    }
+
+    // Find or spawn every saved entity before filling them in, so references resolve
+    let mut ids: HashMap<&SaveId, Entity> = HashMap::new();
+    for saved in &save.entities {
+        let coords = GridCoords::new(saved.coords.0, saved.coords.1);
+        let owner = Owner(saved.owner.unwrap_or_default());
+
+        let entity = match (&saved.id, saved.kind) {
+            (SaveId::Placed(iid), _) => {
+                let Some(entity) = placed.get(iid.as_str()) else {
+                    info!(
+                        "<apply_pending_load> {} is no longer in the level, skipping it",
+                        iid
+                    );
+                    continue;
+                };
+
+                // Move level entities to where they were saved
+                if let Ok(mut transform) = transforms.get_mut(*entity) {
+                    transform.translation = grid_to_translation(coords, transform.translation.z);
+                }
+                *entity
+            }
+            (SaveId::Spawned(_), Some(SpawnedKind::Unit(unit_type))) => spawn_unit(
+                &mut commands,
+                &asset_server,
+                layer,
+                unit_type,
+                owner,
+                coords,
+            ),
+            (SaveId::Spawned(_), Some(SpawnedKind::ConstructionSite(building_type))) => {
+                spawn_construction_site(
+                    &mut commands,
+                    &asset_server,
+                    layer,
+                    building_type,
+                    owner,
+                    coords,
+                )
+            }
+            (SaveId::Spawned(_), Some(SpawnedKind::Building(building_type))) => {
+                let building = spawn_construction_site(
+                    &mut commands,
+                    &asset_server,
+                    layer,
+                    building_type,
+                    owner,
+                    coords,
+                );
+                commands.entity(building).insert(Sprite {
+                    image: asset_server.load(building_type.sprite_path()),
+                    custom_size: Some(Vec2::new(64.0, 64.0)),
+                    ..default()
+                });
+                finish_building(&mut commands, building, building_type);
+                building
+            }
+            (SaveId::Spawned(_), Some(SpawnedKind::ItemPile)) => spawn_pile(
+                &mut commands,
+                &asset_server,
+                layer,
+                coords,
+                Inventory::new(1),
+                &items,
+            ),
+            (SaveId::Spawned(id), None) => {
+                warn!(
+                    "<apply_pending_load> Spawned entity {} has no kind, skipping it",
+                    id
+                );
+                continue;
+            }
+            // Cells are only ever referred to, they come back with the level
+            (SaveId::Cell(x, y), _) => {
+                warn!(
+                    "<apply_pending_load> Cell ({}, {}) has saved state, skipping it",
+                    x, y
+                );
+                continue;
+            }
+        };
+
+        ids.insert(&saved.id, entity);
+    }
+
+    let resolve = |id: &SaveId| match id {
+        SaveId::Cell(x, y) => cells.get(&(*x, *y)).copied(),
+        _ => ids.get(id).copied(),
+    };
+    let resolve_all =
+        |saved: &[SaveId]| -> Vec<Entity> { saved.iter().filter_map(resolve).collect() };
+
+    for saved in &save.entities {
+        let Some(&entity) = ids.get(&saved.id) else {
+            continue;
+        };
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(GridCoords::new(saved.coords.0, saved.coords.1));
+
+        if let Some(owner) = saved.owner {
+            entity_commands.insert(Owner(owner));
+        }
+        if let Some(health) = &saved.health {
+            entity_commands.insert(health.clone());
+        }
+        if let Some(inventory) = &saved.inventory {
+            entity_commands.insert(inventory.clone());
+        }
+        if let Some(filter) = &saved.filter {
+            entity_commands.insert(filter.clone());
+        }
+        if let Some(skills) = &saved.skills {
+            entity_commands.insert(skills.clone());
+        }
+        if let Some(progression) = &saved.progression {
+            entity_commands.insert(progression.clone());
+        }
+        if let Some(move_target) = &saved.move_target {
+            entity_commands.insert(MoveTarget {
+                destination: move_target.destination.map(|(x, y)| GridCoords::new(x, y)),
+                path: move_target
+                    .path
+                    .iter()
+                    .map(|&(x, y)| GridCoords::new(x, y))
+                    .collect(),
+            });
+        }
+
+        match &saved.activity {
+            Some(SavedActivity::Gathering {
+                item,
+                progress,
+                target,
+                base_time,
+                skill_modifier,
+                double_yield,
+            }) => {
+                if let Some(target) = resolve(target) {
+                    entity_commands.insert(Gathering {
+                        item: item.clone(),
+                        progress: *progress,
+                        target,
+                        base_time: *base_time,
+                        skill_modifier: *skill_modifier,
+                        double_yield: *double_yield,
+                    });
+                }
+            }
+            Some(SavedActivity::GatheringIntent { item, target }) => {
+                if let Some(target) = resolve(target) {
+                    entity_commands.insert(GatheringIntent {
+                        target,
+                        item: item.clone(),
+                    });
+                }
+            }
+            Some(SavedActivity::Constructing {
+                building_type,
+                progress,
+                required_time,
+                site,
+            }) => {
+                if let Some(site) = resolve(site) {
+                    entity_commands.insert(Constructing {
+                        building_type: *building_type,
+                        progress: *progress,
+                        required_time: *required_time,
+                        site,
+                    });
+                }
+            }
+            None => {}
+        }
+
+        if saved.homeless {
+            entity_commands.insert(Homeless);
+        }
+        if let Some(production) = &saved.production {
+            entity_commands.insert(ProductionQueue {
+                queue: production.queue.clone(),
+                progress: production.progress,
+                rally_point: production.rally_point.map(|(x, y)| GridCoords::new(x, y)),
+            });
+        }
+        if let Some(crafting) = &saved.crafting {
+            entity_commands.insert(crafting.clone());
+        }
+        if let Some((x, y)) = saved.creature_home {
+            entity_commands.queue(move |mut creature: EntityWorldMut| {
+                if let Some(mut creature) = creature.get_mut::<Creature>() {
+                    creature.home = Some(GridCoords::new(x, y));
+                }
+            });
+        }
+
+        // Houses and garrisons keep the capacity their building type gives them,
+        // which buildings spawned above only get once their commands are applied
+        if let Some(residents) = &saved.residents {
+            let residents = resolve_all(residents);
+            for resident in &residents {
+                commands.entity(*resident).insert(Home(entity));
+            }
+            commands
+                .entity(entity)
+                .queue(move |mut house: EntityWorldMut| {
+                    if let Some(mut housing) = house.get_mut::<Housing>() {
+                        housing.residents = residents;
+                    }
+                });
+        }
+        if let Some(occupants) = &saved.occupants {
+            let occupants = resolve_all(occupants);
+            for occupant in &occupants {
+                shelter_unit(&mut commands, *occupant, entity);
+            }
+            commands
+                .entity(entity)
+                .queue(move |mut building: EntityWorldMut| {
+                    if let Some(mut garrison) = building.get_mut::<Garrison>() {
+                        garrison.occupants = occupants;
+                    }
+                });
+        }
+    }
+
+    for mut transform in &mut cameras {
+        transform.translation.x = save.camera.x;
+        transform.translation.y = save.camera.y;
+        transform.scale = Vec3::splat(1.0 / save.camera.zoom);
+    }
+    // Headless runs have no camera to restore
+    if let Some(mut pan_state) = pan_state {
+        pan_state.set_zoom_level(save.camera.zoom);
+    }
+    progress.play_time.0 = save.play_time;
+    progress.tick.0 = save.tick;
+    *progress.match_state = save.match_state.clone();
+    *progress.match_stats = save.match_stats.clone();
+    autosave_timer.0 = 0.0;
+
+    // Claims and selections pointed at entities from before the reload
+    *job_board = JobBoard::default();
+    for ring in &selection_rings {
+        commands.entity(ring).despawn();
+    }
+
+    info!(
+        "<apply_pending_load> Loaded {} entities, {} removed from the level",
+        ids.len(),
+        save.removed.len()
+    );
+    notices.send(SaveNotice::info("Game loaded"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::wildlife::CreatureKind;
+    use crate::systems::crafting::Recipe;
+    use crate::systems::garrison::Garrisoned;
+    use crate::systems::victory::WinConditions;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// A level with one placed worker cutting a forest cell and a workshop with someone inside
+    fn level_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<PlayTime>()
+            .init_resource::<CameraPanState>()
+            .init_resource::<SimTick>()
+            .init_resource::<MatchState>()
+            .init_resource::<MatchStats>()
+            .insert_resource(BaseLevel {
+                iids: ["worker", "workshop", "wolf", "rules", "gone"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            });
+
+        let world = app.world_mut();
+        world.resource_mut::<SimTick>().0 = 1200;
+        world.resource_mut::<MatchState>().players = vec![Owner(0), Owner(1)];
+        world
+            .resource_mut::<MatchStats>()
+            .record_gathered(Owner(0), &ItemId::WOOD, 25);
+        world.spawn((Camera::default(), Transform::from_xyz(32.0, 64.0, 0.0)));
+        // Level entities without a cell are still part of the level
+        world.spawn((EntityIid::new("rules"), WinConditions::default()));
+        let forest = world
+            .spawn((ResourceNode::default(), GridCoords::new(4, 5)))
+            .id();
+        let worker = world
+            .spawn((
+                EntityIid::new("worker"),
+                GridCoords::new(3, 5),
+                Owner(0),
+                Character,
+                Skills::default(),
+                Gathering {
+                    item: ItemId::WOOD,
+                    progress: 1.5,
+                    target: forest,
+                    base_time: 3.0,
+                    skill_modifier: 1.0,
+                    double_yield: 0.25,
+                },
+            ))
+            .id();
+        world.spawn((
+            EntityIid::new("wolf"),
+            GridCoords::new(12, 10),
+            Owner::NEUTRAL,
+            Creature {
+                home: Some(GridCoords::new(10, 10)),
+                ..Creature::new(CreatureKind::Wolf)
+            },
+        ));
+        let crafter = world
+            .spawn((
+                GridCoords::new(8, 8),
+                Owner(0),
+                Character,
+                Garrisoned {
+                    building: Entity::PLACEHOLDER,
+                },
+            ))
+            .id();
+        world.spawn((
+            EntityIid::new("workshop"),
+            GridCoords::new(8, 8),
+            Owner(0),
+            Garrison {
+                capacity: 2,
+                occupants: vec![crafter],
+            },
+            Housing {
+                capacity: 4,
+                residents: vec![worker, crafter],
+            },
+            CraftingStation {
+                queue: vec![Recipe::Planks],
+                progress: 2.0,
+            },
+        ));
+        app
+    }
+
+    fn capture(app: &mut App) -> SaveGame {
+        app.world_mut()
+            .run_system_once(|snapshot: SaveSnapshot| snapshot.capture())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn save_round_trips_through_ron() {
+        let mut app = level_app();
+        let save = capture(&mut app);
+
+        let contents = save.to_ron().unwrap();
+        let loaded = SaveGame::from_ron(&contents).unwrap();
+        assert_eq!(loaded.to_ron().unwrap(), contents);
+
+        assert_eq!(loaded.removed, vec!["gone".to_string()]);
+        assert_eq!(loaded.camera.x, 32.0);
+        assert_eq!(loaded.tick, 1200);
+        assert_eq!(loaded.match_state.players, vec![Owner(0), Owner(1)]);
+        assert_eq!(
+            loaded.match_stats.get(Owner(0)).gathered.get(&ItemId::WOOD),
+            Some(&25)
+        );
+
+        let worker = loaded
+            .entities
+            .iter()
+            .find(|saved| saved.id == SaveId::Placed("worker".into()))
+            .unwrap();
+        assert_eq!(worker.coords, (3, 5));
+        assert!(matches!(
+            &worker.activity,
+            Some(SavedActivity::Gathering { target: SaveId::Cell(4, 5), progress, .. })
+                if *progress == 1.5
+        ));
+
+        let workshop = loaded
+            .entities
+            .iter()
+            .find(|saved| saved.id == SaveId::Placed("workshop".into()))
+            .unwrap();
+        let crafter = SaveId::Spawned(0);
+        assert_eq!(workshop.occupants, Some(vec![crafter.clone()]));
+        assert_eq!(
+            workshop.residents,
+            Some(vec![SaveId::Placed("worker".into()), crafter])
+        );
+        assert_eq!(
+            workshop
+                .crafting
+                .as_ref()
+                .map(|crafting| crafting.queue.clone()),
+            Some(vec![Recipe::Planks])
+        );
+
+        let wolf = loaded
+            .entities
+            .iter()
+            .find(|saved| saved.id == SaveId::Placed("wolf".into()))
+            .unwrap();
+        assert_eq!(wolf.coords, (12, 10));
+        assert_eq!(wolf.creature_home, Some((10, 10)));
+    }
+
+    #[test]
+    fn save_from_another_version_is_refused() {
+        let mut app = level_app();
+        let mut save = capture(&mut app);
+        save.version = 0;
+
+        let error = SaveGame::from_ron(&save.to_ron().unwrap()).unwrap_err();
+        assert!(matches!(
+            error,
+            SaveError::Version {
+                found: 0,
+                expected: SAVE_VERSION
+            }
+        ));
+    }
+
+    /// A slot written to a save directory of its own, removed again once dropped
+    struct TestSlot {
+        dir: SaveDir,
+        slot: &'static str,
+    }
+
+    impl TestSlot {
+        fn write(slot: &'static str, contents: &str) -> Self {
+            let dir = SaveDir(std::env::temp_dir().join(format!(
+                "my-rts-game-{}-{}",
+                std::process::id(),
+                slot
+            )));
+            backend::write(&dir.0, slot, contents).unwrap();
+            Self { dir, slot }
+        }
+    }
+
+    impl Drop for TestSlot {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir.0);
+        }
+    }
+
+    #[test]
+    fn garbage_save_shows_a_parse_error() {
+        let slot = TestSlot::write("test-garbage", "this is not a save");
+
+        assert!(matches!(
+            read_save_info(&slot.dir, slot.slot),
+            Err(SaveError::Parse(_))
+        ));
+        let saves = list_saves(&slot.dir);
+        assert_eq!(saves.len(), 1);
+        assert!(saves[0].slot == slot.slot && saves[0].info.is_err());
+    }
+
+    #[test]
+    fn old_save_shows_a_version_error() {
+        let slot = TestSlot::write(
+            "test-version-0",
+            "(version: 0, saved_at: 0, map: \"test\", play_time: 0.0)",
+        );
+
+        let error = read_save_info(&slot.dir, slot.slot).unwrap_err();
+        assert!(matches!(error, SaveError::Version { found: 0, .. }));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "save is from version 0, this game reads version {}",
+                SAVE_VERSION
+            )
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use crate::components::ui::{BlocksMapClicks, HudPanel};
+use crate::systems::multiplayer::Lockstep;
+use crate::systems::save::{list_saves, load_from_slot, SaveDir, SaveNotice};
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for the load menu and save notices in the HUD.
+pub struct SaveMenuPlugin;
+
+impl Plugin for SaveMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NoticeTimer>()
+            .add_systems(Startup, setup_save_menu)
+            .add_systems(PostStartup, setup_notice_text)
+            .add_systems(
+                Update,
+                (toggle_save_menu, handle_load_buttons)
+                    .chain()
+                    .run_if(not(resource_exists::<Lockstep>)),
+            )
+            .add_systems(Update, show_save_notices);
+    }
+}
+
+/// Seconds a save notice stays in the HUD.
+const NOTICE_SECONDS: f32 = 5.0;
+
+/// The load menu, hidden until F8 is pressed.
+#[derive(Component)]
+struct SaveMenu;
+
+/// Holds the rows of the load menu, refilled whenever it opens.
+#[derive(Component)]
+struct SaveList;
+
+/// Button in the load menu that loads a slot.
+#[derive(Component, Debug)]
+struct LoadSlotButton(String);
+
+/// Marker for the save notice line in the HUD.
+#[derive(Component)]
+struct SaveNoticeText;
+
+/// Counts down until the current notice is cleared.
+#[derive(Resource, Default)]
+struct NoticeTimer(Timer);
+
+/// Formats seconds since the Unix epoch as a UTC date and time
+fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time % 3600) / 60
+    )
+}
+
+/// Formats play time as hours and minutes, or minutes and seconds when short
+fn format_play_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Spawns the hidden load menu in the middle of the screen
+fn setup_save_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(30.0),
+                top: Val::Percent(20.0),
+                width: Val::Percent(40.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.95)),
+            GlobalZIndex(10),
+            // Clicking a save shouldn't also select or drop things on the map
+            Interaction::default(),
+            FocusPolicy::Pass,
+            BlocksMapClicks,
+            SaveMenu,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Load game (F8 to close)"),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 22.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+
+            parent.spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                SaveList,
+            ));
+        });
+}
+
+/// Adds the save notice line to the HUD
+fn setup_notice_text(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    commands.entity(hud).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            SaveNoticeText,
+        ));
+    });
+}
+
+/// Opens and closes the load menu with F8, listing the saves each time it opens
+fn toggle_save_menu(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut menus: Query<&mut Node, With<SaveMenu>>,
+    lists: Query<Entity, With<SaveList>>,
+    save_dir: Res<SaveDir>,
+    asset_server: Res<AssetServer>,
+) {
+    if !keyboard.just_pressed(KeyCode::F8) {
+        return;
+    }
+
+    let Ok(mut menu) = menus.get_single_mut() else {
+        return;
+    };
+
+    if menu.display != Display::None {
+        menu.display = Display::None;
+        return;
+    }
+    menu.display = Display::Flex;
+
+    let Ok(list) = lists.get_single() else {
+        return;
+    };
+
+    let saves = list_saves(&save_dir);
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|parent| {
+        if saves.is_empty() {
+            parent.spawn((
+                Text::new("No saves yet, F5 saves the game"),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+        }
+
+        for save in saves {
+            match &save.info {
+                Ok(info) => {
+                    parent
+                        .spawn((
+                            Button,
+                            Node {
+                                padding: UiRect::all(Val::Px(6.0)),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                            FocusPolicy::Pass,
+                            LoadSlotButton(save.slot.clone()),
+                        ))
+                        .with_children(|button| {
+                            button.spawn((
+                                Text::new(format!(
+                                    "{} - {} - {} - played {}",
+                                    save.slot,
+                                    format_timestamp(info.saved_at),
+                                    info.map,
+                                    format_play_time(info.play_time)
+                                )),
+                                TextFont {
+                                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                    font_size: 14.0,
+                                    ..default()
+                                },
+                                TextColor(Color::WHITE),
+                            ));
+                        });
+                }
+                // Broken saves are listed so the player knows why they can't be loaded
+                Err(error) => {
+                    parent.spawn((
+                        Text::new(format!("{} - {}", save.slot, error)),
+                        TextFont {
+                            font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                            font_size: 14.0,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(1.0, 0.4, 0.4)),
+                    ));
+                }
+            }
+        }
+    });
+}
+
+/// Loads the clicked save and closes the menu
+fn handle_load_buttons(
+    mut commands: Commands,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &LoadSlotButton)>,
+    mut menus: Query<&mut Node, With<SaveMenu>>,
+    worlds: Query<Entity, With<LdtkProjectHandle>>,
+    save_dir: Res<SaveDir>,
+    mut notices: EventWriter<SaveNotice>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Some((_, button)) = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+    else {
+        return;
+    };
+
+    load_from_slot(&mut commands, &worlds, &save_dir, &button.0, &mut notices);
+
+    for mut menu in &mut menus {
+        menu.display = Display::None;
+    }
+}
+
+/// Shows the latest save notice in the HUD for a few seconds
+fn show_save_notices(
+    time: Res<Time>,
+    mut notices: EventReader<SaveNotice>,
+    mut timer: ResMut<NoticeTimer>,
+    mut texts: Query<(&mut Text, &mut TextColor), With<SaveNoticeText>>,
+) {
+    if let Some(notice) = notices.read().last() {
+        for (mut text, mut color) in &mut texts {
+            *text = Text::new(notice.message.clone());
+            color.0 = if notice.is_error {
+                Color::srgb(1.0, 0.4, 0.4)
+            } else {
+                Color::srgb(0.6, 1.0, 0.6)
+            };
+        }
+        timer.0 = Timer::from_seconds(NOTICE_SECONDS, TimerMode::Once);
+        return;
+    }
+
+    if timer.0.tick(time.delta()).just_finished() {
+        for (mut text, _) in &mut texts {
+            *text = Text::new("");
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use bevy_aseprite_ultra::prelude::*;
+use bevy_ecs_ldtk::ldtk::Type;
 use bevy_ecs_ldtk::prelude::*;
 
 /// Plugin for scene management and loading.
@@ -28,6 +29,18 @@ impl Plugin for ScenePlugin {
 #[derive(Component)]
 struct SplashScreen;
 
+/// LDtk map the game is played on.
+pub const MAP_PATH: &str = "test-map.ldtk";
+
+/// Finds the LDtk entity layer, so entities spawned at runtime share the
+/// same coordinate space as the entities placed in the map.
+pub fn find_entity_layer(layers: &Query<(Entity, &LayerMetadata)>) -> Option<Entity> {
+    layers
+        .iter()
+        .find(|(_, layer)| layer.layer_instance_type == Type::Entities)
+        .map(|(entity, _)| entity)
+}
+
 /// Sets up the initial scene with a camera and splash screen.
 pub fn setup_scene(mut commands: Commands, asset_server: Res<AssetServer>) {
     info!("Setting up scene...");
@@ -101,8 +114,8 @@ fn splash_screen_system(
             },
         ));
 
-        info!("Loading LDtk map: test-map.ldtk");
-        let map_handle = asset_server.load("test-map.ldtk");
+        info!("Loading LDtk map: {}", MAP_PATH);
+        let map_handle = asset_server.load(MAP_PATH);
         info!("Map handle created: {:?}", map_handle);
 
         commands.spawn(LdtkWorldBundle {
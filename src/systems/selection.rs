@@ -1,4 +1,5 @@
 use crate::components::unit::{Selectable, Selected, SelectionRing, Unit};
+use crate::systems::ui::{pointer_over_ui, PanelInteractions};
 use bevy::input::mouse::MouseButton;
 use bevy::input::ButtonInput;
 use bevy::prelude::*;
@@ -23,6 +24,8 @@ fn selection_system(
     window_query: Query<&Window, With<PrimaryWindow>>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    panel_interactions: PanelInteractions,
     selectable_query: Query<(Entity, &GlobalTransform, &Sprite), With<Selectable>>,
     selected_query: Query<Entity, With<Selected>>,
     selection_ring_query: Query<Entity, With<SelectionRing>>,
@@ -33,6 +36,14 @@ fn selection_system(
         return;
     }
 
+    // Ignore clicks on the UI, and keep the selection while placing a building or marking resources
+    if pointer_over_ui(&panel_interactions)
+        || keyboard.pressed(KeyCode::KeyB)
+        || keyboard.pressed(KeyCode::KeyG)
+    {
+        return;
+    }
+
     // Get the primary window
     let window = window_query.single();
 
@@ -0,0 +1,245 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::components::combat::Health;
+use crate::components::inventory::Inventory;
+use crate::components::movement::Moving;
+use crate::components::resources::ResourceNode;
+use crate::components::unit::Unit;
+use crate::systems::crafting::CraftingStation;
+use crate::systems::garrison::Garrison;
+use crate::systems::production::ProductionQueue;
+use bevy::ecs::schedule::ExecutorKind;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Simulation ticks per second of game time.
+pub const TICK_RATE: f64 = 20.0;
+
+/// Plugin that runs the game rules on a fixed timestep in a set order.
+///
+/// Gameplay systems live in `FixedUpdate`, so a unit walks, gathers and fights
+/// the same whatever the frame rate. `FixedUpdate` runs on one thread and its
+/// stages are chained, so every tick applies the same systems in the same order.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .init_resource::<SimTick>()
+            .init_resource::<SimIds>()
+            .init_resource::<TickGate>()
+            .edit_schedule(FixedUpdate, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            .configure_sets(
+                FixedUpdate,
+                (
+                    SimulationSet::Gate,
+                    SimulationSet::Tick,
+                    SimulationSet::Input,
+                    SimulationSet::Commands,
+                    SimulationSet::Orders,
+                    SimulationSet::Movement,
+                    SimulationSet::Actions,
+                    SimulationSet::Resolve,
+                )
+                    .chain()
+                    .run_if(resource_exists::<LevelReady>),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    SimulationSet::Tick,
+                    SimulationSet::Input,
+                    SimulationSet::Commands,
+                    SimulationSet::Orders,
+                    SimulationSet::Movement,
+                    SimulationSet::Actions,
+                    SimulationSet::Resolve,
+                )
+                    .run_if(|gate: Res<TickGate>| gate.open),
+            )
+            .add_systems(Update, track_level)
+            .add_systems(
+                FixedUpdate,
+                (advance_tick, assign_sim_ids)
+                    .chain()
+                    .in_set(SimulationSet::Tick),
+            );
+    }
+}
+
+/// The stages of a simulation tick, run in this order.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    /// Decides whether the tick runs at all, by setting `TickGate`.
+    Gate,
+    /// Counts the tick and numbers new entities.
+    Tick,
+    /// Settles which `GameCommand`s this tick carries out, recording or replaying them.
+    Input,
+    /// Carries out the `GameCommand`s given since the last tick.
+    Commands,
+    /// Units pick up work on their own: jobs, auto behaviour, drop-offs.
+    Orders,
+    /// Pathfinding and walking.
+    Movement,
+    /// Work that takes time: gathering, building, training, crafting and fighting.
+    Actions,
+    /// Bookkeeping once the tick's work is done: deaths, housing, idle units and XP.
+    Resolve,
+}
+
+/// Present while the level is fully spawned. The simulation doesn't tick without it.
+#[derive(Resource, Debug)]
+pub struct LevelReady;
+
+/// Whether the current tick runs. Decided once in `SimulationSet::Gate` so
+/// every later stage agrees, open unless something is holding the game back.
+#[derive(Resource, Debug)]
+pub struct TickGate {
+    pub open: bool,
+}
+
+impl Default for TickGate {
+    fn default() -> Self {
+        Self { open: true }
+    }
+}
+
+/// Number of simulation ticks run since the level last spawned.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimTick(pub u64);
+
+/// Names an entity the same way in every run of the same game, unlike `Entity`,
+/// so recorded commands can find their units again.
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct SimId(pub u32);
+
+/// Looks entities up by their `SimId`.
+#[derive(Resource, Debug, Default)]
+pub struct SimIds {
+    next: u32,
+    entities: HashMap<SimId, Entity>,
+}
+
+impl SimIds {
+    /// The entity with this id, if it was ever numbered. It may since have despawned
+    pub fn entity(&self, id: SimId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    /// Hands out the next id to `entity`
+    pub(crate) fn assign(&mut self, entity: Entity) -> SimId {
+        let id = SimId(self.next);
+        self.next += 1;
+        self.entities.insert(id, entity);
+        id
+    }
+}
+
+/// What `state_hash` looks at on every numbered entity.
+pub type SimState<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static SimId,
+        &'static GridCoords,
+        Option<&'static Health>,
+        Option<&'static Inventory>,
+        Option<&'static Moving>,
+    ),
+>;
+
+/// Hash of everything that should come out the same in two runs of the same game
+pub fn state_hash(entities: &SimState) -> u64 {
+    let mut entities: Vec<_> = entities.iter().collect();
+    entities.sort_by_key(|(id, ..)| **id);
+
+    let mut hasher = DefaultHasher::new();
+    for (id, coords, health, inventory, moving) in entities {
+        id.hash(&mut hasher);
+        (coords.x, coords.y).hash(&mut hasher);
+        if let Some(health) = health {
+            health.current.to_bits().hash(&mut hasher);
+        }
+        if let Some(inventory) = inventory {
+            for slot in inventory.slots.iter().flatten() {
+                (&slot.item, slot.quantity).hash(&mut hasher);
+            }
+        }
+        if let Some(moving) = moving {
+            moving.progress.to_bits().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Pauses the simulation while the level is being spawned, and starts counting
+/// ticks again once it's in
+pub(crate) fn track_level(
+    mut commands: Commands,
+    mut level_events: EventReader<LevelEvent>,
+    mut tick: ResMut<SimTick>,
+) {
+    for event in level_events.read() {
+        match event {
+            LevelEvent::SpawnTriggered(_) => {
+                commands.remove_resource::<LevelReady>();
+            }
+            LevelEvent::Transformed(_) => {
+                info!("<track_level> Level is in, starting the simulation");
+                commands.insert_resource(LevelReady);
+                tick.0 = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn advance_tick(mut tick: ResMut<SimTick>) {
+    tick.0 += 1;
+}
+
+/// Numbers everything a command can point at, in map order so every run hands
+/// out the same ids
+fn assign_sim_ids(
+    mut commands: Commands,
+    mut sim_ids: ResMut<SimIds>,
+    new_entities: Query<
+        (Entity, &GridCoords, Option<&EntityIid>),
+        (
+            Without<SimId>,
+            Or<(
+                With<Unit>,
+                With<ResourceNode>,
+                With<Inventory>,
+                With<Health>,
+                With<Garrison>,
+                With<ProductionQueue>,
+                With<CraftingStation>,
+            )>,
+        ),
+    >,
+) {
+    let mut new_entities: Vec<_> = new_entities.iter().collect();
+    // Entities spawned on the same cell in the same tick fall back to spawn order
+    new_entities.sort_by_key(|(entity, coords, iid)| {
+        (
+            coords.y,
+            coords.x,
+            iid.map(|iid| iid.as_str().to_string()),
+            *entity,
+        )
+    });
+
+    for (entity, ..) in new_entities {
+        let id = sim_ids.assign(entity);
+        commands.entity(entity).insert(id);
+    }
+}
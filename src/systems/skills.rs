@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+
+use crate::components::items::ItemId;
+use crate::components::skills::{
+    PerkDefinition, PerkEffect, SkillDefinition, SkillKind, SkillProgression, Skills, XpCurve,
+};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::Selected;
+use crate::systems::ron_asset::RonAssetLoader;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Plugin that loads skill definitions and levels units up as they gain XP.
+pub struct SkillsPlugin;
+
+impl Plugin for SkillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SkillDefinitions>()
+            .register_asset_loader(RonAssetLoader::<SkillDefinitions>::new(&["skills.ron"]))
+            .init_resource::<SkillRegistry>()
+            .add_event::<SkillXpGained>()
+            .add_event::<SkillLevelUp>()
+            .add_systems(Startup, load_skill_definitions)
+            .add_systems(Update, update_skill_registry)
+            .add_systems(
+                FixedUpdate,
+                (apply_skill_xp, announce_level_ups)
+                    .chain()
+                    .in_set(SimulationSet::Resolve),
+            )
+            .add_systems(Update, update_skills_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Contents of a `.skills.ron` file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct SkillDefinitions {
+    pub skills: Vec<SkillDefinition>,
+}
+
+/// Handle keeping the skill definitions loaded.
+#[derive(Resource)]
+struct SkillDefinitionsHandle(Handle<SkillDefinitions>);
+
+/// Curve for skills missing from the definitions.
+const DEFAULT_CURVE: XpCurve = XpCurve::Linear {
+    base: 100.0,
+    per_level: 10.0,
+};
+
+/// Level cap for skills missing from the definitions.
+const DEFAULT_MAX_LEVEL: u32 = 20;
+
+/// Effectiveness gained per level for skills missing from the definitions.
+const DEFAULT_GAIN_PER_LEVEL: f32 = 0.1;
+
+/// Everything that earns XP. Which skill each action trains and how much XP
+/// it's worth are decided here and nowhere else, so balancing happens in one place:
+///
+/// | Action                     | Skill        | XP |
+/// |----------------------------|--------------|----|
+/// | Gathering wood             | Woodcutting  | 5  |
+/// | Gathering gold or stone    | Mining       | 5  |
+/// | Gathering anything else    | Harvesting   | 5  |
+/// | Finishing a building       | Construction | 10 |
+/// | Finishing a crafted item   | Crafting     | 10 |
+/// | Landing a hit              | Combat       | 2  |
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkillAction {
+    /// One yield of an item from a resource node
+    Gather(ItemId),
+    Construct,
+    Craft,
+    Attack,
+}
+
+impl SkillAction {
+    /// Skill the action trains, and whose effectiveness applies while doing it
+    pub fn skill(&self) -> SkillKind {
+        match self {
+            SkillAction::Gather(item) if *item == ItemId::WOOD => SkillKind::Woodcutting,
+            SkillAction::Gather(item) if *item == ItemId::GOLD || *item == ItemId::STONE => {
+                SkillKind::Mining
+            }
+            SkillAction::Gather(_) => SkillKind::Harvesting,
+            SkillAction::Construct => SkillKind::Construction,
+            SkillAction::Craft => SkillKind::Crafting,
+            SkillAction::Attack => SkillKind::Combat,
+        }
+    }
+
+    pub fn xp(&self) -> f32 {
+        match self {
+            SkillAction::Gather(_) => 5.0,
+            SkillAction::Construct => 10.0,
+            SkillAction::Craft => 10.0,
+            SkillAction::Attack => 2.0,
+        }
+    }
+}
+
+/// Sent when a unit does something that earns XP.
+#[derive(Event, Debug)]
+pub struct SkillXpGained {
+    pub entity: Entity,
+    pub action: SkillAction,
+}
+
+/// Sent when a unit reaches a new level in a skill.
+#[derive(Event, Debug)]
+pub struct SkillLevelUp {
+    pub entity: Entity,
+    pub skill: SkillKind,
+    pub level: u32,
+}
+
+/// Every skill's progression rules, by skill.
+#[derive(Resource, Debug, Default)]
+pub struct SkillRegistry {
+    skills: HashMap<SkillKind, SkillDefinition>,
+}
+
+impl SkillRegistry {
+    pub fn get(&self, skill: SkillKind) -> Option<&SkillDefinition> {
+        self.skills.get(&skill)
+    }
+
+    pub fn xp_to_next(&self, skill: SkillKind, level: u32) -> f32 {
+        self.get(skill)
+            .map(|definition| definition.curve)
+            .unwrap_or(DEFAULT_CURVE)
+            .xp_to_next(level)
+    }
+
+    pub fn max_level(&self, skill: SkillKind) -> u32 {
+        self.get(skill)
+            .map(|definition| definition.max_level)
+            .unwrap_or(DEFAULT_MAX_LEVEL)
+    }
+
+    pub fn gain_per_level(&self, skill: SkillKind) -> f32 {
+        self.get(skill)
+            .map(|definition| definition.gain_per_level)
+            .unwrap_or(DEFAULT_GAIN_PER_LEVEL)
+    }
+
+    /// Perks of a skill unlocked at or below a level
+    pub fn perks(&self, skill: SkillKind, level: u32) -> impl Iterator<Item = &PerkDefinition> {
+        self.get(skill)
+            .into_iter()
+            .flat_map(|definition| &definition.perks)
+            .filter(move |perk| perk.level <= level)
+    }
+
+    /// Total of one perk effect a unit has unlocked in a skill, 0 without any
+    pub fn perk_bonus(
+        &self,
+        progression: &SkillProgression,
+        skill: SkillKind,
+        effect: PerkEffect,
+    ) -> f32 {
+        self.perks(skill, progression.level(skill))
+            .filter(|perk| perk.effect == effect)
+            .map(|perk| perk.amount)
+            .sum()
+    }
+}
+
+/// Adds XP to a unit's skill, levelling it up for every threshold crossed until the cap
+fn add_xp(
+    entity: Entity,
+    skill: SkillKind,
+    amount: f32,
+    skills: &mut Skills,
+    progression: &mut SkillProgression,
+    registry: &SkillRegistry,
+    level_ups: &mut EventWriter<SkillLevelUp>,
+) {
+    let max_level = registry.max_level(skill);
+    let current = progression.levels.entry(skill).or_default();
+
+    if current.level >= max_level {
+        return;
+    }
+
+    current.xp += amount;
+
+    while current.level < max_level {
+        let needed = registry.xp_to_next(skill, current.level);
+        if current.xp < needed {
+            break;
+        }
+
+        current.xp -= needed;
+        current.level += 1;
+        *skills.get_mut(skill) += registry.gain_per_level(skill);
+
+        level_ups.send(SkillLevelUp {
+            entity,
+            skill,
+            level: current.level,
+        });
+    }
+
+    // Nothing to save up for once capped
+    if current.level >= max_level {
+        current.xp = 0.0;
+    }
+}
+
+fn load_skill_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SkillDefinitionsHandle(
+        asset_server.load("data/core.skills.ron"),
+    ));
+}
+
+/// Rebuilds the registry whenever the definitions are loaded or edited
+fn update_skill_registry(
+    mut events: EventReader<AssetEvent<SkillDefinitions>>,
+    definitions: Res<Assets<SkillDefinitions>>,
+    handle: Option<Res<SkillDefinitionsHandle>>,
+    mut registry: ResMut<SkillRegistry>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+
+        let Some(definitions) = definitions.get(&handle.0) else {
+            continue;
+        };
+
+        registry.skills = definitions
+            .skills
+            .iter()
+            .map(|definition| (definition.skill, definition.clone()))
+            .collect();
+
+        info!(
+            "<update_skill_registry> Loaded {} skill definitions",
+            registry.skills.len()
+        );
+    }
+}
+
+/// The one place XP is handed out, everything else sends `SkillXpGained`
+fn apply_skill_xp(
+    mut xp_events: EventReader<SkillXpGained>,
+    mut units: Query<(&mut Skills, &mut SkillProgression)>,
+    registry: Res<SkillRegistry>,
+    mut level_ups: EventWriter<SkillLevelUp>,
+) {
+    for event in xp_events.read() {
+        let Ok((mut skills, mut progression)) = units.get_mut(event.entity) else {
+            continue;
+        };
+
+        add_xp(
+            event.entity,
+            event.action.skill(),
+            event.action.xp(),
+            &mut skills,
+            &mut progression,
+            &registry,
+            &mut level_ups,
+        );
+    }
+}
+
+/// Logs level ups and any perks they unlock
+fn announce_level_ups(
+    mut level_ups: EventReader<SkillLevelUp>,
+    skills: Query<&Skills>,
+    registry: Res<SkillRegistry>,
+) {
+    for level_up in level_ups.read() {
+        let value = skills
+            .get(level_up.entity)
+            .map(|skills| skills.get(level_up.skill))
+            .unwrap_or_default();
+
+        info!(
+            "<announce_level_ups> {:?} reached {} level {} ({:.1})",
+            level_up.entity,
+            level_up.skill.name(),
+            level_up.level,
+            value
+        );
+
+        for perk in registry
+            .perks(level_up.skill, level_up.level)
+            .filter(|perk| perk.level == level_up.level)
+        {
+            info!(
+                "<announce_level_ups> {:?} unlocked {}",
+                level_up.entity, perk.name
+            );
+        }
+    }
+}
+
+/// Shows the selected unit's skill levels, XP and unlocked perks
+fn update_skills_ui(
+    selected: Query<(&Skills, &SkillProgression), With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    registry: Res<SkillRegistry>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok((skills, progression)) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new("Skills:"),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        for skill in SkillKind::ALL {
+            let current = progression.get(skill);
+            let line = if current.level >= registry.max_level(skill) {
+                format!(
+                    "{}: {:.1} (level {}, max)",
+                    skill.name(),
+                    skills.get(skill),
+                    current.level
+                )
+            } else {
+                format!(
+                    "{}: {:.1} (level {}, {:.0}/{:.0} xp)",
+                    skill.name(),
+                    skills.get(skill),
+                    current.level,
+                    current.xp,
+                    registry.xp_to_next(skill, current.level)
+                )
+            };
+
+            parent.spawn((
+                Text::new(line),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        }
+
+        let perks: Vec<&str> = SkillKind::ALL
+            .iter()
+            .flat_map(|skill| registry.perks(*skill, progression.level(*skill)))
+            .map(|perk| perk.name.as_str())
+            .collect();
+
+        if !perks.is_empty() {
+            parent.spawn((
+                Text::new(format!("Perks: {}", perks.join(", "))),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.6, 0.8, 1.0)),
+            ));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn skills_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<SkillRegistry>()
+            .add_event::<SkillXpGained>()
+            .add_event::<SkillLevelUp>()
+            .add_systems(Update, apply_skill_xp);
+        let unit = app
+            .world_mut()
+            .spawn((Skills::default(), SkillProgression::default()))
+            .id();
+        (app, unit)
+    }
+
+    fn level_ups(app: &App) -> Vec<u32> {
+        let events = app.world().resource::<Events<SkillLevelUp>>();
+        events
+            .iter_current_update_events()
+            .map(|level_up| level_up.level)
+            .collect()
+    }
+
+    #[test]
+    fn actions_train_their_skill() {
+        let skill = |item: ItemId| SkillAction::Gather(item).skill();
+        assert_eq!(skill(ItemId::WOOD), SkillKind::Woodcutting);
+        assert_eq!(skill(ItemId::GOLD), SkillKind::Mining);
+        assert_eq!(skill(ItemId::STONE), SkillKind::Mining);
+        assert_eq!(skill(ItemId::FOOD), SkillKind::Harvesting);
+        assert_eq!(SkillAction::Construct.skill(), SkillKind::Construction);
+        assert_eq!(SkillAction::Craft.skill(), SkillKind::Crafting);
+        assert_eq!(SkillAction::Attack.skill(), SkillKind::Combat);
+    }
+
+    #[test]
+    fn xp_event_is_applied_once() {
+        let (mut app, unit) = skills_app();
+        app.world_mut().send_event(SkillXpGained {
+            entity: unit,
+            action: SkillAction::Gather(ItemId::STONE),
+        });
+
+        // Events stay around for two updates, the reader must not see it twice
+        app.update();
+        app.update();
+
+        let progression = app.world().get::<SkillProgression>(unit).unwrap();
+        assert_eq!(progression.get(SkillKind::Mining).xp, 5.0);
+        assert_eq!(progression.get(SkillKind::Harvesting).xp, 0.0);
+    }
+
+    fn give_xp(app: &mut App, unit: Entity, amount: f32) {
+        app.world_mut()
+            .run_system_once(
+                move |mut units: Query<(&mut Skills, &mut SkillProgression)>,
+                      registry: Res<SkillRegistry>,
+                      mut level_ups: EventWriter<SkillLevelUp>| {
+                    let (mut skills, mut progression) = units.get_mut(unit).unwrap();
+                    add_xp(
+                        unit,
+                        SkillKind::Mining,
+                        amount,
+                        &mut skills,
+                        &mut progression,
+                        &registry,
+                        &mut level_ups,
+                    );
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn xp_crosses_several_levels_at_once() {
+        let (mut app, unit) = skills_app();
+
+        // The default curve costs 100, 110 and 120 for the first three levels
+        give_xp(&mut app, unit, 335.0);
+
+        let progression = app.world().get::<SkillProgression>(unit).unwrap();
+        assert_eq!(progression.get(SkillKind::Mining).level, 4);
+        assert_eq!(progression.get(SkillKind::Mining).xp, 5.0);
+        assert_eq!(level_ups(&app), vec![2, 3, 4]);
+
+        let skills = app.world().get::<Skills>(unit).unwrap();
+        assert!((skills.mining - (1.0 + 3.0 * DEFAULT_GAIN_PER_LEVEL)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn xp_stops_at_the_level_cap() {
+        let (mut app, unit) = skills_app();
+
+        give_xp(&mut app, unit, 1_000_000.0);
+
+        let progression = app.world().get::<SkillProgression>(unit).unwrap();
+        assert_eq!(progression.get(SkillKind::Mining).level, DEFAULT_MAX_LEVEL);
+        assert_eq!(progression.get(SkillKind::Mining).xp, 0.0);
+        assert_eq!(level_ups(&app).len() as u32, DEFAULT_MAX_LEVEL - 1);
+
+        // Capped skills don't bank any more XP
+        give_xp(&mut app, unit, 500.0);
+        let progression = app.world().get::<SkillProgression>(unit).unwrap();
+        assert_eq!(progression.get(SkillKind::Mining).xp, 0.0);
+    }
+}
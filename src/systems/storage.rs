@@ -0,0 +1,347 @@
+use crate::components::inventory::{Inventory, Stockpile, StorageFilter};
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selected};
+use crate::systems::game_command::GameCommand;
+use crate::systems::movement::grid_distance;
+use crate::systems::resource_gathering::{find_adjacent_positions, GatheringIntent};
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for storage filters and workers taking their loads to storage.
+pub struct StoragePlugin;
+
+impl Plugin for StoragePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_filter_buttons.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                (execute_filter_commands, cancel_drop_off).in_set(SimulationSet::Commands),
+            )
+            .add_systems(FixedUpdate, find_drop_off.in_set(SimulationSet::Orders))
+            .add_systems(
+                FixedUpdate,
+                complete_drop_off.in_set(SimulationSet::Actions),
+            )
+            .add_systems(Update, update_storage_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+// Component for a worker taking its load to storage, then maybe back to gathering
+#[derive(Component, Debug)]
+pub struct DropOff {
+    // Resource and item to go back to gathering afterwards
+    pub resume: Option<(Entity, ItemId)>,
+    pub chest: Option<Entity>,
+}
+
+impl DropOff {
+    /// Unload, then go back to gathering the resource
+    pub fn new(resource: Entity, item: ItemId) -> Self {
+        Self {
+            resume: Some((resource, item)),
+            chest: None,
+        }
+    }
+
+    /// Unload and stop there
+    pub fn haul() -> Self {
+        Self {
+            resume: None,
+            chest: None,
+        }
+    }
+}
+
+/// Button in the info panel that toggles one item in a storage filter.
+#[derive(Component, Debug)]
+struct ToggleFilterButton(ItemId);
+
+/// Orders items toggled in the selected storage's filter
+fn handle_filter_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &ToggleFilterButton)>,
+    selected: Query<Entity, (With<StorageFilter>, With<Selected>)>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(storage) = selected.get_single() else {
+        return;
+    };
+
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            game_commands.send(GameCommand::ToggleFilter {
+                storage,
+                item: button.0.clone(),
+            });
+        }
+    }
+}
+
+/// Toggles items in storage filters as ordered
+fn execute_filter_commands(
+    mut game_commands: EventReader<GameCommand>,
+    mut filters: Query<&mut StorageFilter>,
+) {
+    for command in game_commands.read() {
+        if let GameCommand::ToggleFilter { storage, item } = command {
+            if let Ok(mut filter) = filters.get_mut(*storage) {
+                filter.toggle(item);
+            }
+        }
+    }
+}
+
+/// Any new order replaces the trip to storage
+fn cancel_drop_off(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    dropping_off: Query<(), With<DropOff>>,
+) {
+    for command in game_commands.read() {
+        if let Some(unit) = command
+            .ordered_unit()
+            .filter(|unit| dropping_off.contains(*unit))
+        {
+            commands.entity(unit).remove::<DropOff>();
+        }
+    }
+}
+
+/// Picks the nearest of its player's storage that accepts something the worker carries
+fn find_drop_off(
+    mut commands: Commands,
+    mut workers: Query<(
+        Entity,
+        &GridCoords,
+        &Inventory,
+        Option<&Owner>,
+        &mut DropOff,
+        &mut MoveTarget,
+    )>,
+    chests: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Inventory,
+            &StorageFilter,
+            Option<&Owner>,
+        ),
+        With<Stockpile>,
+    >,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, coords, inventory, owner, mut drop_off, mut move_target) in &mut workers {
+        if drop_off.chest.is_some() {
+            continue;
+        }
+
+        let owner = owner.copied().unwrap_or_default();
+        let nearest = chests
+            .iter()
+            .filter(|(.., chest_owner)| chest_owner.copied().unwrap_or_default() == owner)
+            .filter(|(_, _, chest_inventory, filter, _)| {
+                inventory.slots.iter().flatten().any(|stack| {
+                    filter.allows(&stack.item) && chest_inventory.room_for(&stack.item, &items) > 0
+                })
+            })
+            .min_by_key(|(_, chest_coords, ..)| grid_distance(coords, chest_coords));
+
+        let Some((chest, chest_coords, ..)) = nearest else {
+            info!(
+                "<find_drop_off> No storage has room for what {:?} carries",
+                entity
+            );
+            commands.entity(entity).remove::<DropOff>();
+            continue;
+        };
+
+        if grid_distance(coords, chest_coords) > 1 {
+            let mut approach_positions = find_adjacent_positions(*chest_coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - coords.x).pow(2) + (pos.y - coords.y).pow(2));
+
+            let Some(dest) = approach_positions.first() else {
+                info!("<find_drop_off> No way to reach {:?}", chest);
+                commands.entity(entity).remove::<DropOff>();
+                continue;
+            };
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
+        }
+
+        drop_off.chest = Some(chest);
+        info!(
+            "<find_drop_off> {:?} is taking its load to {:?}",
+            entity, chest
+        );
+    }
+}
+
+/// Unloads into the chest, then heads back to the resource
+fn complete_drop_off(
+    mut commands: Commands,
+    mut workers: Query<(Entity, &DropOff, &GridCoords, &mut MoveTarget), Without<Moving>>,
+    coords: Query<&GridCoords>,
+    filters: Query<&StorageFilter>,
+    mut inventories: Query<&mut Inventory>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, drop_off, worker_coords, mut move_target) in &mut workers {
+        let Some(chest) = drop_off.chest else {
+            continue;
+        };
+
+        let Ok(chest_coords) = coords.get(chest) else {
+            commands.entity(entity).remove::<DropOff>();
+            continue;
+        };
+
+        if grid_distance(worker_coords, chest_coords) > 1 {
+            // Still waiting on a path, or the walk ended somewhere else
+            if move_target.destination.is_none() && move_target.path.is_empty() {
+                info!(
+                    "<complete_drop_off> {:?} couldn't reach {:?}",
+                    entity, chest
+                );
+                commands.entity(entity).remove::<DropOff>();
+            }
+            continue;
+        }
+
+        commands.entity(entity).remove::<DropOff>();
+
+        let Ok([mut worker_inventory, mut chest_inventory]) =
+            inventories.get_many_mut([entity, chest])
+        else {
+            continue;
+        };
+
+        let carried: Vec<ItemId> = worker_inventory
+            .slots
+            .iter()
+            .flatten()
+            .map(|stack| stack.item.clone())
+            .collect();
+
+        let mut moved = 0;
+        for item in carried {
+            if filters.get(chest).is_ok_and(|filter| !filter.allows(&item)) {
+                continue;
+            }
+
+            let count = worker_inventory.count_item(&item);
+            moved += worker_inventory.transfer_to(&mut chest_inventory, &item, count, &items);
+        }
+        chest_inventory.sort(&items);
+
+        info!(
+            "<complete_drop_off> {:?} stored {} items in {:?}",
+            entity, moved, chest
+        );
+
+        // Head back to where the load came from
+        let Some((resource, item)) = &drop_off.resume else {
+            continue;
+        };
+
+        let Ok(resource_coords) = coords.get(*resource) else {
+            continue;
+        };
+
+        let mut approach_positions = find_adjacent_positions(*resource_coords, &obstacles);
+        approach_positions
+            .sort_by_key(|pos| (pos.x - worker_coords.x).pow(2) + (pos.y - worker_coords.y).pow(2));
+
+        let Some(dest) = approach_positions.first() else {
+            continue;
+        };
+        move_target.destination = Some(*dest);
+        move_target.path.clear();
+
+        commands.entity(entity).insert(GatheringIntent {
+            target: *resource,
+            item: item.clone(),
+        });
+    }
+}
+
+/// Shows which items the selected storage accepts, with a toggle for each
+fn update_storage_ui(
+    selected: Query<&StorageFilter, With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok(filter) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new("Accepts"),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                flex_wrap: FlexWrap::Wrap,
+                column_gap: Val::Px(4.0),
+                row_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for id in items.ids() {
+                    let background = if filter.allows(id) {
+                        Color::srgb(0.2, 0.5, 0.2)
+                    } else {
+                        Color::srgb(0.4, 0.15, 0.15)
+                    };
+
+                    row.spawn((
+                        Button,
+                        // Let the panel see the click too, so it doesn't reach the map
+                        FocusPolicy::Pass,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BackgroundColor(background),
+                        ToggleFilterButton(id.clone()),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(items.name(id)),
+                            TextFont {
+                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                font_size: 12.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                }
+            });
+    });
+}
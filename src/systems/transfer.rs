@@ -0,0 +1,401 @@
+use crate::components::entities::{Character, Chest};
+use crate::components::inventory::{Inventory, StorageFilter};
+use crate::components::items::{ItemId, ItemRegistry};
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::Selected;
+use crate::systems::game_command::GameCommand;
+use crate::systems::movement::grid_distance;
+use crate::systems::resource_gathering::find_adjacent_positions;
+use crate::systems::simulation::SimulationSet;
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for ordering units to give items to, or take items from, other inventories.
+pub struct TransferPlugin;
+
+impl Plugin for TransferPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TransferSettings>()
+            .add_systems(Update, handle_transfer_buttons.in_set(InfoPanelSet::Input))
+            .add_systems(
+                FixedUpdate,
+                execute_transfer_commands.in_set(SimulationSet::Commands),
+            )
+            .add_systems(
+                FixedUpdate,
+                complete_transfer_orders.in_set(SimulationSet::Actions),
+            )
+            .add_systems(Update, update_transfer_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Amounts the transfer amount button cycles through.
+const AMOUNT_STEPS: [TransferAmount; 4] = [
+    TransferAmount::Exactly(1),
+    TransferAmount::Exactly(5),
+    TransferAmount::Exactly(10),
+    TransferAmount::All,
+];
+
+/// Which way items move in a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferDirection {
+    /// From the unit into the target
+    Give,
+    /// From the target into the unit, only chests allow this
+    Take,
+}
+
+/// How many items a transfer moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferAmount {
+    Exactly(u32),
+    /// Everything of that item the giver holds
+    All,
+}
+
+impl TransferAmount {
+    fn label(&self) -> String {
+        match self {
+            TransferAmount::Exactly(amount) => amount.to_string(),
+            TransferAmount::All => "All".to_string(),
+        }
+    }
+}
+
+/// What the next T + right-click order will transfer, set from the info panel.
+#[derive(Resource, Debug)]
+pub struct TransferSettings {
+    pub item: ItemId,
+    pub amount: TransferAmount,
+    pub direction: TransferDirection,
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self {
+            item: ItemId::WOOD,
+            amount: TransferAmount::All,
+            direction: TransferDirection::Give,
+        }
+    }
+}
+
+// Component for a unit walking to another inventory to give or take items
+#[derive(Component, Debug)]
+pub struct TransferOrder {
+    pub target: Entity,
+    pub item: ItemId,
+    pub amount: TransferAmount,
+    pub direction: TransferDirection,
+}
+
+/// Buttons in the info panel that change the transfer settings.
+#[derive(Component, Debug, Clone, Copy)]
+enum TransferSettingButton {
+    Item,
+    Amount,
+    Direction,
+}
+
+/// Cycles the transfer settings when their buttons are clicked
+fn handle_transfer_buttons(
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    buttons: Query<(&Interaction, &TransferSettingButton)>,
+    mut settings: ResMut<TransferSettings>,
+    items: Res<ItemRegistry>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            TransferSettingButton::Item => {
+                let ids = items.ids();
+                if ids.is_empty() {
+                    continue;
+                }
+                let next = ids
+                    .iter()
+                    .position(|id| **id == settings.item)
+                    .map(|index| (index + 1) % ids.len())
+                    .unwrap_or(0);
+                settings.item = ids[next].clone();
+            }
+            TransferSettingButton::Amount => {
+                let next = AMOUNT_STEPS
+                    .iter()
+                    .position(|amount| *amount == settings.amount)
+                    .map(|index| (index + 1) % AMOUNT_STEPS.len())
+                    .unwrap_or(0);
+                settings.amount = AMOUNT_STEPS[next];
+            }
+            TransferSettingButton::Direction => {
+                settings.direction = match settings.direction {
+                    TransferDirection::Give => TransferDirection::Take,
+                    TransferDirection::Take => TransferDirection::Give,
+                };
+            }
+        }
+    }
+}
+
+/// Sends units to the inventory they were ordered to trade with, any other order calls it off
+fn execute_transfer_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut units: Query<(&GridCoords, &mut MoveTarget), (With<Character>, With<Inventory>)>,
+    transferring: Query<(), With<TransferOrder>>,
+    holders: Query<(&GridCoords, Has<Chest>), With<Inventory>>,
+    obstacles: Query<&GridCoords, With<Collider>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Transfer {
+            unit,
+            target,
+            item,
+            amount,
+            direction,
+        } = command
+        else {
+            if let Some(unit) = command
+                .ordered_unit()
+                .filter(|unit| transferring.contains(*unit))
+            {
+                commands.entity(unit).remove::<TransferOrder>();
+            }
+            continue;
+        };
+
+        if unit == target {
+            continue;
+        }
+
+        let (Ok((unit_coords, mut move_target)), Ok((target_coords, is_chest))) =
+            (units.get_mut(*unit), holders.get(*target))
+        else {
+            continue;
+        };
+
+        if *direction == TransferDirection::Take && !is_chest {
+            info!("<execute_transfer_commands> Can only take items out of chests");
+            continue;
+        }
+
+        if grid_distance(unit_coords, target_coords) > 1 {
+            let mut approach_positions = find_adjacent_positions(*target_coords, &obstacles);
+            approach_positions
+                .sort_by_key(|pos| (pos.x - unit_coords.x).pow(2) + (pos.y - unit_coords.y).pow(2));
+
+            let Some(dest) = approach_positions.first() else {
+                info!("<execute_transfer_commands> No way to reach {:?}", target);
+                continue;
+            };
+            move_target.destination = Some(*dest);
+            move_target.path.clear();
+        }
+
+        commands.entity(*unit).insert(TransferOrder {
+            target: *target,
+            item: item.clone(),
+            amount: *amount,
+            direction: *direction,
+        });
+
+        info!(
+            "<execute_transfer_commands> {:?} will {:?} {} {} with {:?}",
+            unit,
+            direction,
+            amount.label(),
+            item,
+            target
+        );
+    }
+}
+
+/// Transfers the items once the unit is next to its target
+fn complete_transfer_orders(
+    mut commands: Commands,
+    units: Query<(Entity, &TransferOrder, &GridCoords, &MoveTarget), Without<Moving>>,
+    coords: Query<&GridCoords>,
+    mut inventories: Query<&mut Inventory>,
+    filters: Query<&StorageFilter>,
+    items: Res<ItemRegistry>,
+) {
+    for (entity, order, unit_coords, move_target) in &units {
+        let Ok(target_coords) = coords.get(order.target) else {
+            info!("<complete_transfer_orders> Target of {:?} is gone", entity);
+            commands.entity(entity).remove::<TransferOrder>();
+            continue;
+        };
+
+        if grid_distance(unit_coords, target_coords) > 1 {
+            // Still waiting on a path, or the walk ended somewhere else
+            if move_target.destination.is_none() && move_target.path.is_empty() {
+                info!(
+                    "<complete_transfer_orders> {:?} couldn't reach {:?}",
+                    entity, order.target
+                );
+                commands.entity(entity).remove::<TransferOrder>();
+            }
+            continue;
+        }
+
+        commands.entity(entity).remove::<TransferOrder>();
+
+        if order.direction == TransferDirection::Give
+            && filters
+                .get(order.target)
+                .is_ok_and(|filter| !filter.allows(&order.item))
+        {
+            info!(
+                "<complete_transfer_orders> {:?} doesn't accept {}",
+                order.target,
+                items.name(&order.item)
+            );
+            continue;
+        }
+
+        let (from, to) = match order.direction {
+            TransferDirection::Give => (entity, order.target),
+            TransferDirection::Take => (order.target, entity),
+        };
+
+        let Ok([mut from_inventory, mut to_inventory]) = inventories.get_many_mut([from, to])
+        else {
+            continue;
+        };
+
+        let available = from_inventory.count_item(&order.item);
+        let wanted = match order.amount {
+            TransferAmount::Exactly(amount) => amount.min(available),
+            TransferAmount::All => available,
+        };
+
+        let moved = from_inventory.transfer_to(&mut to_inventory, &order.item, wanted, &items);
+
+        if moved < wanted {
+            info!(
+                "<complete_transfer_orders> Only {} of {} {} fit, the rest stays with {:?}",
+                moved,
+                wanted,
+                items.name(&order.item),
+                from
+            );
+        } else {
+            info!(
+                "<complete_transfer_orders> Moved {} {} from {:?} to {:?}",
+                moved,
+                items.name(&order.item),
+                from,
+                to
+            );
+        }
+    }
+}
+
+/// Shows the transfer settings for the selected unit
+fn update_transfer_ui(
+    selected: Query<Option<&TransferOrder>, (With<Selected>, With<Character>, With<Inventory>)>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<TransferSettings>,
+    items: Res<ItemRegistry>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok(order) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new("Transfer (T + right-click)"),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                let direction = match settings.direction {
+                    TransferDirection::Give => "Give",
+                    TransferDirection::Take => "Take",
+                };
+
+                for (label, button) in [
+                    (direction.to_string(), TransferSettingButton::Direction),
+                    (settings.amount.label(), TransferSettingButton::Amount),
+                    (
+                        items.name(&settings.item).to_string(),
+                        TransferSettingButton::Item,
+                    ),
+                ] {
+                    row.spawn((
+                        Button,
+                        // Let the panel see the click too, so it doesn't reach the map
+                        FocusPolicy::Pass,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                        button,
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(label),
+                            TextFont {
+                                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                                font_size: 12.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                }
+            });
+
+        if let Some(order) = order {
+            let direction = match order.direction {
+                TransferDirection::Give => "give",
+                TransferDirection::Take => "take",
+            };
+
+            parent.spawn((
+                Text::new(format!(
+                    "Going to {} {} {}",
+                    direction,
+                    order.amount.label(),
+                    items.name(&order.item)
+                )),
+                TextFont {
+                    font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 1.0, 0.0)),
+            ));
+        }
+    });
+}
@@ -1,15 +1,39 @@
 use crate::components::inventory::Inventory;
-use crate::components::ui::{EntityInfoPanel, EntityNameText};
+use crate::components::ui::{BlocksMapClicks, EntityInfoPanel, EntityNameText, HudPanel};
 use crate::components::unit::Selected;
 use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
 
 /// Plugin for the UI system.
 pub struct UiPlugin;
 
+/// Ordering for systems that read or rebuild the entity info panel.
+///
+/// The panel contents are despawned and rebuilt every frame, so button handlers
+/// must run before the rebuild and extra sections must be added after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InfoPanelSet {
+    /// Systems that react to buttons spawned in the previous frame.
+    Input,
+    /// The system that clears the panel and adds the base character info.
+    Rebuild,
+    /// Systems that append their own sections to the panel.
+    Sections,
+}
+
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ui)
-            .add_systems(Update, update_entity_info_panel);
+        app.configure_sets(
+            Update,
+            (
+                InfoPanelSet::Input,
+                InfoPanelSet::Rebuild,
+                InfoPanelSet::Sections,
+            )
+                .chain(),
+        )
+        .add_systems(Startup, setup_ui)
+        .add_systems(Update, update_entity_info_panel);
     }
 }
 
@@ -25,7 +49,7 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 right: Val::Px(10.0),
                 top: Val::Px(10.0),
                 width: Val::Px(200.0),
-                height: Val::Px(120.0),
+                min_height: Val::Px(120.0),
                 padding: UiRect::all(Val::Px(10.0)),
                 flex_direction: FlexDirection::Column,
                 // Start with the panel hidden
@@ -33,6 +57,9 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.8)),
+            // Track the pointer so clicks on the panel don't fall through to the map
+            Interaction::default(),
+            FocusPolicy::Pass,
             EntityInfoPanel,
         ))
         .with_children(|parent| {
@@ -50,6 +77,33 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
 
             // Additional info can be added here in the future
         });
+
+    // Container in the top left for counters like population
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.6)),
+        HudPanel,
+    ));
+}
+
+/// Pointer state of the entity info panel and of menus that block map clicks.
+pub type PanelInteractions<'w, 's> =
+    Query<'w, 's, &'static Interaction, Or<(With<EntityInfoPanel>, With<BlocksMapClicks>)>>;
+
+/// Returns true if the pointer is over the entity info panel or a menu.
+///
+/// Panel buttons use `FocusPolicy::Pass`, so the panel itself still sees the pointer.
+pub fn pointer_over_ui(panel_interactions: &PanelInteractions) -> bool {
+    panel_interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
 }
 
 /// System to update the entity info panel based on selected entities.
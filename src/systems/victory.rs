@@ -0,0 +1,476 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::components::entities::Character;
+use crate::components::items::ItemId;
+use crate::components::unit::Owner;
+use crate::systems::construction::{Building, ConstructionSite};
+use crate::systems::multiplayer::LocalPlayer;
+use crate::systems::simulation::{SimTick, SimulationSet, TICK_RATE};
+use crate::ClientSet;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plugin for winning, losing and the statistics shown at the end of a game.
+///
+/// A player is defeated once they have no units or buildings left. The last
+/// one standing wins, and maps can add other ways to win by placing a
+/// `WinConditions` entity and `Objective` cells in LDtk.
+pub struct VictoryPlugin;
+
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchState>()
+            .init_resource::<MatchStats>()
+            .register_ldtk_entity::<WinConditionsBundle>("WinConditions")
+            .register_ldtk_entity::<ObjectiveBundle>("Objective")
+            .add_systems(Update, reset_match)
+            .add_systems(
+                FixedUpdate,
+                check_victory
+                    .run_if(|state: Res<MatchState>| state.result.is_none())
+                    .in_set(SimulationSet::Resolve),
+            )
+            .add_systems(Startup, setup_results_screen.in_set(ClientSet))
+            .add_systems(Update, show_results.in_set(ClientSet));
+    }
+}
+
+/// Extra ways to win on this map, read from a "WinConditions" LDtk entity.
+///
+/// The fields are all optional: "DestroyBuildings" (Bool), "GoldGoal" (Int)
+/// and "SurviveMinutes" (Float). Reaching a location is set up by placing
+/// "Objective" entities instead.
+#[derive(Component, Debug, Default, Clone)]
+pub struct WinConditions {
+    /// Win by destroying every building the other players have
+    pub destroy_buildings: bool,
+    /// Win by gathering this much gold
+    pub gold_goal: Option<u32>,
+    /// Everyone still standing after this many seconds wins
+    pub survive_seconds: Option<f32>,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+struct WinConditionsBundle {
+    #[with(win_conditions_from_fields)]
+    conditions: WinConditions,
+}
+
+fn win_conditions_from_fields(entity_instance: &EntityInstance) -> WinConditions {
+    WinConditions {
+        destroy_buildings: entity_instance
+            .get_bool_field("DestroyBuildings")
+            .is_ok_and(|destroy| *destroy),
+        gold_goal: entity_instance
+            .get_int_field("GoldGoal")
+            .ok()
+            .filter(|goal| **goal > 0)
+            .map(|goal| *goal as u32),
+        survive_seconds: entity_instance
+            .get_float_field("SurviveMinutes")
+            .ok()
+            .filter(|minutes| **minutes > 0.0)
+            .map(|minutes| minutes * 60.0),
+    }
+}
+
+/// A cell a player wins by getting one of their units to.
+#[derive(Component, Debug, Default)]
+pub struct Objective;
+
+#[derive(Default, Bundle, LdtkEntity)]
+struct ObjectiveBundle {
+    objective: Objective,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+    #[grid_coords]
+    grid_coords: GridCoords,
+}
+
+/// How a game was won.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VictoryReason {
+    LastStanding,
+    DestroyedBuildings,
+    GatheredGold,
+    Survived,
+    ReachedObjective,
+    /// Nobody won, everyone was defeated
+    Draw,
+}
+
+impl VictoryReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            VictoryReason::LastStanding => "the last player standing",
+            VictoryReason::DestroyedBuildings => "destroyed every enemy building",
+            VictoryReason::GatheredGold => "gathered enough gold",
+            VictoryReason::Survived => "survived until the end",
+            VictoryReason::ReachedObjective => "reached the objective",
+            VictoryReason::Draw => "everyone was defeated",
+        }
+    }
+}
+
+/// How the game ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub winners: Vec<Owner>,
+    pub reason: VictoryReason,
+    pub tick: u64,
+}
+
+/// Who is playing, who has lost and whether the game is over.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MatchState {
+    /// Everyone who owned something when the level started
+    pub players: Vec<Owner>,
+    /// Players who are out, and the tick they went out on
+    pub defeated: Vec<(Owner, u64)>,
+    pub result: Option<MatchResult>,
+    // Players who have had a building at some point, so destroying "every
+    // building" can't be won against someone who never built one
+    had_buildings: HashSet<Owner>,
+}
+
+impl MatchState {
+    pub fn is_defeated(&self, player: Owner) -> bool {
+        self.defeated.iter().any(|(owner, _)| *owner == player)
+    }
+}
+
+/// What one player got done over the game.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub gathered: HashMap<ItemId, u32>,
+    pub units_trained: u32,
+    pub buildings_built: u32,
+}
+
+/// Statistics for every player, shown on the results screen.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MatchStats {
+    players: HashMap<Owner, PlayerStats>,
+}
+
+impl MatchStats {
+    pub fn get(&self, player: Owner) -> PlayerStats {
+        self.players.get(&player).cloned().unwrap_or_default()
+    }
+
+    pub fn record_gathered(&mut self, player: Owner, item: &ItemId, quantity: u32) {
+        *self
+            .players
+            .entry(player)
+            .or_default()
+            .gathered
+            .entry(item.clone())
+            .or_insert(0) += quantity;
+    }
+
+    pub fn record_trained(&mut self, player: Owner) {
+        self.players.entry(player).or_default().units_trained += 1;
+    }
+
+    pub fn record_built(&mut self, player: Owner) {
+        self.players.entry(player).or_default().buildings_built += 1;
+    }
+}
+
+/// Starts a fresh game whenever the level is spawned again, a save being
+/// loaded puts its own back once the level is in
+fn reset_match(
+    mut level_events: EventReader<LevelEvent>,
+    mut state: ResMut<MatchState>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for event in level_events.read() {
+        if let LevelEvent::SpawnTriggered(_) = event {
+            *state = MatchState::default();
+            *stats = MatchStats::default();
+        }
+    }
+}
+
+/// Knocks out players with nothing left and ends the game once someone has won
+fn check_victory(
+    mut state: ResMut<MatchState>,
+    stats: Res<MatchStats>,
+    tick: Res<SimTick>,
+    conditions: Query<&WinConditions>,
+    objectives: Query<&GridCoords, With<Objective>>,
+    units: Query<(&Owner, &GridCoords), With<Character>>,
+    buildings: Query<&Owner, Or<(With<Building>, With<ConstructionSite>)>>,
+) {
+    let mut unit_counts: HashMap<Owner, usize> = HashMap::new();
+    for (owner, _) in &units {
+        *unit_counts.entry(*owner).or_default() += 1;
+    }
+    let mut building_counts: HashMap<Owner, usize> = HashMap::new();
+    for owner in &buildings {
+        *building_counts.entry(*owner).or_default() += 1;
+    }
+
+    if state.players.is_empty() {
+        let mut players: Vec<Owner> = unit_counts
+            .keys()
+            .chain(building_counts.keys())
+            .copied()
+            .filter(|owner| *owner != Owner::NEUTRAL)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if players.is_empty() {
+            return;
+        }
+        players.sort_by_key(|owner| owner.0);
+        info!("<check_victory> Players in this game: {:?}", players);
+        state.players = players;
+    }
+
+    let players = state.players.clone();
+    for player in &players {
+        let has_units = unit_counts.get(player).is_some_and(|count| *count > 0);
+        let has_buildings = building_counts.get(player).is_some_and(|count| *count > 0);
+        if has_buildings {
+            state.had_buildings.insert(*player);
+        }
+        if !has_units && !has_buildings && !state.is_defeated(*player) {
+            info!("<check_victory> Player {} was defeated", player.0 + 1);
+            state.defeated.push((*player, tick.0));
+        }
+    }
+
+    let remaining: Vec<Owner> = players
+        .iter()
+        .copied()
+        .filter(|player| !state.is_defeated(*player))
+        .collect();
+    let conditions = conditions.get_single().cloned().unwrap_or_default();
+    let seconds = tick.0 as f64 / TICK_RATE;
+
+    let won = |reason: VictoryReason, winners: Vec<Owner>| MatchResult {
+        winners,
+        reason,
+        tick: tick.0,
+    };
+
+    let result = if remaining.is_empty() {
+        Some(won(VictoryReason::Draw, Vec::new()))
+    } else if players.len() > 1 && remaining.len() == 1 {
+        Some(won(VictoryReason::LastStanding, remaining.clone()))
+    } else if let Some(player) = remaining.iter().find(|player| {
+        conditions
+            .gold_goal
+            .is_some_and(|goal| stats.get(**player).gathered.get(&ItemId::GOLD) >= Some(&goal))
+    }) {
+        Some(won(VictoryReason::GatheredGold, vec![*player]))
+    } else if let Some(player) = remaining.iter().find(|player| {
+        units.iter().any(|(owner, coords)| {
+            owner == *player && objectives.iter().any(|objective| objective == coords)
+        })
+    }) {
+        Some(won(VictoryReason::ReachedObjective, vec![*player]))
+    } else if let Some(player) = remaining.iter().find(|player| {
+        let mut opponents = players.iter().filter(|opponent| opponent != player);
+        conditions.destroy_buildings
+            && opponents
+                .clone()
+                .any(|opponent| state.had_buildings.contains(opponent))
+            && opponents.all(|opponent| !building_counts.contains_key(opponent))
+    }) {
+        Some(won(VictoryReason::DestroyedBuildings, vec![*player]))
+    } else if conditions
+        .survive_seconds
+        .is_some_and(|survive| seconds >= survive as f64)
+    {
+        Some(won(VictoryReason::Survived, remaining.clone()))
+    } else {
+        None
+    };
+
+    if let Some(result) = result {
+        info!(
+            "<check_victory> Game over at tick {}: {:?} won, {}",
+            result.tick,
+            result.winners,
+            result.reason.describe()
+        );
+        state.result = Some(result);
+    }
+}
+
+/// The end of game screen, hidden until someone wins.
+#[derive(Component)]
+struct ResultsScreen;
+
+/// Victory or defeat, from the local player's side.
+#[derive(Component)]
+struct ResultsTitle;
+
+/// How it ended and everyone's statistics.
+#[derive(Component)]
+struct ResultsText;
+
+fn setup_results_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf");
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(25.0),
+                top: Val::Percent(15.0),
+                width: Val::Percent(50.0),
+                padding: UiRect::all(Val::Px(16.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(10.0),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.95)),
+            GlobalZIndex(20),
+            ResultsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 36.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                ResultsTitle,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font,
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                ResultsText,
+            ));
+        });
+}
+
+/// Formats seconds as minutes and seconds
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Everyone's statistics, one block per player
+fn results_text(state: &MatchState, stats: &MatchStats, result: &MatchResult) -> String {
+    let mut lines = vec![
+        format!(
+            "{} after {}",
+            match result.winners.as_slice() {
+                [] => "Nobody won".to_string(),
+                [winner] => format!("Player {} won", winner.0 + 1),
+                winners => format!(
+                    "Players {} won",
+                    winners
+                        .iter()
+                        .map(|winner| (winner.0 + 1).to_string())
+                        .collect::<Vec<_>>()
+                        .join(" and ")
+                ),
+            },
+            format_duration(result.tick as f64 / TICK_RATE)
+        ),
+        format!("Reason: {}", result.reason.describe()),
+    ];
+
+    for player in &state.players {
+        let player_stats = stats.get(*player);
+        let mut gathered: Vec<_> = player_stats.gathered.iter().collect();
+        gathered.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+        let gathered = if gathered.is_empty() {
+            "nothing".to_string()
+        } else {
+            gathered
+                .iter()
+                .map(|(item, quantity)| format!("{} {}", quantity, item))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        lines.push(String::new());
+        lines.push(
+            match state.defeated.iter().find(|(owner, _)| owner == player) {
+                Some((_, tick)) => format!(
+                    "Player {} (defeated at {})",
+                    player.0 + 1,
+                    format_duration(*tick as f64 / TICK_RATE)
+                ),
+                None => format!("Player {}", player.0 + 1),
+            },
+        );
+        lines.push(format!("  Gathered: {}", gathered));
+        lines.push(format!("  Units trained: {}", player_stats.units_trained));
+        lines.push(format!(
+            "  Buildings built: {}",
+            player_stats.buildings_built
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Esc to keep watching, F8 to load a save".to_string());
+    lines.join("\n")
+}
+
+/// Shows the results and pauses once the game is over, Esc hides them again
+fn show_results(
+    state: Res<MatchState>,
+    stats: Res<MatchStats>,
+    local_player: Res<LocalPlayer>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut screens: Query<&mut Node, With<ResultsScreen>>,
+    mut titles: Query<(&mut Text, &mut TextColor), (With<ResultsTitle>, Without<ResultsText>)>,
+    mut texts: Query<&mut Text, (With<ResultsText>, Without<ResultsTitle>)>,
+    mut shown: Local<bool>,
+) {
+    let Ok(mut screen) = screens.get_single_mut() else {
+        return;
+    };
+
+    let Some(result) = &state.result else {
+        // A save was loaded, carry on with the new game
+        if *shown {
+            *shown = false;
+            screen.display = Display::None;
+            time.unpause();
+        }
+        return;
+    };
+
+    if *shown {
+        if keyboard.just_pressed(KeyCode::Escape) {
+            screen.display = Display::None;
+            time.unpause();
+        }
+        return;
+    }
+    *shown = true;
+
+    let (title, color) = if result.winners.contains(&local_player.0) {
+        ("Victory", Color::srgb(1.0, 0.85, 0.3))
+    } else {
+        ("Defeat", Color::srgb(1.0, 0.4, 0.4))
+    };
+    for (mut text, mut text_color) in &mut titles {
+        *text = Text::new(title);
+        text_color.0 = color;
+    }
+    for mut text in &mut texts {
+        *text = Text::new(results_text(&state, &stats, result));
+    }
+
+    screen.display = Display::Flex;
+    time.pause();
+}
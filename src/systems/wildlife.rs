@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use crate::components::combat::Health;
+use crate::components::movement::{Collider, MoveTarget, Moving};
+use crate::components::ui::EntityInfoPanel;
+use crate::components::unit::{Owner, Selected};
+use crate::components::wildlife::{Creature, Temperament};
+use crate::systems::combat::{Attacking, LastAttacker};
+use crate::systems::garrison::Garrisoned;
+use crate::systems::movement::grid_distance;
+use crate::systems::simulation::{SimId, SimTick, SimulationSet, TICK_RATE};
+use crate::systems::ui::InfoPanelSet;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Plugin for the animals roaming the map.
+///
+/// Creatures are ordinary movers and fighters owned by `Owner::NEUTRAL`: they
+/// walk with `MoveTarget`, fight with `Attacking` and drop their `Inventory`
+/// when killed like anything else. This plugin only decides where they go and
+/// who they go for.
+pub struct WildlifePlugin;
+
+impl Plugin for WildlifePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            creature_behaviour.in_set(SimulationSet::Orders),
+        )
+        .add_systems(Update, update_creature_ui.in_set(InfoPanelSet::Sections));
+    }
+}
+
+/// Hostile creatures go for anyone who comes this close, in cells.
+const AGGRO_RADIUS: i32 = 4;
+
+/// Timid creatures keep running until their attacker is this far away.
+const FLEE_DISTANCE: i32 = 6;
+
+/// Furthest a creature wanders from home when nothing is going on.
+const WANDER_RADIUS: i32 = 3;
+
+/// Seconds a creature waits between wanders, give or take half of it.
+const WANDER_INTERVAL: f32 = 8.0;
+
+/// A number that looks random but is the same in every run of the game, so
+/// replays and multiplayer games see animals wander the same way
+fn roll(id: SimId, tick: u64) -> u64 {
+    // splitmix64
+    let mut z = ((u64::from(id.0) << 32) ^ tick).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Wanders, runs, fights and heads home, depending on the creature's temperament
+fn creature_behaviour(
+    mut commands: Commands,
+    mut creatures: Query<
+        (
+            Entity,
+            &mut Creature,
+            &GridCoords,
+            &mut MoveTarget,
+            Option<&SimId>,
+            Option<&LastAttacker>,
+            Has<Attacking>,
+            Has<Moving>,
+        ),
+        Without<Garrisoned>,
+    >,
+    // Anyone a creature could go for, other animals are left alone
+    people: Query<
+        (Entity, &GridCoords, Option<&Owner>),
+        (With<Health>, Without<Garrisoned>, Without<Creature>),
+    >,
+    obstacles: Query<&GridCoords, With<Collider>>,
+    tick: Res<SimTick>,
+) {
+    let blocked: HashSet<GridCoords> = obstacles.iter().copied().collect();
+
+    for (entity, mut creature, coords, mut move_target, sim_id, last_attacker, attacking, moving) in
+        &mut creatures
+    {
+        // Not numbered yet, it'll start next tick
+        let Some(sim_id) = sim_id.copied() else {
+            continue;
+        };
+        let home = *creature.home.get_or_insert(*coords);
+        let from_home = grid_distance(coords, &home);
+
+        // Strayed too far, drop everything and go home
+        if creature.returning || from_home > creature.leash_radius {
+            if from_home <= 1 {
+                creature.returning = false;
+            } else {
+                if !creature.returning {
+                    info!("<creature_behaviour> {:?} is heading home", entity);
+                    creature.returning = true;
+                    commands
+                        .entity(entity)
+                        .remove::<(Attacking, LastAttacker)>();
+                }
+                if !moving && move_target.destination != Some(home) {
+                    move_target.destination = Some(home);
+                    move_target.path.clear();
+                }
+                continue;
+            }
+        }
+
+        let attacker = last_attacker
+            .and_then(|last| people.get(last.attacker).ok())
+            .map(|(attacker, attacker_coords, _)| (attacker, *attacker_coords));
+
+        match (creature.temperament, attacker) {
+            (Temperament::Timid, Some((_, attacker_coords)))
+                if grid_distance(coords, &attacker_coords) < FLEE_DISTANCE =>
+            {
+                if !moving {
+                    // The free cell nearby furthest from the attacker, without leaving home behind
+                    let flee_to = (-2..=2)
+                        .flat_map(|y| (-2..=2).map(move |x| (x, y)))
+                        .map(|(x, y)| GridCoords::new(coords.x + x, coords.y + y))
+                        .filter(|cell| {
+                            !blocked.contains(cell)
+                                && grid_distance(cell, &home) <= creature.leash_radius
+                        })
+                        .max_by_key(|cell| {
+                            (grid_distance(cell, &attacker_coords), -cell.y, -cell.x)
+                        });
+                    if let Some(flee_to) = flee_to {
+                        move_target.destination = Some(flee_to);
+                        move_target.path.clear();
+                    }
+                }
+                continue;
+            }
+            (Temperament::Defensive | Temperament::Hostile, Some((attacker, _))) => {
+                if !attacking {
+                    info!("<creature_behaviour> {:?} turns on {:?}", entity, attacker);
+                    commands.entity(entity).insert(Attacking {
+                        target: attacker,
+                        cooldown: 0.0,
+                    });
+                }
+                continue;
+            }
+            _ => {
+                if last_attacker.is_some() {
+                    commands.entity(entity).remove::<LastAttacker>();
+                }
+            }
+        }
+
+        if attacking {
+            continue;
+        }
+
+        if creature.temperament == Temperament::Hostile {
+            let intruder = people
+                .iter()
+                .filter(|(_, _, owner)| owner.copied().unwrap_or_default() != Owner::NEUTRAL)
+                .filter(|(_, target_coords, _)| {
+                    grid_distance(coords, target_coords) <= AGGRO_RADIUS
+                        && grid_distance(&home, target_coords) <= creature.leash_radius
+                })
+                .min_by_key(|(target, target_coords, _)| {
+                    (grid_distance(coords, target_coords), *target)
+                });
+            if let Some((target, ..)) = intruder {
+                info!("<creature_behaviour> {:?} goes for {:?}", entity, target);
+                commands.entity(entity).insert(Attacking {
+                    target,
+                    cooldown: 0.0,
+                });
+                continue;
+            }
+        }
+
+        // Nothing going on, amble about now and then
+        if moving || move_target.destination.is_some() || tick.0 < creature.next_wander {
+            continue;
+        }
+
+        let roll = roll(sim_id, tick.0);
+        let wait = WANDER_INTERVAL * (0.5 + (roll % 1000) as f32 / 1000.0);
+        creature.next_wander = tick.0 + (wait as f64 * TICK_RATE) as u64;
+
+        let radius = WANDER_RADIUS.min(creature.leash_radius);
+        let span = (radius * 2 + 1) as u64;
+        let wander_to = GridCoords::new(
+            home.x - radius + ((roll >> 16) % span) as i32,
+            home.y - radius + ((roll >> 32) % span) as i32,
+        );
+        if wander_to != *coords && !blocked.contains(&wander_to) {
+            move_target.destination = Some(wander_to);
+            move_target.path.clear();
+        }
+    }
+}
+
+/// Shows what kind of animal the selected creature is
+fn update_creature_ui(
+    selected: Query<&Creature, With<Selected>>,
+    panel_query: Query<Entity, With<EntityInfoPanel>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel_entity) = panel_query.get_single() else {
+        return;
+    };
+
+    let Ok(creature) = selected.get_single() else {
+        return;
+    };
+
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn((
+            Text::new(format!(
+                "Wild {} ({})",
+                creature.kind.name(),
+                creature.temperament.name()
+            )),
+            TextFont {
+                font: asset_server.load("fonts/fira_sans/FiraSans-Bold.ttf"),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::srgb(0.6, 0.9, 0.5)),
+        ));
+    });
+}
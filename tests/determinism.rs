@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::GridCoords;
+use my_rts_game::components::entities::Worker;
+use my_rts_game::components::items::ItemId;
+use my_rts_game::headless::Simulation;
+
+/// Ticks each run lasts
+const TICKS: u64 = 600;
+
+/// Plays the same orders on a fresh game and returns the state hash after every tick
+fn play_script() -> Vec<u64> {
+    let mut simulation = Simulation::new();
+    assert!(
+        simulation.wait_for_level(Duration::from_secs(30)),
+        "the level did not load"
+    );
+
+    let units = simulation.units();
+    assert!(!units.is_empty(), "no units were found");
+
+    let mut hashes = Vec::new();
+    for tick in 0..TICKS {
+        match tick {
+            // Every worker goes for the wood nearest to it
+            0 => {
+                for unit in &units {
+                    if simulation.world_mut().get::<Worker>(*unit).is_none() {
+                        continue;
+                    }
+                    if let Some(forest) = simulation.resource_nodes(&ItemId::WOOD, *unit).first() {
+                        simulation.gather(*unit, *forest);
+                    }
+                }
+            }
+            // Then the first unit is pulled off to walk somewhere else
+            200 => {
+                let coords = *simulation.world_mut().get::<GridCoords>(units[0]).unwrap();
+                simulation.move_to(units[0], GridCoords::new(coords.x + 3, coords.y));
+            }
+            _ => {}
+        }
+
+        simulation.step();
+        hashes.push(simulation.state_hash());
+    }
+    hashes
+}
+
+#[test]
+fn same_orders_play_out_the_same() {
+    let first = play_script();
+    let second = play_script();
+
+    assert_eq!(first.len(), second.len());
+    for (tick, (a, b)) in first.iter().zip(&second).enumerate() {
+        assert_eq!(a, b, "the runs split at tick {}", tick);
+    }
+
+    // Something must actually have happened for the comparison to mean anything
+    assert_ne!(first.first(), first.last());
+}
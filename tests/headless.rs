@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use my_rts_game::components::entities::Worker;
+use my_rts_game::components::inventory::{Inventory, Stockpile};
+use my_rts_game::components::items::ItemId;
+use my_rts_game::headless::Simulation;
+
+/// Wood stored in stockpile chests
+fn stockpiled_wood(world: &mut World) -> u32 {
+    world
+        .query_filtered::<&Inventory, With<Stockpile>>()
+        .iter(world)
+        .flat_map(|inventory| inventory.slots.iter().flatten())
+        .filter(|slot| slot.item == ItemId::WOOD)
+        .map(|slot| slot.quantity)
+        .sum()
+}
+
+#[test]
+fn ordered_worker_fills_the_stockpile() {
+    let mut simulation = Simulation::new();
+    assert!(
+        simulation.wait_for_level(Duration::from_secs(30)),
+        "the level did not load"
+    );
+
+    let units = simulation.units();
+    assert!(!units.is_empty(), "no units were found");
+    let worker = *units
+        .iter()
+        .find(|unit| simulation.world_mut().get::<Worker>(**unit).is_some())
+        .expect("the map has no workers");
+    let forest = *simulation
+        .resource_nodes(&ItemId::WOOD, worker)
+        .first()
+        .expect("the map has no forests");
+
+    let before = stockpiled_wood(simulation.world_mut());
+    simulation.gather(worker, forest);
+
+    // Walking over, filling up and carrying it back to a chest takes a while
+    assert!(
+        simulation.run_until(300.0, |world| stockpiled_wood(world) > before),
+        "no wood reached the stockpile"
+    );
+    assert_eq!(
+        simulation.stockpile().get(&ItemId::WOOD).copied(),
+        Some(stockpiled_wood(simulation.world_mut()))
+    );
+}
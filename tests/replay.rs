@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::GridCoords;
+use my_rts_game::components::entities::Worker;
+use my_rts_game::components::items::ItemId;
+use my_rts_game::components::unit::Unit;
+use my_rts_game::headless::Simulation;
+use my_rts_game::systems::replay::Replay;
+use my_rts_game::systems::simulation::SimId;
+
+/// Where every unit stands, by id so two runs can be compared
+fn unit_positions(world: &mut World) -> HashMap<SimId, GridCoords> {
+    world
+        .query_filtered::<(&SimId, &GridCoords), With<Unit>>()
+        .iter(world)
+        .map(|(id, coords)| (*id, *coords))
+        .collect()
+}
+
+fn loaded_simulation() -> Simulation {
+    let mut simulation = Simulation::new();
+    assert!(
+        simulation.wait_for_level(Duration::from_secs(30)),
+        "the level did not load"
+    );
+    simulation
+}
+
+#[test]
+fn replay_ends_where_the_recording_did() {
+    // Record a short session of gathering and walking
+    let mut recording = loaded_simulation();
+    let units = recording.units();
+    for unit in &units {
+        if recording.world_mut().get::<Worker>(*unit).is_none() {
+            continue;
+        }
+        if let Some(forest) = recording.resource_nodes(&ItemId::WOOD, *unit).first() {
+            recording.gather(*unit, *forest);
+        }
+    }
+    recording.run_for(20.0);
+
+    let coords = *recording.world_mut().get::<GridCoords>(units[0]).unwrap();
+    recording.move_to(units[0], GridCoords::new(coords.x + 3, coords.y));
+    recording.run_for(10.0);
+
+    let replay = recording.recording();
+    assert!(!replay.commands.is_empty(), "no commands were recorded");
+    let stockpile = recording.stockpile();
+    let positions = unit_positions(recording.world_mut());
+
+    // Play it back from the map, through the same file format a player would load
+    let replay_length = replay.length;
+    let replay = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+    let mut playback = Simulation::new();
+    playback.play_replay(replay);
+    assert!(
+        playback.wait_for_level(Duration::from_secs(30)),
+        "the level did not load"
+    );
+
+    // Runs until the recording's last tick, with room to spare if it never gets there
+    for _ in 0..(replay_length * 2) {
+        if playback.replay_finished() {
+            break;
+        }
+        playback.step();
+    }
+    assert!(
+        playback.replay_finished(),
+        "the replay never reached its end"
+    );
+
+    assert_eq!(playback.stockpile(), stockpile);
+    assert_eq!(unit_positions(playback.world_mut()), positions);
+}